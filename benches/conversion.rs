@@ -0,0 +1,86 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use rosc::{OscMessage, OscType};
+use std::hint::black_box;
+use yamaha_rcp_to_osc::{osc_to_rcp, rcp_to_osc, split_respecting_quotes};
+
+/// Representative RCP lines: a short command, a long quoted string, and a
+/// message with many arguments.
+const RCP_LINES: [(&str, &str); 3] = [
+    (
+        "short",
+        "NOTIFY set MIXER:Current/Channel/Fader/Level 0 0 -1000",
+    ),
+    (
+        "long_quoted",
+        r#"OK ssinfo_ex scene_a 5 "A rather long scene name used for the evening show" "With a comment that is also quite long""#,
+    ),
+    (
+        "many_args",
+        "NOTIFY mtrinfo MIXER:Current/Meter/InCh 0 12 34 56 78 90 12 34 56 78 90 12 34 56 78 90 12 34",
+    ),
+];
+
+fn osc_messages() -> [(&'static str, OscMessage); 3] {
+    [
+        (
+            "short",
+            OscMessage {
+                addr: "/set/MIXER:Current/Channel/Fader/Level".to_string(),
+                args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+            },
+        ),
+        (
+            "long_quoted",
+            OscMessage {
+                addr: "/ssrecall_ex/scene_a".to_string(),
+                args: vec![
+                    OscType::Int(5),
+                    OscType::String(
+                        "A rather long scene name used for the evening show".to_string(),
+                    ),
+                ],
+            },
+        ),
+        (
+            "many_args",
+            OscMessage {
+                addr: "/set/MIXER:Current/Channel/Fader/Level".to_string(),
+                args: (0..18).map(|i| OscType::Float(i as f32 * 0.5)).collect(),
+            },
+        ),
+    ]
+}
+
+fn bench_rcp_to_osc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rcp_to_osc");
+    for (name, line) in RCP_LINES {
+        group.bench_function(name, |b| b.iter(|| rcp_to_osc(black_box(line.to_string()))));
+    }
+    group.finish();
+}
+
+fn bench_osc_to_rcp(c: &mut Criterion) {
+    let mut group = c.benchmark_group("osc_to_rcp");
+    for (name, msg) in osc_messages() {
+        group.bench_function(name, |b| b.iter(|| osc_to_rcp(black_box(&msg))));
+    }
+    group.finish();
+}
+
+fn bench_split_respecting_quotes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("split_respecting_quotes");
+    for (name, line) in RCP_LINES {
+        group.bench_function(name, |b| {
+            b.iter(|| split_respecting_quotes(black_box(line)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_rcp_to_osc,
+    bench_osc_to_rcp,
+    bench_split_respecting_quotes
+);
+criterion_main!(benches);
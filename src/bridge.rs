@@ -0,0 +1,1973 @@
+use crate::csv_log::{CsvLog, Direction};
+use crate::loopback::LoopbackTransport;
+use crate::metrics::{Latency, Stats};
+use crate::replay::{Recorder, ReplayTransport};
+use crate::transport::{SocketTransport, Transport, bind_osc, route_osc};
+use crate::{
+    BridgeConfig, ConsoleConfig, ConversionError, ConversionOptions, LogFn, LogLevel, RcpMessage,
+    RcpMessageKind, clamp_osc_value, expand_bulk_get, osc_packet_to_rcp_with, osc_pattern_matches,
+    osc_to_rcp_arg_with, osc_to_rcp_with, rcp_parts_to_osc_with, split_respecting_quotes, unquote,
+};
+use rosc::{OscMessage, OscPacket, OscTime, OscType};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::{broadcast, oneshot};
+use tokio_util::sync::CancellationToken;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// How long [`Bridge::query`] waits for the console to reply by default
+/// ([`BridgeConfig::response_timeout_ms`]).
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many converted messages a [`Bridge::subscribe`] receiver can fall
+/// behind by before it starts missing them.
+pub const SUBSCRIBER_CAPACITY: usize = 1024;
+
+/// Error returned by [`Bridge::query`].
+#[derive(Debug)]
+pub enum QueryError {
+    /// The command could not be written to the console.
+    Io(std::io::Error),
+    /// No matching `OK`/`ERROR` arrived within the timeout.
+    Timeout(Duration),
+    /// The bridge stopped before a reply arrived.
+    Disconnected,
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Io(e) => write!(f, "Failed to write to RCP stream: {}", e),
+            QueryError::Timeout(timeout) => {
+                write!(f, "No response from console within {:?}", timeout)
+            }
+            QueryError::Disconnected => write!(f, "Bridge stopped before a response arrived"),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QueryError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The console model and firmware reported by `devinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleModel {
+    /// The product name, e.g. `QL5`
+    pub name: String,
+    /// The firmware version, e.g. `V5.81`, if the console reported it
+    pub firmware: Option<String>,
+}
+
+/// A command written by [`Bridge::query`] that is waiting for its reply.
+struct PendingQuery {
+    id: u64,
+    /// The command split into tokens, e.g. `["get", "MIXER:...", "0", "0"]`
+    tokens: Vec<String>,
+    reply: oneshot::Sender<RcpMessage>,
+}
+
+impl PendingQuery {
+    /// Whether `response` is the reply to this query.
+    ///
+    /// The console echoes the full command in an `OK` reply (followed by the
+    /// value for a `get`), but an `ERROR` only echoes the verb, so that is all
+    /// that can be matched on.
+    fn matches(&self, response: &RcpMessage) -> bool {
+        match response.kind {
+            RcpMessageKind::Ok => response.args.starts_with(&self.tokens),
+            RcpMessageKind::Error => response.args.first() == self.tokens.first(),
+            RcpMessageKind::Notify => false,
+        }
+    }
+}
+
+struct BridgeInner<T> {
+    /// The configuration in use, replaced as a whole by
+    /// [`Bridge::reload`] so every message is handled with one consistent
+    /// snapshot
+    config: std::sync::RwLock<Arc<BridgeConfig>>,
+    log: Arc<LogFn>,
+    transport: T,
+    running: AtomicBool,
+    /// Whether [`Bridge::run`] still has to make the first connection to
+    /// the console, for bridges from [`Bridge::connect_all`]
+    connect_on_run: AtomicBool,
+    pending: std::sync::Mutex<Vec<PendingQuery>>,
+    next_query_id: AtomicU64,
+    shutdown: CancellationToken,
+    stats: Arc<Stats>,
+    model: std::sync::Mutex<Option<ConsoleModel>>,
+    /// The conversion options in use: the configured ones plus the mapping
+    /// table for the console's model
+    conversion: std::sync::RwLock<Arc<ConversionOptions>>,
+    /// The arguments last sent on each OSC address, for
+    /// [`BridgeConfig::dedup`]
+    last_sent: std::sync::Mutex<HashMap<String, Vec<OscType>>>,
+    /// Ramped parameters, keyed by their RCP command without the value,
+    /// e.g. `set MIXER:Current/InCh/Fader/Level 0 0`
+    ramps: std::sync::Mutex<HashMap<String, Ramp>>,
+    /// The last value passed on for each address and index arguments with a
+    /// deadband, keyed by the address followed by the index arguments
+    deadband_values: std::sync::Mutex<HashMap<String, f64>>,
+    /// The `subscribe` and `mtrstart` commands of the subscriptions started
+    /// over OSC and not stopped since, which are restored after a reconnect
+    subscriptions: std::sync::Mutex<Vec<String>>,
+    /// Commands received over OSC while the console is disconnected, for
+    /// [`BridgeConfig::reconnect_queue_size`]. `None` while connected.
+    queued: std::sync::Mutex<Option<VecDeque<String>>>,
+    /// Every OSC message converted from the console, for
+    /// [`Bridge::subscribe`]
+    converted: broadcast::Sender<OscPacket>,
+    /// Where converted messages are logged, while running with
+    /// [`BridgeConfig::csv_log_path`] set
+    csv_log: std::sync::Mutex<Option<CsvLog>>,
+    /// The next [`ConversionOptions::sequence_numbers`] argument
+    sequence: AtomicU64,
+    /// When the bridge was created, for the uptime in status replies
+    started: Instant,
+    /// Whether an RCP message has been read since connecting; until then,
+    /// other lines are taken to be the console's greeting or prompt
+    greeted: AtomicBool,
+    /// Whether a line has been read from the console since the systemd
+    /// watchdog was last notified
+    #[cfg(feature = "systemd")]
+    watchdog_fed: AtomicBool,
+}
+
+/// How often a ramp writes its next value.
+const RAMP_STEP: Duration = Duration::from_millis(20);
+
+/// A parameter whose value is ramped (see [`BridgeConfig::ramp_ms`]).
+struct Ramp {
+    /// The value last written to or reported by the console
+    current: f64,
+    /// The value the current ramp started from
+    from: f64,
+    target: f64,
+    started: Instant,
+    /// Whether the parameter takes integers rather than decimals
+    integer: bool,
+    /// Whether a task is stepping toward the target
+    active: bool,
+}
+
+/// A connected Yamaha RCP <-> OSC bridge.
+///
+/// [`Bridge::connect`] sets up the sockets and connects to the console, and
+/// [`Bridge::run`] then bridges messages until the connection closes or
+/// [`Bridge::shutdown`] is called. The
+/// bridge is cheap to clone, so one clone can be running while another is
+/// used to [`query`](Bridge::query) the console.
+///
+/// The bridge is generic over its [`Transport`] so it can be driven by a
+/// [`MockTransport`](crate::MockTransport) in tests.
+pub struct Bridge<T: Transport = SocketTransport> {
+    inner: Arc<BridgeInner<T>>,
+}
+
+impl<T: Transport> Clone for Bridge<T> {
+    fn clone(&self) -> Self {
+        Bridge {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl Bridge<SocketTransport> {
+    /// Binds the OSC sockets and connects to the console.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a socket can't be bound or the console can't be
+    /// reached.
+    pub async fn connect(config: BridgeConfig, log: LogFn) -> Result<Self, BoxError> {
+        let log = Arc::new(log);
+        let transport = SocketTransport::connect(&config, Arc::clone(&log)).await?;
+        Ok(Bridge::from_parts(config, transport, log))
+    }
+
+    /// Binds the OSC sockets once and returns a bridge per console in
+    /// [`BridgeConfig::consoles`]. Each bridge is run separately, and
+    /// connects to its console when run, so an unreachable console doesn't
+    /// hold up the others. With [`BridgeConfig::reconnect`] set, a failed
+    /// first connection is retried as a reconnect would be.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the consoles don't have distinct prefixes or a
+    /// socket can't be bound.
+    pub async fn connect_all(config: BridgeConfig, log: LogFn) -> Result<Vec<Self>, BoxError> {
+        let mut configs: Vec<BridgeConfig> = config
+            .consoles
+            .iter()
+            .map(|console| console_config(&config, console))
+            .collect();
+        // The metrics endpoint, recording and CSV log can only belong to one
+        // console
+        for config in configs.iter_mut().skip(1) {
+            config.metrics_addr.clear();
+            config.record_path.clear();
+            config.csv_log_path.clear();
+        }
+        let mut prefixes = Vec::new();
+        for config in &configs {
+            match config.conversion.address_prefix() {
+                Some(prefix) if !prefixes.contains(&prefix) => prefixes.push(prefix),
+                _ => return Err("Each console needs its own OSC address prefix".into()),
+            }
+        }
+
+        let log = Arc::new(log);
+        let (osc_out, osc_in) = bind_osc(&config, &log).await?;
+        let osc_out = Arc::new(osc_out);
+        let mut routes = Vec::new();
+        let mut bridges = Vec::new();
+        for (config, prefix) in configs.into_iter().zip(prefixes) {
+            let (tx, rx) = unbounded_channel();
+            routes.push((prefix, tx));
+
+            let console_log: Arc<LogFn> = Arc::new({
+                let log = Arc::clone(&log);
+                let console_ip = config.console_ip.clone();
+                Box::new(move |level, msg| log(level, format!("{}: {}", console_ip, msg)))
+            });
+            let transport = SocketTransport::unconnected(
+                &config,
+                Arc::clone(&osc_out),
+                rx,
+                Arc::clone(&console_log),
+            );
+            let bridge = Bridge::from_parts(config, transport, console_log);
+            bridge.inner.connect_on_run.store(true, Ordering::Relaxed);
+            bridges.push(bridge);
+        }
+        tokio::spawn(route_osc(osc_in, routes, log));
+        Ok(bridges)
+    }
+}
+
+/// Sleeps for `duration`, or forever if it is `None`.
+async fn sleep_or_forever(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// The names of the settings that differ between `old` and `new`, with
+/// those in nested settings named after their parent, e.g. `deadbands` or
+/// `conversion.type_coercions`.
+fn changed_settings(old: &BridgeConfig, new: &BridgeConfig) -> Vec<String> {
+    use serde_json::Value;
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    let mut changed = Vec::new();
+    for (name, value) in &new {
+        match (old.get(name), value) {
+            (Some(Value::Object(old_fields)), Value::Object(new_fields)) => {
+                for (field, value) in new_fields {
+                    if old_fields.get(field) != Some(value) {
+                        changed.push(format!("{}.{}", name, field));
+                    }
+                }
+            }
+            (old_value, value) if old_value != Some(value) => changed.push(name.clone()),
+            _ => {}
+        }
+    }
+    changed
+}
+
+/// The configuration for one of several consoles: `config` with the
+/// console's address and prefix, and its OSC addresses under that prefix.
+fn console_config(config: &BridgeConfig, console: &ConsoleConfig) -> BridgeConfig {
+    let mut config = BridgeConfig {
+        console_ip: console.console_ip.clone(),
+        rcp_port: console.rcp_port,
+        consoles: Vec::new(),
+        ..config.clone()
+    };
+    config.conversion.osc_address_prefix = console.osc_address_prefix.clone();
+    if !console.source_tag.is_empty() {
+        config.conversion.source_tag = console.source_tag.clone();
+    }
+    if let Some(prefix) = config.conversion.address_prefix() {
+        for address in [
+            &mut config.dump_address,
+            &mut config.panic_address,
+            &mut config.status_address,
+            &mut config.status_query_address,
+            &mut config.status_reply_address,
+            &mut config.model_address,
+            &mut config.raw_address,
+            &mut config.ping_address,
+            &mut config.pong_address,
+        ] {
+            if !address.is_empty() {
+                *address = format!("{}{}", prefix, address);
+            }
+        }
+    }
+    config
+}
+
+impl Bridge<ReplayTransport> {
+    /// Binds the OSC sockets and plays back a recording made with
+    /// [`BridgeConfig::record_path`] in place of the console.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recording can't be opened or a socket can't
+    /// be bound.
+    pub async fn replay(config: BridgeConfig, path: &Path, log: LogFn) -> Result<Self, BoxError> {
+        let log = Arc::new(log);
+        let transport = ReplayTransport::open(&config, path, Arc::clone(&log)).await?;
+        Ok(Bridge::from_parts(config, transport, log))
+    }
+}
+
+impl Bridge<LoopbackTransport> {
+    /// Binds the OSC sockets and answers each command in place of the
+    /// console, so OSC received is converted to RCP and straight back.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a socket can't be bound.
+    pub async fn loopback(config: BridgeConfig, log: LogFn) -> Result<Self, BoxError> {
+        let log = Arc::new(log);
+        let transport = LoopbackTransport::open(&config, Arc::clone(&log)).await?;
+        Ok(Bridge::from_parts(config, transport, log))
+    }
+}
+
+impl<T: Transport> Bridge<T> {
+    /// Creates a bridge over an already-connected transport.
+    pub fn new(config: BridgeConfig, transport: T, log: LogFn) -> Self {
+        Bridge::from_parts(config, transport, Arc::new(log))
+    }
+
+    fn from_parts(config: BridgeConfig, transport: T, log: Arc<LogFn>) -> Self {
+        let conversion = Arc::new(config.conversion.clone());
+        let bridge = Bridge {
+            inner: Arc::new(BridgeInner {
+                config: std::sync::RwLock::new(Arc::new(config)),
+                log,
+                transport,
+                running: AtomicBool::new(false),
+                connect_on_run: AtomicBool::new(false),
+                pending: std::sync::Mutex::new(Vec::new()),
+                next_query_id: AtomicU64::new(0),
+                shutdown: CancellationToken::new(),
+                stats: Arc::new(Stats::default()),
+                model: std::sync::Mutex::new(None),
+                conversion: std::sync::RwLock::new(conversion),
+                last_sent: std::sync::Mutex::new(HashMap::new()),
+                ramps: std::sync::Mutex::new(HashMap::new()),
+                deadband_values: std::sync::Mutex::new(HashMap::new()),
+                converted: broadcast::channel(SUBSCRIBER_CAPACITY).0,
+                subscriptions: std::sync::Mutex::new(Vec::new()),
+                queued: std::sync::Mutex::new(None),
+                sequence: AtomicU64::new(0),
+                started: Instant::now(),
+                csv_log: std::sync::Mutex::new(None),
+                greeted: AtomicBool::new(false),
+                #[cfg(feature = "systemd")]
+                watchdog_fed: AtomicBool::new(false),
+            }),
+        };
+        if !bridge.config().model.is_empty() {
+            bridge.select_mapping_table(&bridge.config().model);
+        }
+        bridge
+    }
+
+    /// Bridges messages until the console closes the connection or an error
+    /// occurs. If [`BridgeConfig::reconnect`] is set, the bridge instead
+    /// reconnects and carries on. A bridge can only be run once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bridge is already running, the first
+    /// connection of a bridge from [`Bridge::connect_all`] fails, or the
+    /// record file or CSV log can't be opened.
+    pub async fn run(&self) -> Result<(), BoxError> {
+        if self.inner.running.swap(true, Ordering::SeqCst) {
+            return Err("Bridge is already running".into());
+        }
+        if self.inner.connect_on_run.load(Ordering::Relaxed) && !self.connect_first().await? {
+            return Ok(());
+        }
+
+        let mut recorder = match self.config().record_path.as_str() {
+            "" => None,
+            path => Some(
+                Recorder::create(path)
+                    .await
+                    .map_err(|e| format!("Failed to open record file {}: {}", path, e))?,
+            ),
+        };
+        let csv_task = match self.config().csv_log_path.as_str() {
+            "" => None,
+            path => {
+                let (csv_log, task) = CsvLog::create(path, Arc::clone(&self.inner.log))
+                    .await
+                    .map_err(|e| format!("Failed to open CSV log {}: {}", path, e))?;
+                *self.inner.csv_log.lock().unwrap() = Some(csv_log);
+                Some(task)
+            }
+        };
+
+        if !self.config().metrics_addr.is_empty() {
+            self.start_metrics().await;
+        }
+        #[cfg(feature = "systemd")]
+        self.start_watchdog();
+
+        // Spawn a task to handle incoming OSC messages
+        let osc_task = tokio::spawn(self.clone().handle_incoming_osc());
+        let shutdown = &self.inner.shutdown;
+
+        let idle_timeout = match self.config().idle_reconnect_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+
+        let gave_up = loop {
+            self.restore_session().await;
+            let mut idle = false;
+
+            loop {
+                // Only the read is cancelled on shutdown, so a line that has
+                // already arrived is always handled in full
+                let result = tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    result = self.inner.transport.read_line() => result,
+                    _ = sleep_or_forever(idle_timeout) => {
+                        self.log(
+                            LogLevel::Warn,
+                            format!("Nothing received from Yamaha RCP for {:?}", idle_timeout.unwrap()),
+                        );
+                        idle = true;
+                        break;
+                    }
+                };
+                match result {
+                    Ok(Some(line)) => {
+                        self.inner
+                            .stats
+                            .bytes_read
+                            .fetch_add(line.len() as u64, Ordering::Relaxed);
+                        #[cfg(feature = "systemd")]
+                        self.inner.watchdog_fed.store(true, Ordering::Relaxed);
+                        if let Some(recorder) = &mut recorder
+                            && let Err(e) = recorder.record(&line).await
+                        {
+                            self.log(
+                                LogLevel::Error,
+                                format!("Failed to write to record file: {}", e),
+                            );
+                        }
+                        self.handle_rcp_line(line).await
+                    }
+                    Ok(None) => {
+                        self.log(LogLevel::Warn, "Connection closed by server".to_string());
+                        break;
+                    }
+                    Err(e) => {
+                        self.log(LogLevel::Error, format!("Failed to receive data: {}", e));
+                        break;
+                    }
+                }
+            }
+
+            // Nothing more will arrive on this connection, so fail any
+            // outstanding queries
+            self.inner.pending.lock().unwrap().clear();
+            self.inner.stats.connected.store(false, Ordering::Relaxed);
+            if self.config().reconnect_queue_size > 0 {
+                self.inner.queued.lock().unwrap().get_or_insert_default();
+            }
+            self.announce_status(false).await;
+
+            // A silent connection is reconnected even without `reconnect`,
+            // as it may just be stuck rather than closed
+            if shutdown.is_cancelled() || !(self.config().reconnect || idle) {
+                break None;
+            }
+            match self.reconnect().await {
+                Ok(true) => {
+                    self.inner.stats.reconnects.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(false) => break None,
+                Err(e) => break Some(e),
+            }
+        };
+
+        // Stop the OSC handler and let any command it is writing finish
+        // before the console connection is closed
+        shutdown.cancel();
+        let _ = osc_task.await;
+        if let Err(e) = self.inner.transport.close().await {
+            self.log(
+                LogLevel::Error,
+                format!("Failed to close RCP stream: {}", e),
+            );
+        }
+
+        // Closing the log lets its task write out the last rows
+        self.inner.csv_log.lock().unwrap().take();
+        if let Some(task) = csv_task {
+            let _ = task.await;
+        }
+
+        gave_up.map_or(Ok(()), Err)
+    }
+
+    /// Runs every bridge in `bridges` until they have all stopped. If one
+    /// fails, the others are shut down rather than left running, and waited
+    /// for as they close their connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first bridge that fails.
+    pub async fn run_all(bridges: &[Self]) -> Result<(), BoxError> {
+        let mut tasks = tokio::task::JoinSet::new();
+        for bridge in bridges {
+            let bridge = bridge.clone();
+            tasks.spawn(async move { bridge.run().await });
+        }
+        let mut first_error = None;
+        while let Some(result) = tasks.join_next().await {
+            let result = result.map_err(BoxError::from).and_then(|result| result);
+            if let Err(e) = result
+                && first_error.is_none()
+            {
+                // The others are left to stop gracefully, closing their
+                // connections and logs
+                for bridge in bridges {
+                    bridge.shutdown();
+                }
+                first_error = Some(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Serves metrics on the configured address until the bridge stops.
+    #[cfg(feature = "metrics")]
+    async fn start_metrics(&self) {
+        let addr = &self.config().metrics_addr;
+        let listener = match tokio::net::TcpListener::bind(addr.as_str()).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to bind metrics address {}: {}", addr, e),
+                );
+                return;
+            }
+        };
+        self.log(
+            LogLevel::Info,
+            format!("Serving metrics on: http://{}/metrics", addr),
+        );
+
+        let bridge = self.clone();
+        tokio::spawn(async move {
+            let stats = Arc::clone(&bridge.inner.stats);
+            let shutdown = bridge.inner.shutdown.clone();
+            if let Err(e) = crate::metrics::serve(listener, stats, shutdown).await {
+                bridge.log(LogLevel::Error, format!("Metrics server failed: {}", e));
+            }
+        });
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    async fn start_metrics(&self) {
+        self.log(
+            LogLevel::Warn,
+            "Metrics address ignored: built without the \"metrics\" feature".to_string(),
+        );
+    }
+
+    /// Notifies the systemd watchdog, if it is enabled, as long as lines
+    /// keep arriving from the console, so systemd restarts a bridge that has
+    /// stopped reading.
+    #[cfg(feature = "systemd")]
+    fn start_watchdog(&self) {
+        let Some(interval) = crate::systemd::watchdog_interval() else {
+            return;
+        };
+        self.log(
+            LogLevel::Debug,
+            format!("Notifying the systemd watchdog every {:?}", interval),
+        );
+
+        let bridge = self.clone();
+        tokio::spawn(async move {
+            let shutdown = bridge.inner.shutdown.clone();
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = ticks.tick() => {}
+                }
+                if bridge.inner.watchdog_fed.swap(false, Ordering::Relaxed) {
+                    bridge.sd_notify("WATCHDOG=1");
+                }
+            }
+        });
+    }
+
+    /// Sends `state` to systemd, logging rather than failing on an error.
+    #[cfg(feature = "systemd")]
+    fn sd_notify(&self, state: &str) {
+        if let Err(e) = crate::systemd::notify(state) {
+            self.log(
+                LogLevel::Warn,
+                format!("Failed to notify systemd of {}: {}", state, e),
+            );
+        }
+    }
+
+    /// Stops a running bridge.
+    ///
+    /// Messages already received are still bridged, the disconnection is
+    /// announced if a status address is configured, and the console
+    /// connection is flushed and closed, after which [`run`](Bridge::run)
+    /// returns.
+    pub fn shutdown(&self) {
+        self.log(LogLevel::Info, "Shutting down".to_string());
+        self.inner.shutdown.cancel();
+    }
+
+    /// Replaces the configuration, e.g. after the config file was edited,
+    /// without dropping the console connection or the OSC sockets, and logs
+    /// which settings changed. Messages already being handled finish with
+    /// the old configuration.
+    ///
+    /// Settings read for each message take effect at once: mappings,
+    /// filters, deadbands, ramps and the bridge's own addresses. Those only
+    /// read when starting or connecting, such as the console and OSC
+    /// addresses and ports, take effect after a restart.
+    ///
+    /// With several consoles, pass the configuration they were connected
+    /// with; each bridge picks out its own console by its IP and port.
+    pub fn reload(&self, config: BridgeConfig) {
+        let old = self.config();
+        let config = if config.consoles.is_empty() {
+            config
+        } else {
+            let console = config.consoles.iter().find(|console| {
+                console.console_ip == old.console_ip && console.rcp_port == old.rcp_port
+            });
+            let Some(console) = console else {
+                self.log(
+                    LogLevel::Warn,
+                    "Not reloading the config: this console is no longer in it".to_string(),
+                );
+                return;
+            };
+            // Only the first console has these, as connect_all decided
+            BridgeConfig {
+                metrics_addr: old.metrics_addr.clone(),
+                record_path: old.record_path.clone(),
+                csv_log_path: old.csv_log_path.clone(),
+                ..console_config(&config, console)
+            }
+        };
+
+        let changed = changed_settings(&old, &config);
+        *self.inner.config.write().unwrap() = Arc::new(config);
+        let model = match self.config().model.as_str() {
+            "" => self.model().map(|model| model.name),
+            model => Some(model.to_string()),
+        };
+        match model {
+            Some(model) => self.select_mapping_table(&model),
+            None => {
+                *self.inner.conversion.write().unwrap() =
+                    Arc::new(self.config().conversion.clone());
+            }
+        }
+
+        if changed.is_empty() {
+            self.log(
+                LogLevel::Info,
+                "Reloaded the config: nothing changed".to_string(),
+            );
+        } else {
+            self.log(
+                LogLevel::Info,
+                format!("Reloaded the config, changed: {}", changed.join(", ")),
+            );
+        }
+    }
+
+    /// Writes `rcp_command` to the console and waits for its `OK` or `ERROR`
+    /// reply.
+    ///
+    /// Replies are matched to the command rather than taken in arrival order,
+    /// so unrelated `NOTIFY`s and replies to OSC-originated commands are
+    /// skipped. Only the first token of an `ERROR` (the verb) is echoed by the
+    /// console, so concurrent queries with the same verb may receive each
+    /// other's errors. The reply is still forwarded over OSC as usual.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueryError::Timeout`] if no reply arrives within
+    /// [`BridgeConfig::response_timeout_ms`], and
+    /// [`QueryError::Disconnected`] if the bridge stops first.
+    pub async fn query(&self, rcp_command: &str) -> Result<RcpMessage, QueryError> {
+        let id = self.inner.next_query_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+
+        // Register before writing so a fast reply can't be missed
+        self.inner.pending.lock().unwrap().push(PendingQuery {
+            id,
+            tokens: split_respecting_quotes(rcp_command.trim()),
+            reply: tx,
+        });
+
+        self.log(LogLevel::Debug, format!("Sending RCP: {}", rcp_command));
+        let timeout = Duration::from_millis(self.config().response_timeout_ms);
+        let result = match self.write_rcp(rcp_command).await {
+            Ok(()) => match tokio::time::timeout(timeout, rx).await {
+                Ok(Ok(message)) => return Ok(message),
+                Ok(Err(_)) => Err(QueryError::Disconnected),
+                Err(_) => Err(QueryError::Timeout(timeout)),
+            },
+            Err(e) => Err(QueryError::Io(e)),
+        };
+
+        // Not left for a late reply to be matched to
+        self.inner.pending.lock().unwrap().retain(|p| p.id != id);
+        result
+    }
+
+    fn log(&self, level: LogLevel, message: String) {
+        (self.inner.log)(level, message);
+    }
+
+    /// Adds a row to the CSV log, if there is one.
+    fn log_csv(&self, direction: Direction, rcp: &str, msg: &OscMessage) {
+        if let Some(csv_log) = &*self.inner.csv_log.lock().unwrap() {
+            csv_log.log(direction, rcp, msg);
+        }
+    }
+
+    /// Writes a single command to the console.
+    async fn write_rcp(&self, rcp_command: &str) -> std::io::Result<()> {
+        self.inner.transport.write_line(rcp_command).await?;
+        self.count_written(std::slice::from_ref(&rcp_command));
+        Ok(())
+    }
+
+    /// Counts the bytes of `rcp_commands` once they have been written.
+    fn count_written(&self, rcp_commands: &[impl AsRef<str>]) {
+        let bytes: usize = rcp_commands.iter().map(|c| c.as_ref().len()).sum();
+        self.inner
+            .stats
+            .bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Writes the configured dump commands to the console so it replies with
+    /// current values, which are then forwarded over OSC as usual.
+    async fn dump(&self) {
+        let commands = &self.config().dump_commands;
+        if commands.is_empty() {
+            self.log(
+                LogLevel::Warn,
+                "Dump requested but no dump commands are configured".to_string(),
+            );
+            return;
+        }
+
+        // A dump is asking for the current values, even unchanged ones
+        self.inner.last_sent.lock().unwrap().clear();
+        self.log(
+            LogLevel::Info,
+            format!("Dump requested: sending {} commands", commands.len()),
+        );
+        self.write_commands(commands).await;
+    }
+
+    /// Writes the configured panic commands to the console in order. Running
+    /// ramps are stopped first, so none of them moves a value after a panic.
+    async fn panic(&self) {
+        let commands = &self.config().panic_commands;
+        if commands.is_empty() {
+            self.log(
+                LogLevel::Warn,
+                "Panic requested but no panic commands are configured".to_string(),
+            );
+            return;
+        }
+
+        // A ramp's task stops once its entry is gone
+        self.inner.ramps.lock().unwrap().clear();
+        self.log(
+            LogLevel::Warn,
+            format!("Panic requested: sending {} commands", commands.len()),
+        );
+        self.write_commands(commands).await;
+    }
+
+    /// Writes the string argument of a message sent to the raw address to the
+    /// console as it is, if [`BridgeConfig::allow_raw`] is set.
+    async fn write_raw(&self, msg: &OscMessage) {
+        if !self.config().allow_raw {
+            self.log(
+                LogLevel::Warn,
+                format!("Ignoring raw RCP on {}: --allow-raw is off", msg.addr),
+            );
+            return;
+        }
+        let command = match msg.args.as_slice() {
+            [OscType::String(command)] if !command.contains(['\r', '\n']) => command,
+            _ => {
+                self.log(
+                    LogLevel::Warn,
+                    format!(
+                        "Ignoring raw RCP on {}: it needs a single string argument holding one \
+                         command",
+                        msg.addr
+                    ),
+                );
+                return;
+            }
+        };
+        self.log(
+            LogLevel::Warn,
+            format!("Sending raw RCP from OSC: {}", command),
+        );
+        self.write_osc_command(command).await;
+    }
+
+    /// Writes `commands` to the console one by one, stopping at the first
+    /// that fails.
+    async fn write_commands(&self, commands: &[String]) {
+        for command in commands {
+            self.log(LogLevel::Debug, format!("Sending RCP: {}", command));
+            if let Err(e) = self.write_rcp(command).await {
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to write to RCP stream: {}", e),
+                );
+                return;
+            }
+        }
+    }
+
+    /// Restores the console session after every connect, the first one
+    /// included. The console forgets subscriptions when the connection
+    /// drops, so the init commands and the subscriptions started over OSC
+    /// are re-sent, the connection is announced and, if configured, current
+    /// values are dumped.
+    async fn restore_session(&self) {
+        self.inner.stats.connected.store(true, Ordering::Relaxed);
+        self.inner.greeted.store(false, Ordering::Relaxed);
+        // Repeating it after a reconnect is harmless
+        #[cfg(feature = "systemd")]
+        self.sd_notify("READY=1");
+        // Values may have changed while disconnected, so resend them all
+        self.inner.last_sent.lock().unwrap().clear();
+        self.inner.deadband_values.lock().unwrap().clear();
+
+        let subscriptions = self.inner.subscriptions.lock().unwrap().clone();
+        for command in self.config().init_commands.iter().chain(&subscriptions) {
+            self.log(LogLevel::Debug, format!("Sending RCP: {}", command));
+            if let Err(e) = self.write_rcp(command).await {
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to write to RCP stream: {}", e),
+                );
+                return;
+            }
+        }
+        self.write_queued().await;
+
+        self.announce_status(true).await;
+
+        if self.config().dump_on_connect {
+            self.dump().await;
+        }
+
+        // The reply arrives through the read loop, so this can't be awaited
+        // here
+        if self.config().detect_model {
+            tokio::spawn(self.clone().detect_model());
+        }
+    }
+
+    /// The configuration currently in use.
+    fn config(&self) -> Arc<BridgeConfig> {
+        Arc::clone(&self.inner.config.read().unwrap())
+    }
+
+    /// The conversion options currently in use.
+    fn conversion(&self) -> Arc<ConversionOptions> {
+        Arc::clone(&self.inner.conversion.read().unwrap())
+    }
+
+    /// Switches to the first mapping table for `model`, or to none if no
+    /// table matches.
+    fn select_mapping_table(&self, model: &str) {
+        let config = self.config();
+        let mut conversion = config.conversion.clone();
+        match config
+            .mapping_tables
+            .iter()
+            .find(|table| table.matches(model))
+        {
+            Some(table) => {
+                self.log(
+                    LogLevel::Info,
+                    format!("Using mapping table {} for model {}", table.name, model),
+                );
+                // Table entries come first so they win over the generic ones
+                conversion.parameter_mappings =
+                    [table.mappings.clone(), conversion.parameter_mappings].concat();
+            }
+            None if !config.mapping_tables.is_empty() => {
+                self.log(
+                    LogLevel::Warn,
+                    format!("No mapping table for model {}", model),
+                );
+            }
+            None => {}
+        }
+        *self.inner.conversion.write().unwrap() = Arc::new(conversion);
+    }
+
+    /// Subscribes to the OSC messages converted from the console, after
+    /// filtering and dedup, so they can be processed or sent on some other
+    /// way. Each is delivered to every subscriber as well as sent over the
+    /// bridge's own OSC transport.
+    ///
+    /// The bridge never waits for subscribers: one that falls more than
+    /// [`SUBSCRIBER_CAPACITY`] messages behind misses the oldest, and its
+    /// next `recv` returns [`RecvError::Lagged`](broadcast::error::RecvError::Lagged)
+    /// with the number skipped. Only messages converted after subscribing
+    /// are received.
+    pub fn subscribe(&self) -> broadcast::Receiver<OscPacket> {
+        self.inner.converted.subscribe()
+    }
+
+    /// The bridge's counters, which keep counting as it runs. The same
+    /// counters are served on [`BridgeConfig::metrics_addr`].
+    pub fn stats(&self) -> Arc<Stats> {
+        Arc::clone(&self.inner.stats)
+    }
+
+    /// The console model detected on the most recent connect, if any.
+    pub fn model(&self) -> Option<ConsoleModel> {
+        self.inner.model.lock().unwrap().clone()
+    }
+
+    /// Asks the console for its model and firmware with `devinfo`, stores
+    /// them and sends them to the OSC application.
+    async fn detect_model(self) {
+        let name = match self.query("devinfo productname").await {
+            Ok(reply) if reply.kind == RcpMessageKind::Ok && reply.args.len() > 2 => {
+                unquote(&reply.args[2])
+            }
+            Ok(reply) => {
+                self.log(
+                    LogLevel::Warn,
+                    format!("Console did not report its model: {}", reply),
+                );
+                return;
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Warn,
+                    format!("Failed to detect console model: {}", e),
+                );
+                return;
+            }
+        };
+        let firmware = match self.query("devinfo version").await {
+            Ok(reply) if reply.kind == RcpMessageKind::Ok && reply.args.len() > 2 => {
+                Some(unquote(&reply.args[2]))
+            }
+            _ => None,
+        };
+
+        self.log(
+            LogLevel::Info,
+            match &firmware {
+                Some(firmware) => format!("Detected console model: {} ({})", name, firmware),
+                None => format!("Detected console model: {}", name),
+            },
+        );
+
+        let address = &self.config().model_address;
+        let mut args = vec![OscType::String(name.clone())];
+        args.extend(firmware.iter().cloned().map(OscType::String));
+        // A configured model overrides the detected one
+        if self.config().model.is_empty() {
+            self.select_mapping_table(&name);
+        }
+        *self.inner.model.lock().unwrap() = Some(ConsoleModel { name, firmware });
+
+        if !address.is_empty() {
+            let packet = OscPacket::Message(OscMessage {
+                addr: address.clone(),
+                args,
+            });
+            if let Err(e) = self.inner.transport.send(&packet).await {
+                self.log(LogLevel::Error, format!("Failed to send OSC: {}", e));
+            }
+        }
+    }
+
+    /// Sends the connection state to the OSC application, if a status
+    /// address is configured.
+    async fn announce_status(&self, connected: bool) {
+        let address = &self.config().status_address;
+        if address.is_empty() {
+            return;
+        }
+
+        let packet = OscPacket::Message(OscMessage {
+            addr: address.clone(),
+            args: vec![OscType::Int(connected as i32)],
+        });
+        if let Err(e) = self.inner.transport.send(&packet).await {
+            self.log(LogLevel::Error, format!("Failed to send OSC: {}", e));
+        }
+    }
+
+    /// Sends a line received from the console, as is, on the raw address.
+    async fn echo_raw(&self, line: &str) {
+        let address = &self.config().raw_address;
+        if address.is_empty() {
+            return;
+        }
+
+        let packet = OscPacket::Message(OscMessage {
+            addr: address.clone(),
+            args: vec![OscType::String(line.to_string())],
+        });
+        if let Err(e) = self.inner.transport.send(&packet).await {
+            self.log(LogLevel::Error, format!("Failed to send OSC: {}", e));
+        }
+    }
+
+    /// Answers a ping on the pong address, echoing its arguments.
+    async fn pong(&self, args: Vec<OscType>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.config().pong_address.clone(),
+            args,
+        });
+        if let Err(e) = self.inner.transport.send(&packet).await {
+            self.log(LogLevel::Error, format!("Failed to send OSC: {}", e));
+        }
+    }
+
+    /// Answers a status query on the status reply address with the
+    /// connection state, uptime in seconds and reconnect count.
+    async fn reply_status(&self) {
+        let stats = &self.inner.stats;
+        let uptime = self.inner.started.elapsed().as_secs();
+        let reconnects = stats.reconnects.load(Ordering::Relaxed);
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.config().status_reply_address.clone(),
+            args: vec![
+                OscType::Int(stats.connected.load(Ordering::Relaxed) as i32),
+                OscType::Int(i32::try_from(uptime).unwrap_or(i32::MAX)),
+                OscType::Int(i32::try_from(reconnects).unwrap_or(i32::MAX)),
+            ],
+        });
+        if let Err(e) = self.inner.transport.send(&packet).await {
+            self.log(LogLevel::Error, format!("Failed to send OSC: {}", e));
+        }
+    }
+
+    /// Makes the first connection to the console, retrying it as
+    /// [`reconnect`](Bridge::reconnect) does if [`BridgeConfig::reconnect`]
+    /// is set. Returns `false` if the bridge was shut down first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails and isn't retried, or the
+    /// retries give up.
+    async fn connect_first(&self) -> Result<bool, BoxError> {
+        let config = self.config();
+        self.log(
+            LogLevel::Info,
+            format!("Attempting to connect to Yamaha RCP: {}", config.console_ip),
+        );
+        let result = tokio::select! {
+            _ = self.inner.shutdown.cancelled() => return Ok(false),
+            result = self.inner.transport.reconnect() => result,
+        };
+        match result {
+            Ok(()) => {
+                self.log(
+                    LogLevel::Info,
+                    format!("Connected to Yamaha RCP: {}", config.console_ip),
+                );
+                Ok(true)
+            }
+            Err(e) if config.reconnect && e.kind() != std::io::ErrorKind::PermissionDenied => {
+                self.log(LogLevel::Warn, format!("Failed to connect: {}", e));
+                self.reconnect().await
+            }
+            Err(e) => Err(format!(
+                "Failed to connect to Yamaha RCP at {}: {}",
+                config.console_ip, e
+            )
+            .into()),
+        }
+    }
+
+    /// Retries the console connection until it succeeds, doubling the delay
+    /// between attempts up to the configured maximum. Returns `false` if the
+    /// bridge was shut down first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error once [`BridgeConfig::reconnect_max_attempts`]
+    /// attempts have failed.
+    async fn reconnect(&self) -> Result<bool, BoxError> {
+        let config = self.config();
+        let max_delay = Duration::from_millis(config.reconnect_max_delay_ms);
+        let mut delay = Duration::from_millis(config.reconnect_delay_ms);
+        let mut attempts = 0;
+
+        loop {
+            let jittered = jitter(delay, config.reconnect_jitter);
+            self.log(
+                LogLevel::Info,
+                format!("Reconnecting to Yamaha RCP in {:?}", jittered),
+            );
+            let result = tokio::select! {
+                _ = self.inner.shutdown.cancelled() => return Ok(false),
+                result = async {
+                    tokio::time::sleep(jittered).await;
+                    self.inner.transport.reconnect().await
+                } => result,
+            };
+
+            match result {
+                Ok(()) => {
+                    self.log(
+                        LogLevel::Info,
+                        format!("Reconnected to Yamaha RCP: {}", config.console_ip),
+                    );
+                    return Ok(true);
+                }
+                // Retrying with the same credentials would only be rejected
+                // again
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    return Err(format!(
+                        "Failed to log in to Yamaha RCP at {}: {}",
+                        config.console_ip, e
+                    )
+                    .into());
+                }
+                Err(e) => {
+                    self.log(LogLevel::Warn, format!("Failed to reconnect: {}", e));
+                    attempts += 1;
+                    if attempts == config.reconnect_max_attempts {
+                        return Err(format!(
+                            "Gave up reconnecting to Yamaha RCP at {} after {} attempts: {}",
+                            config.console_ip, attempts, e
+                        )
+                        .into());
+                    }
+                    delay = (delay * 2).min(max_delay);
+                }
+            }
+        }
+    }
+
+    /// Hands a reply to the first pending query it belongs to, if any.
+    fn resolve_query(&self, message: &RcpMessage) {
+        let mut pending = self.inner.pending.lock().unwrap();
+        if let Some(index) = pending.iter().position(|p| p.matches(message)) {
+            let _ = pending.remove(index).reply.send(message.clone());
+        }
+    }
+
+    async fn handle_rcp_line(&self, line: String) {
+        let started = self.latency_start();
+        let config = self.config();
+        // Split once and reuse the parts for everything below, as meter
+        // NOTIFYs can arrive in floods
+        let parts = split_respecting_quotes(line.trim());
+
+        if parts.is_empty() {
+            return;
+        }
+
+        self.log(LogLevel::Debug, format!("Received RCP: {}", line.trim()));
+        if config.echo_raw {
+            self.echo_raw(line.trim()).await;
+        }
+
+        let kind = RcpMessage::from_parts(&parts).ok().map(|message| {
+            self.resolve_query(&message);
+            message.kind
+        });
+
+        let osc_message = match rcp_parts_to_osc_with(&parts, &self.conversion()) {
+            Ok(cmd) => {
+                self.inner.greeted.store(true, Ordering::Relaxed);
+                cmd
+            }
+            // Some consoles greet a new connection with a banner or prompt
+            Err(ConversionError::UnsupportedMessageType(_))
+                if !self.inner.greeted.load(Ordering::Relaxed) =>
+            {
+                self.log(
+                    LogLevel::Debug,
+                    format!("Ignored non-RCP line on connect: {}", line.trim()),
+                );
+                return;
+            }
+            Err(e) => {
+                self.inner
+                    .stats
+                    .conversion_errors
+                    .fetch_add(1, Ordering::Relaxed);
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to convert RCP to OSC: {}", e),
+                );
+                return;
+            }
+        };
+
+        //This is a special work around for the Yamaha RCP
+        //The Yamaha RCP does not show all of the 'scene' data needed in sscurrent_ex
+        //So we need to send the ssinfo_ex command to get the current scene information
+        if kind == Some(RcpMessageKind::Notify) && parts[1].as_str() == "sscurrent_ex" {
+            let rcp_command = format!("ssinfo_ex {}", parts[2..].join(" "));
+
+            if let Err(e) = self.write_rcp(&rcp_command).await {
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to write to RCP stream: {}", e),
+                );
+            }
+        }
+
+        if let Some(kind) = kind
+            && !config.forward_types.contains(&kind)
+        {
+            self.inner.stats.filtered.fetch_add(1, Ordering::Relaxed);
+            self.log(
+                LogLevel::Debug,
+                format!("Not forwarding {} message: {}", kind, osc_message.addr),
+            );
+            return;
+        }
+
+        if !config.osc_out_filter.allows(&osc_message.addr) {
+            self.inner.stats.filtered.fetch_add(1, Ordering::Relaxed);
+            self.log(
+                LogLevel::Debug,
+                format!("Filtered out OSC: {}", osc_message.addr),
+            );
+            return;
+        }
+
+        if kind == Some(RcpMessageKind::Notify) && self.is_ramped(&osc_message.addr) {
+            self.track_ramp_value(&parts);
+        }
+
+        if self.is_duplicate(&osc_message) {
+            self.log(
+                LogLevel::Debug,
+                format!("Skipping duplicate OSC: {}", osc_message),
+            );
+            return;
+        }
+
+        let mut osc_message = osc_message;
+        if self.conversion().sequence_numbers {
+            let sequence = self.inner.sequence.fetch_add(1, Ordering::Relaxed);
+            osc_message.args.push(OscType::Long(sequence as i64));
+        }
+
+        self.log(LogLevel::Debug, format!("Sending OSC: {}", osc_message));
+
+        self.log_csv(Direction::RcpToOsc, &line, &osc_message);
+
+        // Convert to packet and send
+        let packet = OscPacket::Message(osc_message);
+        if self.inner.converted.receiver_count() > 0 {
+            // Only fails if every subscriber has just gone
+            let _ = self.inner.converted.send(packet.clone());
+        }
+        match self.inner.transport.send(&packet).await {
+            Ok(()) => {
+                self.inner.stats.rcp_to_osc.fetch_add(1, Ordering::Relaxed);
+                let latency = &self.inner.stats.rcp_to_osc_latency;
+                self.record_latency(latency, Direction::RcpToOsc, started);
+            }
+            Err(e) => self.log(LogLevel::Error, format!("Failed to send OSC: {}", e)),
+        }
+    }
+
+    /// Whether `msg` repeats the last message sent on its address, when
+    /// [`BridgeConfig::dedup`] is set. Otherwise it is remembered as the
+    /// last one.
+    fn is_duplicate(&self, msg: &OscMessage) -> bool {
+        if !self.config().dedup {
+            return false;
+        }
+        let mut last_sent = self.inner.last_sent.lock().unwrap();
+        if last_sent.get(&msg.addr) == Some(&msg.args) {
+            return true;
+        }
+        last_sent.insert(msg.addr.clone(), msg.args.clone());
+        false
+    }
+
+    /// Handles a message sent to one of the bridge's own addresses (see
+    /// [`is_trigger`]).
+    async fn handle_trigger(&self, config: &BridgeConfig, msg: &OscMessage) {
+        if msg.addr == config.dump_address {
+            self.dump().await;
+        } else if msg.addr == config.panic_address {
+            self.panic().await;
+        } else if msg.addr == config.raw_address {
+            self.write_raw(msg).await;
+        } else if msg.addr == config.ping_address {
+            self.pong(msg.args.clone()).await;
+        } else if msg.addr == config.status_query_address {
+            self.reply_status().await;
+        }
+    }
+
+    /// Removes the messages `osc_in_filter` denies from `packet`, logging
+    /// each one, and those within a deadband. Returns `None` if nothing is
+    /// left.
+    fn filter_incoming(&self, packet: OscPacket) -> Option<OscPacket> {
+        match packet {
+            OscPacket::Message(msg) => {
+                if self.config().osc_in_filter.allows(&msg.addr) {
+                    if self.within_deadband(&msg) {
+                        self.log(
+                            LogLevel::Debug,
+                            format!("Skipping OSC within deadband: {}", msg),
+                        );
+                        return None;
+                    }
+                    return Some(OscPacket::Message(msg));
+                }
+                self.inner.stats.filtered.fetch_add(1, Ordering::Relaxed);
+                self.log(
+                    LogLevel::Warn,
+                    format!("Dropped OSC message to denied address {}", msg.addr),
+                );
+                None
+            }
+            OscPacket::Bundle(mut bundle) => {
+                bundle.content = bundle
+                    .content
+                    .into_iter()
+                    .filter_map(|packet| self.filter_incoming(packet))
+                    .collect();
+                (!bundle.content.is_empty()).then_some(OscPacket::Bundle(bundle))
+            }
+        }
+    }
+
+    /// Whether the value in `msg` is too close to the last one passed on for
+    /// its parameter to be worth writing, by [`BridgeConfig::deadbands`].
+    /// Otherwise it is remembered as the last one.
+    fn within_deadband(&self, msg: &OscMessage) -> bool {
+        let config = self.config();
+        let Some(deadband) = config
+            .deadbands
+            .iter()
+            .find(|deadband| osc_pattern_matches(&deadband.address, &msg.addr))
+        else {
+            return false;
+        };
+        let Some((value, index_args)) = msg.args.split_last() else {
+            return false;
+        };
+        let value = match value {
+            OscType::Int(i) => *i as f64,
+            OscType::Long(l) => *l as f64,
+            OscType::Float(f) => *f as f64,
+            OscType::Double(d) => *d,
+            _ => return false,
+        };
+        let key = format!("{} {:?}", msg.addr, index_args);
+        let mut values = self.inner.deadband_values.lock().unwrap();
+        if let Some(last) = values.get(&key)
+            && (value - last).abs() <= deadband.threshold
+        {
+            return true;
+        }
+        values.insert(key, value);
+        false
+    }
+
+    /// Whether values sent to or from `address` are ramped.
+    fn is_ramped(&self, address: &str) -> bool {
+        let config = self.config();
+        config.ramp_ms > 0
+            && config
+                .ramp_addresses
+                .iter()
+                .any(|pattern| osc_pattern_matches(pattern, address))
+    }
+
+    /// Records the value in a NOTIFY for a ramped parameter as its current
+    /// value, so the next ramp starts from it. Ignored mid-ramp, where the
+    /// ramp itself is the most recent value.
+    fn track_ramp_value(&self, parts: &[String]) {
+        let Some((value, command)) = parts[1..].split_last() else {
+            return;
+        };
+        let Ok(value) = value.parse::<f64>() else {
+            return;
+        };
+        let mut ramps = self.inner.ramps.lock().unwrap();
+        let ramp = ramps.entry(command.join(" ")).or_insert(Ramp {
+            current: value,
+            from: value,
+            target: value,
+            started: Instant::now(),
+            integer: !parts[parts.len() - 1].contains('.'),
+            active: false,
+        });
+        if !ramp.active {
+            ramp.current = value;
+        }
+    }
+
+    /// Moves a parameter toward the value in `msg` over
+    /// [`BridgeConfig::ramp_ms`], starting a ramp task if there isn't one
+    /// already. The first value for a parameter is written directly, as
+    /// there is nothing to ramp from.
+    async fn ramp(&self, msg: &OscMessage) {
+        let command = match osc_to_rcp_with(msg, &self.conversion()) {
+            Ok(command) => {
+                // Logged with the target value rather than each step
+                self.log_csv(Direction::OscToRcp, &command, msg);
+                command
+            }
+            Err(e) => {
+                self.inner
+                    .stats
+                    .conversion_errors
+                    .fetch_add(1, Ordering::Relaxed);
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to convert OSC to RCP: {}", e),
+                );
+                return;
+            }
+        };
+        let target = command
+            .rsplit_once(' ')
+            .and_then(|(key, value)| Some((key, value, value.parse::<f64>().ok()?)));
+        let Some((key, value, target)) = target else {
+            // Not a numeric value, so there is nothing to ramp
+            self.write_osc_command(&command).await;
+            return;
+        };
+
+        let start_task = {
+            let mut ramps = self.inner.ramps.lock().unwrap();
+            match ramps.get_mut(key) {
+                Some(ramp) => {
+                    ramp.from = ramp.current;
+                    ramp.target = target;
+                    ramp.started = Instant::now();
+                    ramp.integer = !value.contains('.');
+                    Some(!std::mem::replace(&mut ramp.active, true))
+                }
+                None => {
+                    ramps.insert(
+                        key.to_string(),
+                        Ramp {
+                            current: target,
+                            from: target,
+                            target,
+                            started: Instant::now(),
+                            integer: !value.contains('.'),
+                            active: false,
+                        },
+                    );
+                    None
+                }
+            }
+        };
+        match start_task {
+            Some(true) => {
+                tokio::spawn(self.clone().run_ramp(key.to_string()));
+            }
+            // A running task picks up the new target on its next step
+            Some(false) => {}
+            None => self.write_osc_command(&command).await,
+        }
+    }
+
+    /// Writes the steps of the ramp for `key` until it reaches its target.
+    async fn run_ramp(self, key: String) {
+        let duration = Duration::from_millis(self.config().ramp_ms).as_secs_f64();
+        loop {
+            let (value, integer, done) = {
+                let mut ramps = self.inner.ramps.lock().unwrap();
+                let Some(ramp) = ramps.get_mut(&key) else {
+                    return;
+                };
+                let progress = (ramp.started.elapsed().as_secs_f64() / duration).min(1.0);
+                let mut value = ramp.from + (ramp.target - ramp.from) * progress;
+                if ramp.integer {
+                    value = value.round();
+                }
+                let done = progress >= 1.0;
+                ramp.active = !done;
+                let changed = value != ramp.current;
+                ramp.current = value;
+                (changed.then_some(value), ramp.integer, done)
+            };
+
+            if let Some(value) = value {
+                let value = if integer {
+                    (value as i64).to_string()
+                } else {
+                    osc_to_rcp_arg_with(&OscType::Float(value as f32), &self.conversion())
+                        .unwrap_or_else(|_| value.to_string())
+                };
+                self.write_osc_command(&format!("{} {}", key, value)).await;
+            }
+            if done {
+                return;
+            }
+            tokio::select! {
+                _ = self.inner.shutdown.cancelled() => return,
+                _ = tokio::time::sleep(RAMP_STEP) => {}
+            }
+        }
+    }
+
+    /// Writes a command converted from OSC to the console.
+    async fn write_osc_command(&self, rcp_command: &str) {
+        if self.queue(&[rcp_command.to_string()], false) {
+            return;
+        }
+        self.log(LogLevel::Debug, format!("Sending RCP: {}", rcp_command));
+        match self.write_rcp(rcp_command).await {
+            Ok(()) => {
+                self.track_subscription(rcp_command);
+                self.inner.stats.osc_to_rcp.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to write to RCP stream: {}", e),
+                );
+                self.queue(&[rcp_command.to_string()], true);
+            }
+        }
+    }
+
+    /// Adds `rcp_commands` to the reconnect queue if the console is
+    /// disconnected, or if `failed` (their write just failed, so the
+    /// connection is about to be found closed) and the queue is enabled.
+    /// Returns whether they were queued.
+    fn queue(&self, rcp_commands: &[String], failed: bool) -> bool {
+        let size = self.config().reconnect_queue_size;
+        if size == 0 {
+            return false;
+        }
+        let mut queued = self.inner.queued.lock().unwrap();
+        let queue = match queued.as_mut() {
+            Some(queue) => queue,
+            None if failed => queued.insert(VecDeque::new()),
+            None => return false,
+        };
+        for rcp_command in rcp_commands {
+            if queue.len() == size
+                && let Some(dropped) = queue.pop_front()
+            {
+                self.log(
+                    LogLevel::Warn,
+                    format!("Reconnect queue full, dropping: {}", dropped),
+                );
+            }
+            self.log(
+                LogLevel::Debug,
+                format!("Queueing RCP until reconnected: {}", rcp_command),
+            );
+            queue.push_back(rcp_command.clone());
+        }
+        true
+    }
+
+    /// Writes the commands queued while the console was disconnected, in
+    /// the order they arrived, and stops queueing. Commands that arrive
+    /// meanwhile are queued behind them, so none can overtake.
+    async fn write_queued(&self) {
+        loop {
+            let commands: Vec<String> = {
+                let mut queued = self.inner.queued.lock().unwrap();
+                match queued.as_mut() {
+                    Some(queue) if !queue.is_empty() => queue.drain(..).collect(),
+                    _ => {
+                        *queued = None;
+                        return;
+                    }
+                }
+            };
+            self.log(
+                LogLevel::Info,
+                format!(
+                    "Sending {} commands queued while disconnected",
+                    commands.len()
+                ),
+            );
+            for command in &commands {
+                self.log(LogLevel::Debug, format!("Sending RCP: {}", command));
+            }
+            if let Err(e) = self.inner.transport.write_lines(&commands).await {
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to write to RCP stream: {}", e),
+                );
+                // Kept for the next reconnect, ahead of anything newer
+                let mut queued = self.inner.queued.lock().unwrap();
+                let queue = queued.get_or_insert_default();
+                for command in commands.into_iter().rev() {
+                    queue.push_front(command);
+                }
+                return;
+            }
+            self.count_written(&commands);
+            for command in &commands {
+                self.track_subscription(command);
+            }
+            self.inner
+                .stats
+                .osc_to_rcp
+                .fetch_add(commands.len() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Logs a warning for each value in `packet` that will be clamped into
+    /// its configured range when converted.
+    fn warn_out_of_range(&self, packet: &OscPacket) {
+        let options = &self.conversion();
+        if options.value_ranges.is_empty() {
+            return;
+        }
+
+        match packet {
+            OscPacket::Message(msg) => {
+                if let Some((original, clamped)) = clamp_osc_value(&mut msg.clone(), options) {
+                    self.log(
+                        LogLevel::Warn,
+                        format!(
+                            "Clamped out-of-range value for {}: {:?} -> {:?}",
+                            msg.addr, original, clamped
+                        ),
+                    );
+                }
+            }
+            OscPacket::Bundle(bundle) => {
+                for packet in &bundle.content {
+                    self.warn_out_of_range(packet);
+                }
+            }
+        }
+    }
+
+    async fn handle_incoming_osc(self) {
+        loop {
+            let result = tokio::select! {
+                _ = self.inner.shutdown.cancelled() => break,
+                result = self.inner.transport.recv() => result,
+            };
+            let started = self.latency_start();
+            let packet = match result {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(e) => {
+                    self.log(
+                        LogLevel::Error,
+                        format!("Error receiving OSC message: {}", e),
+                    );
+                    break;
+                }
+            };
+            match &packet {
+                OscPacket::Message(msg) => {
+                    self.log(LogLevel::Debug, format!("Received OSC: {}", msg));
+                }
+                // Not logged with Display, which panics on timetags before
+                // the Unix epoch such as the common "immediately" (0, 1)
+                OscPacket::Bundle(bundle) => self.log(
+                    LogLevel::Debug,
+                    format!("Received OSC bundle of {} packets", bundle.content.len()),
+                ),
+            }
+            // One snapshot for the whole packet, in case of a reload
+            let config = self.config();
+            let Some(packet) = self.filter_incoming(packet) else {
+                continue;
+            };
+            // Messages to the bridge's own addresses are handled first, in
+            // order, wherever they are in a bundle, and never converted
+            let mut triggers = Vec::new();
+            let packet = take_triggers(&config, packet, &mut triggers);
+            for msg in &triggers {
+                self.handle_trigger(&config, msg).await;
+            }
+            let Some(packet) = packet else {
+                continue;
+            };
+
+            if let OscPacket::Message(msg) = &packet
+                && self.is_ramped(&msg.addr)
+            {
+                self.ramp(msg).await;
+                continue;
+            }
+
+            self.warn_out_of_range(&packet);
+
+            let rcp_commands = match osc_packet_to_rcp_with(&packet, &self.conversion()) {
+                Ok(commands) => commands,
+                Err(e) => {
+                    self.inner
+                        .stats
+                        .conversion_errors
+                        .fetch_add(1, Ordering::Relaxed);
+                    self.log(
+                        LogLevel::Error,
+                        format!("Failed to convert OSC to RCP: {}", e),
+                    );
+                    continue;
+                }
+            };
+
+            if let OscPacket::Bundle(bundle) = &packet
+                && config.honor_timetags
+                && let Some(delay) = timetag_delay(bundle.timetag)
+            {
+                self.log(
+                    LogLevel::Debug,
+                    format!(
+                        "Scheduled OSC bundle of {} packets in {} ms",
+                        bundle.content.len(),
+                        delay.as_millis()
+                    ),
+                );
+                let bridge = self.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        _ = bridge.inner.shutdown.cancelled() => {}
+                        _ = tokio::time::sleep(delay) => {
+                            bridge.write_rcp_commands(&packet, &rcp_commands).await;
+                        }
+                    }
+                });
+                continue;
+            }
+            if self.write_rcp_commands(&packet, &rcp_commands).await {
+                let latency = &self.inner.stats.osc_to_rcp_latency;
+                self.record_latency(latency, Direction::OscToRcp, started);
+            }
+        }
+    }
+
+    /// Writes the commands converted from `packet` to the console, or queues
+    /// them while it is disconnected. Returns whether they were written.
+    async fn write_rcp_commands(&self, packet: &OscPacket, rcp_commands: &[String]) -> bool {
+        if self.queue(rcp_commands, false) {
+            return false;
+        }
+        // The commands from a bundle are written together, so they go out
+        // in as few TCP segments as possible
+        for rcp_command in rcp_commands {
+            self.log(LogLevel::Debug, format!("Sending RCP: {}", rcp_command));
+        }
+        if self.inner.csv_log.lock().unwrap().is_some() {
+            // One command per message, in the same order, or one per index
+            // for a bulk get
+            let conversion = self.conversion();
+            let mut rcp_commands = rcp_commands.iter();
+            for msg in packet_messages(packet) {
+                let count = expand_bulk_get(&msg.addr, &conversion).map_or(1, |c| c.len());
+                for rcp_command in rcp_commands.by_ref().take(count) {
+                    self.log_csv(Direction::OscToRcp, rcp_command, msg);
+                }
+            }
+        }
+        if let Err(e) = self.inner.transport.write_lines(rcp_commands).await {
+            self.log(
+                LogLevel::Error,
+                format!("Failed to write to RCP stream: {}", e),
+            );
+            self.queue(rcp_commands, true);
+            return false;
+        }
+        self.count_written(rcp_commands);
+        for rcp_command in rcp_commands {
+            self.track_subscription(rcp_command);
+        }
+        self.inner
+            .stats
+            .osc_to_rcp
+            .fetch_add(rcp_commands.len() as u64, Ordering::Relaxed);
+        true
+    }
+
+    /// The time a message was received, if its latency is measured.
+    fn latency_start(&self) -> Option<Instant> {
+        self.config().measure_latency.then(Instant::now)
+    }
+
+    /// Records the time since `started` in `latency`, if it is measured.
+    fn record_latency(&self, latency: &Latency, direction: Direction, started: Option<Instant>) {
+        let Some(started) = started else {
+            return;
+        };
+        let elapsed = started.elapsed();
+        latency.record(elapsed);
+        self.log(
+            LogLevel::Debug,
+            format!(
+                "{} latency: {:.3} ms",
+                direction.as_str(),
+                elapsed.as_secs_f64() * 1000.0
+            ),
+        );
+    }
+
+    /// Records a subscription started by a `subscribe` or `mtrstart`
+    /// command written to the console, or forgets the one stopped by an
+    /// `unsubscribe` or `mtrstop`, so
+    /// [`restore_session`](Bridge::restore_session) can start them again.
+    fn track_subscription(&self, rcp_command: &str) {
+        let parts = split_respecting_quotes(rcp_command);
+        let [verb, path, ..] = parts.as_slice() else {
+            return;
+        };
+        let Some((start, _)) = SUBSCRIPTION_COMMANDS
+            .iter()
+            .find(|(start, stop)| verb == start || verb == stop)
+        else {
+            return;
+        };
+        let mut subscriptions = self.inner.subscriptions.lock().unwrap();
+        // Restarting a subscription replaces its arguments
+        subscriptions.retain(|command| {
+            let parts = split_respecting_quotes(command);
+            parts.first().map(String::as_str) != Some(*start) || parts.get(1) != Some(path)
+        });
+        if verb == start {
+            subscriptions.push(rcp_command.to_string());
+        }
+    }
+}
+
+/// The RCP commands that start a subscription, each with the one that stops
+/// it.
+const SUBSCRIPTION_COMMANDS: &[(&str, &str)] =
+    &[("subscribe", "unsubscribe"), ("mtrstart", "mtrstop")];
+
+/// Varies `delay` randomly by up to `fraction` of it either way, with
+/// `fraction` clamped to 0..=1.
+fn jitter(delay: Duration, fraction: f64) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+
+    if !fraction.is_finite() || fraction <= 0.0 {
+        return delay;
+    }
+    let fraction = fraction.min(1.0);
+    // Each RandomState is randomly seeded, which is random enough for this
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish() as f64
+        / u64::MAX as f64;
+    delay.mul_f64(1.0 + fraction * (2.0 * random - 1.0))
+}
+
+/// How long until `timetag`, or `None` if it has passed, as the "immediately"
+/// timetag (0, 1) always has.
+///
+/// Compared as NTP fixed-point rather than through [`SystemTime`], whose
+/// conversion from an [`OscTime`] panics before the Unix epoch.
+fn timetag_delay(timetag: OscTime) -> Option<Duration> {
+    let now = OscTime::try_from(SystemTime::now()).ok()?;
+    let fixed = |time: OscTime| (u64::from(time.seconds) << 32) | u64::from(time.fractional);
+    let ahead = fixed(timetag).checked_sub(fixed(now)).filter(|&d| d > 0)?;
+    let nanos = ((ahead & 0xffff_ffff) * 1_000_000_000) >> 32;
+    Some(Duration::new(ahead >> 32, nanos as u32))
+}
+
+/// Whether `address` is one of the bridge's own: the dump, panic, raw,
+/// ping or status query address.
+fn is_trigger(config: &BridgeConfig, address: &str) -> bool {
+    [
+        &config.dump_address,
+        &config.panic_address,
+        &config.raw_address,
+        &config.ping_address,
+        &config.status_query_address,
+    ]
+    .iter()
+    .any(|trigger| !trigger.is_empty() && *trigger == address)
+}
+
+/// Moves the messages in `packet` sent to the bridge's own addresses (see
+/// [`is_trigger`]) into `triggers`, depth-first, returning what is left, or
+/// `None` if nothing is.
+fn take_triggers(
+    config: &BridgeConfig,
+    packet: OscPacket,
+    triggers: &mut Vec<OscMessage>,
+) -> Option<OscPacket> {
+    match packet {
+        OscPacket::Message(msg) if is_trigger(config, &msg.addr) => {
+            triggers.push(msg);
+            None
+        }
+        OscPacket::Message(msg) => Some(OscPacket::Message(msg)),
+        OscPacket::Bundle(mut bundle) => {
+            bundle.content = bundle
+                .content
+                .into_iter()
+                .filter_map(|packet| take_triggers(config, packet, triggers))
+                .collect();
+            (!bundle.content.is_empty()).then_some(OscPacket::Bundle(bundle))
+        }
+    }
+}
+
+/// The messages in `packet`, with bundles flattened depth-first.
+fn packet_messages(packet: &OscPacket) -> Vec<&OscMessage> {
+    match packet {
+        OscPacket::Message(msg) => vec![msg],
+        OscPacket::Bundle(bundle) => bundle.content.iter().flat_map(packet_messages).collect(),
+    }
+}
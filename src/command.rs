@@ -0,0 +1,88 @@
+use crate::{ConversionError, ConversionOptions, osc_to_rcp_arg_with};
+use rosc::OscType;
+
+/// Builds an RCP command line, formatting and quoting the arguments the
+/// same way as messages converted from OSC (see [`osc_to_rcp_arg`]), e.g.
+/// `RcpCommandBuilder::set("MIXER:Current/InCh/Fader/Level").args([0, 0, -3000])`
+/// builds `set MIXER:Current/InCh/Fader/Level 0 0 -3000`.
+///
+/// [`osc_to_rcp_arg`]: crate::osc_to_rcp_arg
+#[derive(Debug, Clone, PartialEq)]
+pub struct RcpCommandBuilder {
+    command: String,
+    path: Option<String>,
+    args: Vec<OscType>,
+}
+
+impl RcpCommandBuilder {
+    /// Starts a command such as `devstatus` or `ssrecall_ex`.
+    pub fn new(command: impl Into<String>) -> Self {
+        RcpCommandBuilder {
+            command: command.into(),
+            path: None,
+            args: Vec::new(),
+        }
+    }
+
+    /// Starts a `set` of the parameter at `path`, e.g.
+    /// `MIXER:Current/InCh/Fader/Level`.
+    pub fn set(path: impl Into<String>) -> Self {
+        RcpCommandBuilder::new("set").path(path)
+    }
+
+    /// Starts a `get` of the parameter at `path`.
+    pub fn get(path: impl Into<String>) -> Self {
+        RcpCommandBuilder::new("get").path(path)
+    }
+
+    /// Sets the parameter path written after the command.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Adds an argument. Strings are quoted unless they already are.
+    pub fn arg(mut self, arg: impl Into<OscType>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Adds several arguments, e.g. the channel indexes and value.
+    pub fn args<I>(mut self, args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<OscType>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Formats the command line, without a trailing newline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an argument can't be written to RCP, such as a
+    /// NaN float.
+    pub fn build(&self) -> Result<String, ConversionError> {
+        self.build_with(&ConversionOptions::default())
+    }
+
+    /// Formats the command line, with floats and blobs written as `options`
+    /// asks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an argument can't be written to RCP.
+    pub fn build_with(&self, options: &ConversionOptions) -> Result<String, ConversionError> {
+        let mut tokens = vec![self.command.clone()];
+        tokens.extend(self.path.clone());
+        for arg in &self.args {
+            let arg = osc_to_rcp_arg_with(arg, options)?;
+            // An empty array has no arguments to write
+            if !arg.is_empty() {
+                tokens.push(arg);
+            }
+        }
+        Ok(tokens.join(" "))
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{LogFn, LogLevel};
+use rosc::{OscMessage, OscType};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+
+/// How often buffered rows are written out to the file.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const HEADER: &str = "timestamp,direction,rcp,osc_address,osc_args\n";
+
+/// Which way a logged message was converted.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Direction {
+    RcpToOsc,
+    OscToRcp,
+}
+
+impl Direction {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Direction::RcpToOsc => "rcp->osc",
+            Direction::OscToRcp => "osc->rcp",
+        }
+    }
+}
+
+/// Appends a CSV row for each converted message to a file, for show
+/// documentation: the UTC time, the direction, the RCP line, and the OSC
+/// address and arguments.
+///
+/// Rows are only formatted on the caller's task; a background task writes
+/// them and flushes once a second, so the message loops never wait on the
+/// disk. The task flushes what is left and exits once every `CsvLog` is
+/// dropped.
+#[derive(Clone)]
+pub(crate) struct CsvLog {
+    rows: UnboundedSender<String>,
+}
+
+impl CsvLog {
+    /// Opens `path` for appending, creating it with a header row if needed,
+    /// and starts the task writing to it.
+    pub(crate) async fn create(
+        path: &str,
+        log: Arc<LogFn>,
+    ) -> std::io::Result<(Self, JoinHandle<()>)> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let is_new = file.metadata().await?.len() == 0;
+        let mut writer = BufWriter::new(file);
+        if is_new {
+            writer.write_all(HEADER.as_bytes()).await?;
+        }
+
+        let (tx, mut rx) = unbounded_channel::<String>();
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(FLUSH_INTERVAL);
+            let result = loop {
+                let written = tokio::select! {
+                    row = rx.recv() => match row {
+                        Some(row) => writer.write_all(row.as_bytes()).await,
+                        None => break writer.flush().await,
+                    },
+                    _ = ticks.tick() => writer.flush().await,
+                };
+                if let Err(e) = written {
+                    break Err(e);
+                }
+            };
+            if let Err(e) = result {
+                log(
+                    LogLevel::Error,
+                    format!("Failed to write to CSV log: {}", e),
+                );
+            }
+        });
+        Ok((CsvLog { rows: tx }, task))
+    }
+
+    /// Queues a row for the conversion of `rcp` to or from `msg`.
+    pub(crate) fn log(&self, direction: Direction, rcp: &str, msg: &OscMessage) {
+        let args: Vec<String> = msg.args.iter().map(format_arg).collect();
+        let fields = [
+            utc_timestamp(SystemTime::now()),
+            direction.as_str().to_string(),
+            rcp.trim().to_string(),
+            msg.addr.clone(),
+            args.join(" "),
+        ];
+        let mut row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        row.last_mut().unwrap().push('\n');
+        // Only fails if the writer has stopped after an error, already logged
+        let _ = self.rows.send(row.join(","));
+    }
+}
+
+/// An OSC argument as it would be read in a spreadsheet.
+fn format_arg(arg: &OscType) -> String {
+    match arg {
+        OscType::Int(i) => i.to_string(),
+        OscType::Long(l) => l.to_string(),
+        OscType::Float(f) => f.to_string(),
+        OscType::Double(d) => d.to_string(),
+        OscType::String(s) => s.clone(),
+        OscType::Bool(b) => b.to_string(),
+        OscType::Array(array) => {
+            let content: Vec<String> = array.content.iter().map(format_arg).collect();
+            format!("[{}]", content.join(","))
+        }
+        other => format!("{:?}", other),
+    }
+}
+
+/// Quotes `field` if it contains anything CSV treats specially.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Formats `time` as an ISO 8601 UTC timestamp with milliseconds, e.g.
+/// `2024-06-10T06:13:20.123Z`.
+pub(crate) fn utc_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
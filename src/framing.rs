@@ -0,0 +1,71 @@
+/// Splits a stream of bytes from the console into RCP lines.
+///
+/// Bytes can be pushed in chunks of any size, such as the reads from a TCP
+/// socket, and each complete line is returned once its `\n` arrives. A
+/// trailing `\r` is removed, and a partial line is kept until the rest of it
+/// is pushed, so a line split mid-character decodes correctly. Lines are
+/// decoded as UTF-8, with invalid bytes replaced, unless they are taken as
+/// bytes with [`push_raw`](RcpLineFramer::push_raw).
+///
+/// A [`quote_aware`](RcpLineFramer::quote_aware) framer doesn't end a line on
+/// a `\n` inside a quoted value, as a scene name or comment may contain one.
+#[derive(Debug, Default)]
+pub struct RcpLineFramer {
+    buffer: Vec<u8>,
+    /// How much of `buffer` has already been scanned for line ends
+    scanned: usize,
+    in_quotes: bool,
+    quote_aware: bool,
+}
+
+impl RcpLineFramer {
+    /// Creates a framer that ends a line at every `\n`.
+    pub fn new() -> Self {
+        RcpLineFramer::default()
+    }
+
+    /// Creates a framer that ignores a `\n` inside a quoted value.
+    pub fn quote_aware() -> Self {
+        RcpLineFramer {
+            quote_aware: true,
+            ..RcpLineFramer::default()
+        }
+    }
+
+    /// Adds `bytes` to the stream, returning the lines they complete, without
+    /// their line terminators.
+    pub fn push(&mut self, bytes: &[u8]) -> impl Iterator<Item = String> + use<> {
+        self.push_raw(bytes)
+            .map(|line| String::from_utf8_lossy(&line).into_owned())
+    }
+
+    /// Like [`push`](Self::push), but returns the lines as raw bytes, so the
+    /// caller can decide what to do with any that aren't valid UTF-8.
+    pub fn push_raw(&mut self, bytes: &[u8]) -> impl Iterator<Item = Vec<u8>> + use<> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for i in self.scanned..self.buffer.len() {
+            match self.buffer[i] {
+                b'"' if self.quote_aware => self.in_quotes = !self.in_quotes,
+                b'\n' if !self.in_quotes => {
+                    let line = &self.buffer[start..i];
+                    lines.push(line.strip_suffix(b"\r").unwrap_or(line).to_vec());
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        self.buffer.drain(..start);
+        self.scanned = self.buffer.len();
+        lines.into_iter()
+    }
+
+    /// Discards any partial line, e.g. when the connection is replaced.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.scanned = 0;
+        self.in_quotes = false;
+    }
+}
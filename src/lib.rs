@@ -1,14 +1,42 @@
-use rosc::{OscMessage, OscType};
+use base64::prelude::*;
+use rosc::{OscMessage, OscPacket, OscTime, OscType};
 use serde::{Deserialize, Serialize};
-use socket2::{Domain, Socket, Type};
-use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpStream, UdpSocket};
-use tokio::sync::Mutex;
+use std::borrow::Cow;
+
+mod bridge;
+mod command;
+mod csv_log;
+mod framing;
+mod log_format;
+mod loopback;
+mod metrics;
+mod namespace;
+mod pattern;
+mod replay;
+#[cfg(feature = "systemd")]
+mod systemd;
+mod transport;
+
+pub use bridge::{Bridge, ConsoleModel, QUERY_TIMEOUT, QueryError, SUBSCRIBER_CAPACITY};
+pub use command::RcpCommandBuilder;
+pub use framing::RcpLineFramer;
+pub use log_format::{ColorMode, LogFormat};
+pub use loopback::LoopbackTransport;
+pub use metrics::Stats;
+pub use namespace::OscNamespace;
+pub use pattern::osc_pattern_matches;
+pub use replay::ReplayTransport;
+pub use transport::{
+    MockHandle, MockTransport, SocketTransport, Transport, osc_feedback_loop, probe_console,
+    send_console_command, split_osc_packet,
+};
 
 /// Configuration for running the Yamaha RCP <-> OSC bridge
+///
+/// Missing fields are filled in from [`BridgeConfig::default`] when
+/// deserializing, so a config file only needs the settings it changes.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BridgeConfig {
     /// The remote console IP
     pub console_ip: String,
@@ -18,14 +46,1406 @@ pub struct BridgeConfig {
     pub udp_osc_out_addr: String,
     /// The remote OSC port
     pub udp_osc_out_port: u16,
+    /// Local port OSC is sent from over UDP, so the source port is stable
+    /// for firewall rules and controllers that match on it. 0 picks a free
+    /// one.
+    pub udp_osc_out_local_port: u16,
     /// The local OSC bind address
     pub udp_osc_in_addr: String,
+    /// Local addresses to receive OSC on, all on `udp_osc_in_port`, in place
+    /// of `udp_osc_in_addr`, e.g. one per subnet on a multi-homed host. An
+    /// address that can't be bound is logged and skipped.
+    pub udp_osc_in_addrs: Vec<String>,
     /// The local OSC bind port
     pub udp_osc_in_port: u16,
+    /// Transport used for sending and receiving OSC
+    pub osc_transport: OscTransport,
+    /// Unix datagram socket to receive OSC on instead of the network, for an
+    /// OSC application on the same host. Replaces the OSC in and out
+    /// addresses, and requires `osc_uds_out_path`. Unix only.
+    pub osc_uds_path: String,
+    /// Unix datagram socket OSC is sent to when `osc_uds_path` is set
+    pub osc_uds_out_path: String,
+    /// Refuse to start, rather than warn, when OSC would be sent back to
+    /// the bridge's own OSC in address (see [`osc_feedback_loop`])
+    pub strict: bool,
+    /// Largest OSC packet sent over UDP, in bytes. A larger bundle is split
+    /// into bundles that fit, and a larger message is dropped with a
+    /// warning. 0 disables the check.
+    pub max_udp_size: usize,
+    /// Number of OSC packets that may wait to be sent over UDP, so a slow
+    /// network or a flood of meter updates never holds up reading from the
+    /// console. When it is full the oldest packet is dropped, and the drops
+    /// are logged. 0 sends each packet as it is converted.
+    pub osc_send_queue_size: usize,
+    /// Send OSC to a multicast group, so every controller that joined it gets
+    /// the console's feedback. This is set automatically when
+    /// `udp_osc_out_addr` is an IPv4 multicast address (224.0.0.0/4), and
+    /// forces it for a hostname that resolves to one. UDP only.
+    pub osc_multicast: bool,
+    /// Time-to-live of multicast OSC packets: the number of routers they may
+    /// cross. 1 keeps them on the local network.
+    pub osc_multicast_ttl: u32,
+    /// Local IPv4 address of the interface multicast OSC is sent on. Empty
+    /// uses whichever interface the routing table picks.
+    pub osc_multicast_interface: String,
+    /// Network interface (e.g. `eth1`) the OSC sockets and the console
+    /// connection are pinned to with `SO_BINDTODEVICE`, so traffic can't
+    /// leak onto another network on a multi-homed machine. Only supported
+    /// on Linux; elsewhere it is ignored with a warning. Empty uses
+    /// whichever interface the routing table picks.
+    pub bind_device: String,
+    /// Options controlling how values are converted between RCP and OSC
+    pub conversion: ConversionOptions,
+    /// Inbound OSC address that triggers a dump of `dump_commands` to the
+    /// console. Empty disables the trigger.
+    pub dump_address: String,
+    /// RCP commands (typically `get`s) written to the console when a dump is
+    /// triggered, so current values are sent back over OSC
+    pub dump_commands: Vec<String>,
+    /// Inbound OSC address that triggers writing `panic_commands` to the
+    /// console, as a single safety trigger during a show. Empty disables
+    /// the trigger.
+    pub panic_address: String,
+    /// RCP commands (e.g. mutes or a safe scene recall) written to the
+    /// console in order when a panic is triggered
+    pub panic_commands: Vec<String>,
+    /// Inbound OSC address the bridge answers itself, without involving the
+    /// console, by sending `pong_address` with the same arguments (e.g. a
+    /// sequence number). Empty disables it.
+    pub ping_address: String,
+    /// OSC address a ping is answered on
+    pub pong_address: String,
+    /// Also send `dump_commands` whenever the console connection is
+    /// (re-)established
+    pub dump_on_connect: bool,
+    /// RCP commands (typically subscriptions) written to the console every
+    /// time the connection is established, including after a reconnect
+    pub init_commands: Vec<String>,
+    /// OSC address the connection state is announced on (`1` when the
+    /// console connects, `0` when it disconnects). Empty disables it.
+    pub status_address: String,
+    /// Inbound OSC address the bridge answers itself by sending
+    /// `status_reply_address` with the current connection state (`1` or
+    /// `0`), the seconds since the bridge started and the number of
+    /// reconnects, for controllers that poll. Empty disables it.
+    pub status_query_address: String,
+    /// OSC address a status query is answered on
+    pub status_reply_address: String,
+    /// Reconnect to the console when the connection drops instead of
+    /// stopping the bridge
+    pub reconnect: bool,
+    /// Delay before the first reconnect attempt, doubled after each failed
+    /// attempt
+    pub reconnect_delay_ms: u64,
+    /// Upper bound on the delay between reconnect attempts
+    pub reconnect_max_delay_ms: u64,
+    /// Fraction (0 to 1) each reconnect delay is randomly varied by either
+    /// way, so bridges that lost the console together don't retry in step
+    pub reconnect_jitter: f64,
+    /// Give up after this many failed reconnect attempts in a row, stopping
+    /// the bridge with an error. 0 retries forever.
+    pub reconnect_max_attempts: u32,
+    /// Keep up to this many commands received over OSC while the console is
+    /// disconnected, and write them once it reconnects, dropping the oldest
+    /// when full. 0 drops commands that can't be written.
+    pub reconnect_queue_size: usize,
+    /// How long to wait for the console to accept a connection before the
+    /// attempt fails, for the initial connection and each reconnect
+    pub connect_timeout_ms: u64,
+    /// How long to wait for the console's `OK` or `ERROR` reply wherever
+    /// one is waited for: [`Bridge::query`], the login and
+    /// [`send_console_command`]
+    pub response_timeout_ms: u64,
+    /// User to log in to the console as, for consoles that require a login
+    /// before accepting commands. Empty logs in with only the password.
+    pub rcp_user: String,
+    /// Password to log in to the console with, right after connecting and
+    /// before any `init_commands`. A rejected login stops the bridge rather
+    /// than being retried. Empty skips the login.
+    pub rcp_password: String,
+    /// Which OSC addresses converted from the console's messages are sent
+    /// over OSC; the rest are dropped
+    pub osc_out_filter: AddressFilter,
+    /// Which kinds of message from the console are sent over OSC, e.g. only
+    /// `NOTIFY`s so the console's `OK` confirmations don't echo a
+    /// controller's own changes back to it. Replies still answer queries.
+    pub forward_types: Vec<RcpMessageKind>,
+    /// Which incoming OSC addresses are passed on to the console; the rest
+    /// are dropped with a warning, e.g. to block scene stores
+    pub osc_in_filter: AddressFilter,
+    /// Changes too small to pass on for incoming OSC addresses, e.g. the
+    /// jitter of a finger resting on a touch fader. The first matching
+    /// deadband applies.
+    pub deadbands: Vec<Deadband>,
+    /// Don't send an OSC message that is identical to the previous one sent
+    /// on the same address, for consoles that repeat unchanged NOTIFYs
+    pub dedup: bool,
+    /// Write the RCP commands from an incoming OSC bundle with a future
+    /// timetag at the scheduled time, rather than as soon as it arrives
+    pub honor_timetags: bool,
+    /// Reconnect if nothing is received from the console for this many
+    /// seconds, even if `reconnect` is off, to catch connections that die
+    /// without an error. 0 disables it.
+    pub idle_reconnect_secs: u64,
+    /// How long, in milliseconds, values sent to `ramp_addresses` take to
+    /// move from the current value to the new one, in small `set` steps.
+    /// 0 disables ramping.
+    pub ramp_ms: u64,
+    /// OSC address patterns whose values are ramped over `ramp_ms` rather
+    /// than jumping, e.g. for motorized faders
+    pub ramp_addresses: Vec<String>,
+    /// Address (e.g. `0.0.0.0:9100`) to serve Prometheus metrics on at
+    /// `/metrics`. Empty disables it. Requires the `metrics` feature.
+    pub metrics_addr: String,
+    /// Time how long the bridge takes to pass each message on: from
+    /// receiving an OSC packet to writing its RCP commands to the console,
+    /// and from receiving an RCP line to sending its OSC message. Each
+    /// measurement is logged at debug level and added to a latency
+    /// histogram served on `metrics_addr`.
+    pub measure_latency: bool,
+    /// File every RCP line received from the console is appended to, with
+    /// a timestamp, for replaying later with [`Bridge::replay`]. Empty
+    /// disables recording.
+    pub record_path: String,
+    /// How fast [`Bridge::replay`] plays a recording back compared to the
+    /// recorded timing, e.g. `2.0` for twice as fast or `0.5` for half
+    /// speed
+    pub replay_speed: f64,
+    /// Play recordings back as fast as possible, ignoring the recorded
+    /// timing
+    pub replay_no_timing: bool,
+    /// CSV file a row is appended to for every converted message, in both
+    /// directions, for show documentation. Empty disables it.
+    pub csv_log_path: String,
+    /// Log a hexdump of every chunk of bytes read from the console, at
+    /// [`LogLevel::Debug`], for diagnosing encoding and framing problems
+    pub log_raw_rcp: bool,
+    /// Skip lines from the console that aren't valid UTF-8, logging their
+    /// bytes, instead of converting them with the invalid bytes replaced
+    pub strict_utf8: bool,
+    /// What every command written to the console ends with, including the
+    /// login, init commands and the bridge's own queries
+    pub rcp_line_ending: RcpLineEnding,
+    /// Also send every line received from the console, unconverted, as a
+    /// string on `raw_address`, for inspecting the protocol remotely
+    pub echo_raw: bool,
+    /// OSC address raw console lines are sent on with `echo_raw`, and raw
+    /// commands are received on with `allow_raw`
+    pub raw_address: String,
+    /// Write the string argument of OSC messages sent to `raw_address` to
+    /// the console verbatim, without converting it, as an escape hatch for
+    /// commands the conversion doesn't cover. Each one is logged as a
+    /// warning. When unset such messages are ignored.
+    pub allow_raw: bool,
+    /// Ask the console for its model with `devinfo` on every connect
+    pub detect_model: bool,
+    /// OSC address the detected model (and firmware, if known) is sent on.
+    /// Empty disables it.
+    pub model_address: String,
+    /// Console model used to pick a mapping table instead of the detected
+    /// one. Empty uses the detected model.
+    pub model: String,
+    /// Parameter mapping tables, of which the first matching the console's
+    /// model is used in addition to `conversion.parameter_mappings`
+    pub mapping_tables: Vec<MappingTable>,
+    /// Consoles to bridge at the same time, sharing the OSC sockets. When
+    /// set, these replace `console_ip`, `rcp_port` and
+    /// `conversion.osc_address_prefix`.
+    pub consoles: Vec<ConsoleConfig>,
 }
 
-/// Severity of a log message, analogous to levels in other logging systems.
+/// One of several consoles bridged at once (see [`BridgeConfig::consoles`]).
+///
+/// Incoming OSC is routed to the console whose prefix it starts with, and
+/// that console's OSC is sent with its prefix. The status, model, dump and
+/// ping addresses are also prefixed, so each console reports and dumps on
+/// its own. Every other setting is shared.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsoleConfig {
+    /// The remote console IP
+    pub console_ip: String,
+    /// The remote RCP port
+    pub rcp_port: u16,
+    /// OSC address prefix for this console, e.g. `/foh`. Must be unique.
+    pub osc_address_prefix: String,
+    /// Source tag appended to this console's OSC messages (see
+    /// [`ConversionOptions::source_tag`]). Empty uses the shared one.
+    pub source_tag: String,
+}
+
+impl Default for ConsoleConfig {
+    fn default() -> Self {
+        ConsoleConfig {
+            console_ip: String::new(),
+            rcp_port: 49280,
+            osc_address_prefix: String::new(),
+            source_tag: String::new(),
+        }
+    }
+}
+
+impl Default for BridgeConfig {
+    /// The same defaults as the CLI, with no console IP set.
+    fn default() -> Self {
+        BridgeConfig {
+            console_ip: String::new(),
+            rcp_port: 49280,
+            udp_osc_out_addr: "127.0.0.1".to_string(),
+            udp_osc_out_port: 3999,
+            udp_osc_out_local_port: 0,
+            udp_osc_in_addr: "0.0.0.0".to_string(),
+            udp_osc_in_addrs: Vec::new(),
+            udp_osc_in_port: 4000,
+            osc_transport: OscTransport::default(),
+            osc_uds_path: String::new(),
+            osc_uds_out_path: String::new(),
+            strict: false,
+            max_udp_size: 0,
+            osc_send_queue_size: 0,
+            osc_multicast: false,
+            osc_multicast_ttl: 1,
+            osc_multicast_interface: String::new(),
+            bind_device: String::new(),
+            conversion: ConversionOptions::default(),
+            dump_address: "/bridge/dump".to_string(),
+            dump_commands: Vec::new(),
+            panic_address: "/bridge/panic".to_string(),
+            panic_commands: Vec::new(),
+            ping_address: "/bridge/ping".to_string(),
+            pong_address: "/bridge/pong".to_string(),
+            dump_on_connect: false,
+            init_commands: Vec::new(),
+            status_address: String::new(),
+            status_query_address: "/bridge/status/get".to_string(),
+            status_reply_address: "/bridge/status/reply".to_string(),
+            reconnect: false,
+            reconnect_delay_ms: 1000,
+            reconnect_max_delay_ms: 30_000,
+            reconnect_jitter: 0.0,
+            reconnect_max_attempts: 0,
+            reconnect_queue_size: 0,
+            connect_timeout_ms: 5000,
+            response_timeout_ms: QUERY_TIMEOUT.as_millis() as u64,
+            rcp_user: String::new(),
+            rcp_password: String::new(),
+            idle_reconnect_secs: 0,
+            osc_out_filter: AddressFilter::default(),
+            forward_types: vec![
+                RcpMessageKind::Notify,
+                RcpMessageKind::Ok,
+                RcpMessageKind::Error,
+            ],
+            osc_in_filter: AddressFilter::default(),
+            deadbands: Vec::new(),
+            dedup: false,
+            honor_timetags: false,
+            ramp_ms: 0,
+            ramp_addresses: Vec::new(),
+            metrics_addr: String::new(),
+            measure_latency: false,
+            record_path: String::new(),
+            replay_speed: 1.0,
+            replay_no_timing: false,
+            csv_log_path: String::new(),
+            log_raw_rcp: false,
+            strict_utf8: false,
+            rcp_line_ending: RcpLineEnding::Lf,
+            echo_raw: false,
+            raw_address: "/bridge/raw".to_string(),
+            allow_raw: false,
+            detect_model: true,
+            model_address: "/bridge/model".to_string(),
+            model: String::new(),
+            mapping_tables: Vec::new(),
+            consoles: Vec::new(),
+        }
+    }
+}
+
+/// Transport used for the OSC side of the bridge.
+///
+/// The conversion logic is identical for every transport; only how packets
+/// are framed and delivered differs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OscTransport {
+    /// One OSC packet per UDP datagram. This is the default.
+    #[default]
+    Udp,
+    /// OSC packets over TCP, each prefixed with its length as a big-endian
+    /// int32 as per the OSC 1.0 specification. The bridge connects to the
+    /// OSC out address and accepts connections on the OSC in address.
+    Tcp,
+}
+
+impl std::fmt::Display for OscTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            OscTransport::Udp => "udp",
+            OscTransport::Tcp => "tcp",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for OscTransport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "udp" => Ok(OscTransport::Udp),
+            "tcp" => Ok(OscTransport::Tcp),
+            _ => Err(format!(
+                "Unknown OSC transport '{}' (expected 'udp' or 'tcp')",
+                s
+            )),
+        }
+    }
+}
+
+/// What each command written to the console ends with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RcpLineEnding {
+    /// A bare `\n`. This is the default.
+    #[default]
+    Lf,
+    /// `\r\n`, for firmware that insists on it.
+    Crlf,
+}
+
+impl RcpLineEnding {
+    /// The terminator's characters.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RcpLineEnding::Lf => "\n",
+            RcpLineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+impl std::fmt::Display for RcpLineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RcpLineEnding::Lf => "lf",
+            RcpLineEnding::Crlf => "crlf",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for RcpLineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(RcpLineEnding::Lf),
+            "crlf" => Ok(RcpLineEnding::Crlf),
+            _ => Err(format!(
+                "Unknown RCP line ending '{}' (expected 'lf' or 'crlf')",
+                s
+            )),
+        }
+    }
+}
+
+/// How OSC floats are written as RCP arguments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FloatFormat {
+    /// Always include a decimal point, so `3.0` is written as `3.0` and
+    /// `3.5` as `3.5`. This is the default.
+    #[default]
+    Decimal,
+    /// Write integer-valued floats without a decimal point (`3.0` becomes
+    /// `3`), for RCP parameters that are strictly integer. Other values are
+    /// written as in [`FloatFormat::Decimal`].
+    IntegerWhenWhole,
+}
+
+impl std::fmt::Display for FloatFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            FloatFormat::Decimal => "decimal",
+            FloatFormat::IntegerWhenWhole => "integer-when-whole",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for FloatFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "decimal" => Ok(FloatFormat::Decimal),
+            "integer-when-whole" => Ok(FloatFormat::IntegerWhenWhole),
+            _ => Err(format!(
+                "Unknown float format '{}' (expected 'decimal' or 'integer-when-whole')",
+                s
+            )),
+        }
+    }
+}
+
+/// What an OSC `Nil` argument is written to RCP as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NilArg {
+    /// Leave it out, so a trigger such as `/devstatus` sent with a single
+    /// `Nil` becomes the bare command. This is the default.
+    #[default]
+    Omit,
+    /// Fail the conversion with [`ConversionError::UnsupportedArgType`].
+    Reject,
+}
+
+impl std::fmt::Display for NilArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            NilArg::Omit => "omit",
+            NilArg::Reject => "reject",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for NilArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "omit" => Ok(NilArg::Omit),
+            "reject" => Ok(NilArg::Reject),
+            _ => Err(format!(
+                "Unknown nil handling '{}' (expected 'omit' or 'reject')",
+                s
+            )),
+        }
+    }
+}
+
+/// How floats are rounded when they are coerced to integers (see
+/// [`ArgType::Int`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoundMode {
+    /// Round to the nearest integer, with halves away from zero, so `2.5`
+    /// becomes 3 and `-2.5` becomes -3. This is the default.
+    #[default]
+    HalfAwayFromZero,
+    /// Round to the nearest integer, with halves to the even one, so `2.5`
+    /// becomes 2 and `3.5` becomes 4.
+    HalfEven,
+    /// Round to the nearest integer, with halves up, so `2.5` becomes 3 and
+    /// `-2.5` becomes -2.
+    HalfUp,
+    /// Drop the fraction, so `2.9` becomes 2 and `-2.9` becomes -2.
+    Truncate,
+}
+
+impl RoundMode {
+    /// Rounds `value` to an `i32`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConversionError::IntOverflow`] if the rounded value is out
+    /// of range or `value` isn't finite, rather than saturating.
+    pub fn to_int(self, value: f64) -> Result<i32, ConversionError> {
+        let rounded = match self {
+            RoundMode::HalfAwayFromZero => value.round(),
+            RoundMode::HalfEven => value.round_ties_even(),
+            RoundMode::HalfUp => (value + 0.5).floor(),
+            RoundMode::Truncate => value.trunc(),
+        };
+        if rounded.is_finite() && rounded >= f64::from(i32::MIN) && rounded <= f64::from(i32::MAX) {
+            Ok(rounded as i32)
+        } else {
+            Err(ConversionError::IntOverflow(value))
+        }
+    }
+}
+
+impl std::fmt::Display for RoundMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RoundMode::HalfAwayFromZero => "half-away-from-zero",
+            RoundMode::HalfEven => "half-even",
+            RoundMode::HalfUp => "half-up",
+            RoundMode::Truncate => "truncate",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for RoundMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-away-from-zero" => Ok(RoundMode::HalfAwayFromZero),
+            "half-even" => Ok(RoundMode::HalfEven),
+            "half-up" => Ok(RoundMode::HalfUp),
+            "truncate" => Ok(RoundMode::Truncate),
+            _ => Err(format!(
+                "Unknown round mode '{}' (expected 'half-away-from-zero', 'half-even', \
+                 'half-up' or 'truncate')",
+                s
+            )),
+        }
+    }
+}
+
+/// How OSC blobs are encoded as RCP string arguments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobEncoding {
+    /// Standard base64 with padding, e.g. `"AQID"`. This is the default.
+    #[default]
+    Base64,
+    /// Lowercase hex, two digits per byte, e.g. `"010203"`.
+    Hex,
+}
+
+impl BlobEncoding {
+    fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            BlobEncoding::Base64 => BASE64_STANDARD.encode(bytes),
+            BlobEncoding::Hex => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+
+    fn decode(self, s: &str) -> Option<Vec<u8>> {
+        match self {
+            BlobEncoding::Base64 => BASE64_STANDARD.decode(s).ok(),
+            BlobEncoding::Hex => {
+                if !s.len().is_multiple_of(2) || !s.is_ascii() {
+                    return None;
+                }
+                (0..s.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+                    .collect()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for BlobEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            BlobEncoding::Base64 => "base64",
+            BlobEncoding::Hex => "hex",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for BlobEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(BlobEncoding::Base64),
+            "hex" => Ok(BlobEncoding::Hex),
+            _ => Err(format!(
+                "Unknown blob encoding '{}' (expected 'base64' or 'hex')",
+                s
+            )),
+        }
+    }
+}
+
+/// Options controlling how values are converted between RCP and OSC.
+///
+/// The default options match the behavior of the plain conversion functions
+/// such as [`osc_to_rcp`] and [`rcp_to_osc`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversionOptions {
+    /// How OSC floats are formatted when converted to RCP arguments
+    pub float_format: FloatFormat,
+    /// Number of decimal places floats are rounded to in RCP arguments.
+    /// `None` writes the shortest form that reads back as the same float,
+    /// e.g. `0.1` rather than `0.100000001`.
+    pub float_decimals: Option<usize>,
+    /// Address prefix (e.g. `/console1`) prepended to outgoing OSC addresses
+    /// and stripped from incoming ones. Empty means no prefix.
+    pub osc_address_prefix: String,
+    /// How OSC blobs are encoded as RCP strings, and decoded back for
+    /// `blob_addresses`
+    pub blob_encoding: BlobEncoding,
+    /// What OSC `Nil` arguments are written as
+    pub nil_arg: NilArg,
+    /// Send quoted RCP string arguments over OSC without their quotes, e.g.
+    /// `"Vocal 1"` as `Vocal 1`. Strings are quoted again on the way back,
+    /// so either form can be sent to the console.
+    pub unquote_strings: bool,
+    /// Send RCP numbers written with a leading zero, e.g. `01` or `007`,
+    /// as strings so the zero isn't lost (see [`rcp_to_osc_type_with`])
+    pub keep_leading_zeros: bool,
+    /// Outgoing OSC addresses (including any prefix) whose quoted string
+    /// arguments are decoded back into blobs using `blob_encoding`
+    pub blob_addresses: Vec<String>,
+    /// Outgoing OSC addresses (including any prefix) whose last argument,
+    /// a number of seconds since the Unix epoch, is sent as an OSC timetag
+    pub time_addresses: Vec<String>,
+    /// OSC address patterns (including any prefix) whose comma-separated
+    /// RCP arguments, e.g. `1,2,3`, are sent as OSC arrays of typed values,
+    /// and whose OSC arrays are joined with commas on the way back
+    pub list_addresses: Vec<String>,
+    /// Runs of RCP arguments sent together as one OSC array, e.g. a pan
+    /// value and its flag, and split back into separate arguments on the
+    /// way to the console. See [`ArgGroup`].
+    pub arg_groups: Vec<ArgGroup>,
+    /// Argument types forced for incoming OSC addresses. For each argument,
+    /// the first coercion whose pattern matches the address and whose
+    /// positions include the argument applies; other arguments are
+    /// converted as usual.
+    pub type_coercions: Vec<TypeCoercion>,
+    /// How floats are rounded when a type coercion or suffix makes them
+    /// integers
+    pub round_mode: RoundMode,
+    /// Ranges that values sent to matching OSC addresses are clamped into
+    /// before being written to the console. The first matching range
+    /// applies; unmatched addresses are passed through unchanged.
+    pub value_ranges: Vec<ValueRange>,
+    /// Parameters renamed between a canonical OSC name and the console's
+    /// RCP path, in both directions. Usually filled in from the
+    /// [`MappingTable`] for the connected console's model.
+    pub parameter_mappings: Vec<ParameterMapping>,
+    /// Friendlier OSC addresses for RCP parameters, in both directions, e.g.
+    /// `/ch/1/fader` for channel 1's `MIXER:Current/InCh/Fader/Level`. The
+    /// first matching alias applies.
+    pub address_aliases: Vec<AddressAlias>,
+    /// OSC addresses that each read a parameter for a whole range of
+    /// indices, e.g. `/get/ch/*/fader` for every channel's fader, so a
+    /// controller can refresh them with one message. See [`BulkGet`].
+    pub bulk_gets: Vec<BulkGet>,
+    /// String argument appended to every OSC message converted from the
+    /// console, e.g. `foh`, so controllers fed by several consoles can tell
+    /// them apart. A trailing argument equal to it is dropped from incoming
+    /// OSC, so tagged messages can be sent back unchanged. Empty disables
+    /// it.
+    pub source_tag: String,
+    /// Append a sequence number, counting up from 0, as a 64-bit integer
+    /// (`h`) argument to every OSC message the bridge converts from the
+    /// console, after any `source_tag`, so controllers can detect lost
+    /// packets. Each console's bridge counts its own messages. A trailing
+    /// 64-bit integer is dropped from incoming OSC, as RCP values never
+    /// need one, so numbered messages can be sent back unchanged.
+    pub sequence_numbers: bool,
+    /// Read a trailing `/i`, `/f` or `/s` segment of incoming OSC addresses,
+    /// e.g. `/scene/current/i`, as the type to write the arguments as (see
+    /// [`ArgType`]), and drop it from the command. It takes precedence over
+    /// `type_coercions`.
+    pub type_suffixes: bool,
+    /// Segment (e.g. `/ok`) put before the address of messages converted
+    /// from an `OK` reply, after any `osc_address_prefix`, so controllers
+    /// can tell confirmations of their own commands from live updates. It
+    /// is stripped from incoming OSC. Empty keeps replies on the same
+    /// addresses as `NOTIFY`s.
+    pub ok_prefix: String,
+    /// Segment (e.g. `/notify`) put before the address of messages
+    /// converted from a `NOTIFY`, like `ok_prefix`
+    pub notify_prefix: String,
+    /// RCP command (e.g. `set`) written for incoming OSC addresses whose
+    /// first segment isn't one of `verbs`, so a bare parameter path such as
+    /// `/MIXER/Current/InCh/Fader/Level` becomes a `set` of it. Empty treats
+    /// the first segment as the command, whatever it is.
+    pub default_verb: String,
+    /// The RCP commands recognized as the first segment of an address when
+    /// `default_verb` is set. Empty uses [`RCP_VERBS`].
+    pub verbs: Vec<String>,
+    /// OSC base addresses for structured console errors by code, e.g. to
+    /// send warnings and fatal errors to different places. An error whose
+    /// code isn't listed is sent on `/error/<code>`.
+    pub error_addresses: Vec<ErrorAddress>,
+    /// OSC commands written to the console as a different RCP command,
+    /// e.g. `subscribe` as `mtrstart` for consoles that start meter
+    /// subscriptions that way. The arguments are kept as they are.
+    pub verb_mappings: Vec<VerbMapping>,
+}
+
+/// Writes OSC messages whose first address segment is `osc` as the RCP
+/// command `rcp`, so `/subscribe/MIXER:Current/Meter/InCh 100` can be sent
+/// as `mtrstart MIXER:Current/Meter/InCh 100`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerbMapping {
+    /// The command as sent over OSC, e.g. `subscribe`
+    pub osc: String,
+    /// The RCP command written instead, e.g. `mtrstart`
+    pub rcp: String,
+}
+
+/// Sends structured console errors with any of `codes` to
+/// `<address>/<code>` rather than `/error/<code>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErrorAddress {
+    /// The OSC base address, e.g. `/error/fatal`
+    pub address: String,
+    /// The error codes sent to it
+    pub codes: Vec<i32>,
+}
+
+/// Renames a parameter between the name used in OSC addresses and its RCP
+/// path, so `/set/fader` is written to the console as
+/// `set MIXER:Current/InCh/Fader/Level` and its NOTIFYs are sent back as
+/// `/set/fader`. The verb is kept as is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParameterMapping {
+    /// The canonical name used on the OSC side, e.g. `fader`
+    pub osc: String,
+    /// The console's parameter path, e.g. `MIXER:Current/InCh/Fader/Level`
+    pub rcp: String,
+    /// Types of the parameter's arguments by position, in both directions,
+    /// e.g. to keep a zero-padded label such as `007` a string rather than
+    /// the number 7. Arguments past the end are converted as usual.
+    #[serde(default)]
+    pub arg_types: Vec<ArgType>,
+}
+
+/// A named set of [`ParameterMapping`]s for one console family, so the same
+/// OSC layout can drive consoles that use different parameter paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MappingTable {
+    /// Name of the table, used in log messages
+    pub name: String,
+    /// Patterns (see [`osc_pattern_matches`]) for the models the table
+    /// applies to, e.g. `QL*`
+    pub models: Vec<String>,
+    /// The parameters to rename
+    pub mappings: Vec<ParameterMapping>,
+}
+
+impl MappingTable {
+    /// Whether this table applies to `model`.
+    pub fn matches(&self, model: &str) -> bool {
+        self.models
+            .iter()
+            .any(|pattern| osc_pattern_matches(pattern, model))
+    }
+}
+
+/// Gives an RCP parameter and its index arguments a friendlier OSC address,
+/// so `/ch/1/fader -1000` is written to the console as
+/// `set MIXER:Current/InCh/Fader/Level 0 0 -1000` and its NOTIFYs are sent
+/// back on `/ch/1/fader`.
+///
+/// Each `{}` segment of `osc` captures a number, which is written to the
+/// `{}` in the same position in `rcp` less `index_base`, and the other way
+/// round. The OSC arguments are the values after the index arguments; a
+/// message with none is written as a `get`, and any other as a `set`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressAlias {
+    /// The friendly OSC address, e.g. `/ch/{}/fader`
+    pub osc: String,
+    /// The RCP parameter path followed by its index arguments, e.g.
+    /// `MIXER:Current/InCh/Fader/Level {} 0`
+    pub rcp: String,
+    /// The OSC number of RCP index 0, e.g. `1` for channels numbered from 1
+    pub index_base: i32,
+}
+
+impl AddressAlias {
+    /// The RCP parameter path and index arguments for the OSC address
+    /// `segments`, or `None` if they don't match this alias.
+    fn rcp_for(&self, segments: &[&str]) -> Option<(String, Vec<OscType>)> {
+        let template: Vec<&str> = self.osc.split('/').filter(|s| !s.is_empty()).collect();
+        if template.len() != segments.len() {
+            return None;
+        }
+        let mut captures = Vec::new();
+        for (expected, segment) in template.iter().zip(segments) {
+            if *expected == "{}" {
+                let index = segment.parse::<i32>().ok()?.checked_sub(self.index_base)?;
+                captures.push(index);
+            } else if expected != segment {
+                return None;
+            }
+        }
+
+        let mut tokens = self.rcp.split_whitespace();
+        let path = tokens.next()?;
+        let mut captures = captures.into_iter();
+        let index_args = tokens
+            .map(|token| match token {
+                "{}" => captures.next().map(OscType::Int),
+                literal => Some(rcp_to_osc_type(literal)),
+            })
+            .collect::<Option<_>>()?;
+        Some((path.to_string(), index_args))
+    }
+
+    /// The OSC address for an RCP message's `parts` (type, verb, path and
+    /// arguments), with the number of parts its path and index arguments
+    /// take up, or `None` if they don't match this alias.
+    fn osc_for(&self, parts: &[String]) -> Option<(String, usize)> {
+        let mut tokens = self.rcp.split_whitespace();
+        if tokens.next()? != parts.get(2)? {
+            return None;
+        }
+        let mut captures = Vec::new();
+        let mut used = 3;
+        for token in tokens {
+            let part = parts.get(used)?;
+            if token == "{}" {
+                captures.push(part.parse::<i32>().ok()?.checked_add(self.index_base)?);
+            } else if token != part {
+                return None;
+            }
+            used += 1;
+        }
+
+        let mut captures = captures.into_iter();
+        let address = self
+            .osc
+            .split('/')
+            .map(|segment| match segment {
+                "{}" => captures.next().map(|index| index.to_string()),
+                segment => Some(segment.to_string()),
+            })
+            .collect::<Option<Vec<_>>>()?
+            .join("/");
+        Some((address, used))
+    }
+}
+
+/// Expands an OSC message sent to `address` into a `get` of an RCP
+/// parameter for each index from `from` to `to`, so `/get/ch/*/fader` can
+/// read `MIXER:Current/InCh/Fader/Level 0 0` through
+/// `MIXER:Current/InCh/Fader/Level 31 0`. The message's arguments are
+/// ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BulkGet {
+    /// The OSC address, including any prefix, matched exactly, e.g.
+    /// `/get/ch/*/fader`
+    pub address: String,
+    /// The RCP parameter path followed by its index arguments, with `{}`
+    /// where the index goes, e.g. `MIXER:Current/InCh/Fader/Level {} 0`
+    pub rcp: String,
+    /// The first index
+    pub from: i32,
+    /// The last index, included
+    pub to: i32,
+}
+
+impl BulkGet {
+    /// The `get` commands for each index in the range.
+    fn commands(&self) -> Vec<String> {
+        (self.from..=self.to)
+            .map(|index| format!("get {}", self.rcp.replace("{}", &index.to_string())))
+            .collect()
+    }
+}
+
+/// Sends `len` consecutive arguments, from position `start`, of OSC
+/// messages converted from the console for matching addresses as a single
+/// OSC array, so a controller can show a pair such as a pan value and its
+/// flag as one control. An array in that position of an incoming message
+/// is split back into separate RCP arguments.
+///
+/// Positions count the OSC arguments, so index arguments taken up by an
+/// [`AddressAlias`] aren't included. Messages with too few arguments are
+/// left alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArgGroup {
+    /// OSC address pattern (see [`osc_pattern_matches`]) matched against
+    /// the address, including any prefix
+    pub address: String,
+    /// Position of the first argument grouped, counting from 0
+    pub start: usize,
+    /// Number of arguments grouped
+    pub len: usize,
+}
+
+impl ArgGroup {
+    /// Replaces the arguments this group covers with an array of them.
+    fn group(&self, args: &mut Vec<OscType>) {
+        let end = self.start + self.len;
+        if self.len == 0 || end > args.len() {
+            return;
+        }
+        let content = args.splice(self.start..end, []).collect();
+        args.insert(self.start, OscType::Array(rosc::OscArray { content }));
+    }
+
+    /// Replaces an array in this group's position with its contents.
+    fn split(&self, args: &mut Vec<OscType>) {
+        if let Some(OscType::Array(array)) = args.get(self.start) {
+            let content = array.content.clone();
+            args.splice(self.start..=self.start, content);
+        }
+    }
+}
+
+/// An RCP argument type that OSC values can be coerced to.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgType {
+    /// Floats are rounded to the nearest integer (see [`RoundMode`]).
+    Int,
+    /// Integers are written with a decimal point.
+    Float,
+    /// Numbers are written as quoted strings.
+    String,
+}
+
+/// Forces the type of the numeric arguments of OSC messages sent to
+/// matching addresses, e.g. for controllers that only send floats to
+/// integer RCP parameters, or to quote a numeric scene label the console
+/// only accepts as a string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeCoercion {
+    /// OSC address pattern (see [`osc_pattern_matches`]) matched against
+    /// the incoming address, including any prefix
+    pub address: String,
+    /// The type the arguments are converted to
+    #[serde(rename = "type")]
+    pub arg_type: ArgType,
+    /// Positions of the arguments converted, counting from 0. Empty
+    /// converts every argument.
+    #[serde(default)]
+    pub positions: Vec<usize>,
+}
+
+impl TypeCoercion {
+    /// Whether this coercion converts the argument at `position`.
+    fn applies_to(&self, position: usize) -> bool {
+        self.positions.is_empty() || self.positions.contains(&position)
+    }
+}
+
+/// Allow and deny lists of OSC address patterns (see
+/// [`osc_pattern_matches`]) deciding which messages are passed on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressFilter {
+    /// If not empty, only addresses matching one of these are allowed
+    pub allow: Vec<String>,
+    /// Addresses matching any of these are denied, even if allowed above
+    pub deny: Vec<String>,
+}
+
+impl AddressFilter {
+    /// Whether `address` passes the filter.
+    pub fn allows(&self, address: &str) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| osc_pattern_matches(pattern, address))
+        };
+        (self.allow.is_empty() || matches(&self.allow)) && !matches(&self.deny)
+    }
+}
+
+/// The accepted range of the value of a parameter, e.g. `-32768` to `1000`
+/// for a fader level.
+///
+/// Only the value, the last argument of `set <param> <x> <y> <value>`, is
+/// clamped; the index arguments before it are left alone.
+///
+/// A range read from a config file must have `min` no larger than `max`,
+/// and an integer between them for integer values to be clamped to (see
+/// [`ValueRange::check`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedValueRange")]
+pub struct ValueRange {
+    /// OSC address pattern (see [`osc_pattern_matches`]) matched against
+    /// the incoming address, including any prefix
+    pub address: String,
+    /// The smallest accepted value
+    pub min: f64,
+    /// The largest accepted value
+    pub max: f64,
+}
+
+/// A [`ValueRange`] as written in a config file, before it is checked.
+#[derive(Deserialize)]
+struct UncheckedValueRange {
+    address: String,
+    min: f64,
+    max: f64,
+}
+
+impl TryFrom<UncheckedValueRange> for ValueRange {
+    type Error = String;
+
+    fn try_from(range: UncheckedValueRange) -> Result<Self, Self::Error> {
+        let range = ValueRange {
+            address: range.address,
+            min: range.min,
+            max: range.max,
+        };
+        range.check()?;
+        Ok(range)
+    }
+}
+
+/// The smallest change of value worth writing to the console for matching
+/// OSC addresses.
+///
+/// A value, the last argument, is only passed on if it differs by more
+/// than `threshold` from the last one passed on for the same address and
+/// index arguments. The first value always is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deadband {
+    /// OSC address pattern (see [`osc_pattern_matches`]) matched against
+    /// the incoming address, including any prefix
+    pub address: String,
+    /// The largest change that is dropped
+    pub threshold: f64,
+}
+
+impl ValueRange {
+    /// Checks that the range can be clamped into: `min` is no larger than
+    /// `max`, and there is an integer between them.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message naming the range's address if it can't.
+    pub fn check(&self) -> Result<(), String> {
+        if self.min.is_nan() || self.max.is_nan() || self.min > self.max {
+            return Err(format!(
+                "Invalid value range for {}: min {} is larger than max {}",
+                self.address, self.min, self.max
+            ));
+        }
+        if self.min.ceil() > self.max.floor() {
+            return Err(format!(
+                "Invalid value range for {}: there is no integer between {} and {}",
+                self.address, self.min, self.max
+            ));
+        }
+        Ok(())
+    }
+
+    /// Clamps `arg` into the range. A range that fails [`check`](Self::check)
+    /// gives `max`, rather than panicking.
+    fn clamp(&self, arg: &OscType) -> OscType {
+        let (min, max) = (self.min.ceil(), self.max.floor());
+        match arg {
+            OscType::Int(i) => OscType::Int((*i as f64).max(min).min(max).round() as i32),
+            OscType::Long(l) => OscType::Long((*l as f64).max(min).min(max).round() as i64),
+            OscType::Float(f) => OscType::Float(f.max(self.min as f32).min(self.max as f32)),
+            OscType::Double(d) => OscType::Double(d.max(self.min).min(self.max)),
+            _ => arg.clone(),
+        }
+    }
+}
+
+impl ArgType {
+    /// Converts `arg` to this type, leaving arguments that aren't numbers
+    /// as they are. Floats are rounded to integers with `round_mode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConversionError::IntOverflow`] if a float rounds to a value
+    /// outside the `i32` range, or isn't finite.
+    fn coerce(self, arg: &OscType, round_mode: RoundMode) -> Result<OscType, ConversionError> {
+        Ok(match (self, arg) {
+            (ArgType::Int, OscType::Float(f)) => OscType::Int(round_mode.to_int(f64::from(*f))?),
+            (ArgType::Int, OscType::Double(d)) => OscType::Int(round_mode.to_int(*d)?),
+            (ArgType::Float, OscType::Int(i)) => OscType::Float(*i as f32),
+            (ArgType::String, OscType::Int(i)) => OscType::String(i.to_string()),
+            (ArgType::String, OscType::Float(f)) => OscType::String(f.to_string()),
+            (_, OscType::Array(array)) => OscType::Array(rosc::OscArray {
+                content: array
+                    .content
+                    .iter()
+                    .map(|arg| self.coerce(arg, round_mode))
+                    .collect::<Result<_, _>>()?,
+            }),
+            _ => arg.clone(),
+        })
+    }
+
+    /// Converts the RCP argument `arg` to this type, keeping strings
+    /// exactly as written rather than reading them as numbers first. A
+    /// number that can't be converted is left as it is.
+    fn read_rcp(self, arg: &str, round_mode: RoundMode) -> OscType {
+        let converted = rcp_to_osc_type(arg);
+        match self {
+            ArgType::String => OscType::String(arg.trim().to_string()),
+            _ => self.coerce(&converted, round_mode).unwrap_or(converted),
+        }
+    }
+
+    /// The type named by a typed-address suffix segment: `i`, `f` or `s`.
+    fn from_suffix(segment: &str) -> Option<ArgType> {
+        match segment {
+            "i" => Some(ArgType::Int),
+            "f" => Some(ArgType::Float),
+            "s" => Some(ArgType::String),
+            _ => None,
+        }
+    }
+}
+
+impl ConversionOptions {
+    /// Whether `address` carries comma-separated lists.
+    fn is_list_address(&self, address: &str) -> bool {
+        self.list_addresses
+            .iter()
+            .any(|pattern| osc_pattern_matches(pattern, address))
+    }
+
+    /// The first of [`arg_groups`](Self::arg_groups) matching `address`.
+    fn arg_group(&self, address: &str) -> Option<&ArgGroup> {
+        self.arg_groups
+            .iter()
+            .find(|group| osc_pattern_matches(&group.address, address))
+    }
+
+    /// Whether `arg` is a [`sequence_numbers`](Self::sequence_numbers)
+    /// argument.
+    pub(crate) fn is_sequence_number(&self, arg: &OscType) -> bool {
+        self.sequence_numbers && matches!(arg, OscType::Long(_))
+    }
+
+    /// Whether `arg` is the configured [`source_tag`](Self::source_tag).
+    pub(crate) fn is_source_tag(&self, arg: &OscType) -> bool {
+        !self.source_tag.is_empty() && matches!(arg, OscType::String(s) if *s == self.source_tag)
+    }
+
+    /// The OSC base address structured errors with `code` are sent under.
+    fn error_address(&self, code: i32) -> &str {
+        self.error_addresses
+            .iter()
+            .find(|mapping| mapping.codes.contains(&code))
+            .map_or("/error", |mapping| mapping.address.trim_end_matches('/'))
+    }
+
+    /// The RCP path for the OSC parameter name `osc`.
+    fn rcp_parameter<'a>(&'a self, osc: &'a str) -> &'a str {
+        self.parameter_mappings
+            .iter()
+            .find(|mapping| mapping.osc == osc)
+            .map_or(osc, |mapping| &mapping.rcp)
+    }
+
+    /// The argument types configured for the OSC parameter name `osc`.
+    fn osc_arg_types(&self, osc: &str) -> &[ArgType] {
+        self.parameter_mappings
+            .iter()
+            .find(|mapping| mapping.osc == osc)
+            .map_or(&[], |mapping| &mapping.arg_types)
+    }
+
+    /// The argument types configured for the RCP path `rcp`.
+    fn rcp_arg_types(&self, rcp: &str) -> &[ArgType] {
+        self.parameter_mappings
+            .iter()
+            .find(|mapping| mapping.rcp == rcp)
+            .map_or(&[], |mapping| &mapping.arg_types)
+    }
+
+    /// The OSC parameter name for the RCP path `rcp`.
+    fn osc_parameter<'a>(&'a self, rcp: &'a str) -> &'a str {
+        self.parameter_mappings
+            .iter()
+            .find(|mapping| mapping.rcp == rcp)
+            .map_or(rcp, |mapping| &mapping.osc)
+    }
+
+    /// Returns the OSC address prefix in its canonical `/segment` form, or
+    /// `None` if no prefix is configured.
+    fn address_prefix(&self) -> Option<String> {
+        normalize_prefix(&self.osc_address_prefix)
+    }
+
+    /// Whether `segment` is an RCP command, or one of `verb_mappings`,
+    /// rather than the start of a parameter path.
+    fn is_verb(&self, segment: &str) -> bool {
+        if self
+            .verb_mappings
+            .iter()
+            .any(|mapping| mapping.osc == segment)
+        {
+            true
+        } else if self.verbs.is_empty() {
+            RCP_VERBS.contains(&segment)
+        } else {
+            self.verbs.iter().any(|verb| verb == segment)
+        }
+    }
+
+    /// The segment put before the address of a message of `kind` (`OK` or
+    /// `NOTIFY`), if one is configured.
+    fn kind_prefix(&self, kind: RcpMessageKind) -> Option<String> {
+        match kind {
+            RcpMessageKind::Ok => normalize_prefix(&self.ok_prefix),
+            RcpMessageKind::Notify => normalize_prefix(&self.notify_prefix),
+            RcpMessageKind::Error => None,
+        }
+    }
+
+    /// Removes an `ok_prefix` or `notify_prefix` from the start of an
+    /// incoming address.
+    fn strip_kind_prefix<'a>(&self, address: &'a str) -> &'a str {
+        [RcpMessageKind::Ok, RcpMessageKind::Notify]
+            .into_iter()
+            .filter_map(|kind| self.kind_prefix(kind))
+            .find_map(|prefix| {
+                address
+                    .strip_prefix(&prefix)
+                    .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .unwrap_or(address)
+    }
+}
+
+/// The RCP commands recognized by default as the first segment of an OSC
+/// address when [`ConversionOptions::default_verb`] is set.
+pub const RCP_VERBS: &[&str] = &[
+    "set",
+    "get",
+    "prminfo",
+    "prmnum",
+    "mtrinfo",
+    "mtrstart",
+    "mtrstop",
+    "sscurrent_ex",
+    "ssinfo_ex",
+    "ssrecall_ex",
+    "ssupdate_ex",
+    "devinfo",
+    "devstatus",
+    "scpmode",
+    "subscribe",
+    "unsubscribe",
+];
+
+/// `prefix` with exactly one leading and no trailing `/`, or `None` if it's
+/// empty.
+fn normalize_prefix(prefix: &str) -> Option<String> {
+    let trimmed = prefix.trim_matches('/');
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(format!("/{}", trimmed))
+    }
+}
+
+/// Error returned when a message can't be converted between RCP and OSC.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ConversionError {
+    /// The RCP line or OSC address has no content.
+    Empty,
+    /// The OSC address doesn't start with the configured prefix.
+    MissingPrefix {
+        /// The address that was received
+        address: String,
+        /// The prefix it was expected to start with
+        prefix: String,
+    },
+    /// The RCP line has fewer tokens than its message type requires.
+    TooFewParts,
+    /// The RCP line isn't a `NOTIFY`, `OK` or `ERROR` message.
+    UnsupportedMessageType(String),
+    /// The OSC argument type has no RCP equivalent.
+    UnsupportedArgType(#[cfg_attr(feature = "serde", serde(with = "osc_arg"))] OscType),
+    /// The RCP argument for a blob address isn't valid in the configured
+    /// [`BlobEncoding`].
+    InvalidBlob(String),
+    /// The OSC float is NaN or infinite, which RCP has no way to write.
+    NonFiniteFloat(f32),
+    /// The OSC argument is an `Infinitum`, which RCP has no way to write.
+    Infinitum,
+    /// The float coerced to an integer is out of the `i32` range RCP
+    /// values use, or isn't finite.
+    IntOverflow(f64),
+    /// The OSC address or a string argument contains a line break or other
+    /// control character, which would split or corrupt the RCP command.
+    ControlCharacter(String),
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::Empty => write!(f, "Empty message"),
+            ConversionError::MissingPrefix { address, prefix } => write!(
+                f,
+                "OSC address {} does not start with prefix {}",
+                address, prefix
+            ),
+            ConversionError::TooFewParts => write!(f, "Too few parts in RCP message"),
+            ConversionError::UnsupportedMessageType(kind) => {
+                write!(f, "Unsupported message type: {}", kind)
+            }
+            ConversionError::UnsupportedArgType(arg) => {
+                write!(f, "Unsupported OSC type: {:?}", arg)
+            }
+            ConversionError::InvalidBlob(arg) => write!(f, "Invalid blob argument: {}", arg),
+            ConversionError::NonFiniteFloat(value) => {
+                write!(f, "Float {} can't be sent to the console", value)
+            }
+            ConversionError::Infinitum => {
+                write!(f, "OSC Infinitum can't be sent to the console")
+            }
+            ConversionError::IntOverflow(value) => {
+                write!(f, "{} is out of range for an integer argument", value)
+            }
+            ConversionError::ControlCharacter(text) => {
+                write!(
+                    f,
+                    "Control character in {:?} can't be sent to the console",
+                    text
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Serializes an [`OscType`], which has no serde support of its own, as the
+/// encoded bytes of an OSC message with it as the only argument.
+#[cfg(feature = "serde")]
+mod osc_arg {
+    use rosc::{OscMessage, OscPacket, OscType};
+    use serde::{Deserialize, Deserializer, Serializer, de, ser};
+
+    pub(crate) fn serialize<S: Serializer>(
+        arg: &OscType,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let packet = OscPacket::Message(OscMessage {
+            addr: "/".to_string(),
+            args: vec![arg.clone()],
+        });
+        let bytes =
+            rosc::encoder::encode(&packet).map_err(|e| ser::Error::custom(format!("{:?}", e)))?;
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OscType, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        match rosc::decoder::decode_udp(&bytes) {
+            Ok((_, OscPacket::Message(msg))) if msg.args.len() == 1 => {
+                Ok(msg.args.into_iter().next().unwrap())
+            }
+            _ => Err(de::Error::custom("invalid encoded OSC argument")),
+        }
+    }
+}
+
+/// Severity of a log message, analogous to levels in other logging systems.
+///
+/// Levels are ordered from least to most severe, so `level >= LogLevel::Warn`
+/// selects warnings and errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum LogLevel {
     /// Verbose, high-volume detail (e.g. raw RCP/OSC traffic) useful when
@@ -57,7 +1477,11 @@ pub type LogFn = Box<dyn Fn(LogLevel, String) + Send + Sync>;
 /// Run the Yamaha RCP <-> OSC bridge with the provided configuration.
 ///
 /// This function connects to the Yamaha RCP TCP endpoint and bridges messages
-/// to/from OSC over UDP. It runs until the TCP connection closes or an error occurs.
+/// to/from OSC over UDP. It runs until the TCP connection closes or an error
+/// occurs, or, if [`BridgeConfig::reconnect`] is set, until
+/// [`BridgeConfig::reconnect_max_attempts`] reconnects have failed. With
+/// [`BridgeConfig::consoles`], it runs until every console's bridge stops,
+/// or until one fails, which shuts down the others too.
 pub async fn run_bridge(
     config: BridgeConfig,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -75,250 +1499,290 @@ pub async fn run_bridge_with_logger(
     config: BridgeConfig,
     log: LogFn,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // RCP (TCP) settings
-    let rcp_port = config.rcp_port;
-    let rcp_host = config.console_ip.clone();
-
-    // OSC (UDP) settings
-    let osc_out_addr = format!("{}:{}", config.udp_osc_out_addr, config.udp_osc_out_port);
-    let osc_in_addr = format!("{}:{}", config.udp_osc_in_addr, config.udp_osc_in_port);
-
-    // Set up UDP sockets with SO_REUSEADDR to allow quick restart
-    let socket_out = UdpSocket::bind("0.0.0.0:0").await?;
-
-    // Create socket with reuse options for incoming OSC
-    let addr: SocketAddr = osc_in_addr
-        .parse()
-        .map_err(|e| format!("Invalid OSC address: {}", e))?;
-    let socket = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
-    socket.set_reuse_address(true)?;
-
-    // On Unix systems, also set SO_REUSEPORT for immediate reuse
-    #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
-    {
-        use std::os::unix::io::AsRawFd;
-        let fd = socket.as_raw_fd();
-        unsafe {
-            let optval: libc::c_int = 1;
-            libc::setsockopt(
-                fd,
-                libc::SOL_SOCKET,
-                libc::SO_REUSEPORT,
-                &optval as *const _ as *const libc::c_void,
-                std::mem::size_of_val(&optval) as libc::socklen_t,
-            );
-        }
-    }
-
-    socket.bind(&addr.into())?;
-    socket.set_nonblocking(true)?;
-    let std_socket: std::net::UdpSocket = socket.into();
-    let socket_in = Arc::new(UdpSocket::from_std(std_socket)?);
-
-    log(
-        LogLevel::Info,
-        format!("Listening for OSC messages on: {}", osc_in_addr),
-    );
-    log(
-        LogLevel::Info,
-        format!("Sending OSC messages to: {}", osc_out_addr),
-    );
-    log(
-        LogLevel::Info,
-        format!("Attempting to connect to Yamaha RCP: {}", rcp_host),
-    );
-
-    // Connect to TCP RCP
-    match TcpStream::connect((rcp_host.clone(), rcp_port)).await {
-        Ok(stream) => {
-            log(
-                LogLevel::Info,
-                format!("Connected to Yamaha RCP: {}", rcp_host),
-            );
-            let mut buffer = [0; 1024];
-            let socket_in_clone = Arc::clone(&socket_in);
-            let (mut rcp_read, rcp_write) = stream.into_split();
-            let rcp_write = Arc::new(Mutex::new(rcp_write));
-            let rcp_write_clone = Arc::clone(&rcp_write);
-
-            // Spawn a task to handle incoming OSC messages
-            let log_clone = Arc::new(log);
-            let log_for_osc = Arc::clone(&log_clone);
-            tokio::spawn(async move {
-                if let Err(_e) =
-                    handle_incoming_osc(socket_in_clone, rcp_write_clone, log_for_osc).await
-                {
-                    // Error already logged in handle_incoming_osc
-                }
-            });
+    if config.consoles.is_empty() {
+        return Bridge::connect(config, log).await?.run().await;
+    }
 
-            //RCP commands can sometimes be sent in bundles and should be split by newline
-            let mut incomplete_line = String::new();
-            loop {
-                match rcp_read.read(&mut buffer).await {
-                    Ok(0) => {
-                        log_clone(LogLevel::Warn, "Connection closed by server".to_string());
-                        break;
-                    }
-                    Ok(n) => {
-                        let data = String::from_utf8_lossy(&buffer[..n]);
-                        incomplete_line.push_str(&data);
-
-                        // Process each complete line
-                        while let Some(newline_pos) = incomplete_line.find('\n') {
-                            let line = incomplete_line[..newline_pos].to_string();
-                            incomplete_line = incomplete_line[newline_pos + 1..].to_string();
-                            let parts = split_respecting_quotes(line.trim());
-
-                            if parts.is_empty() {
-                                continue;
-                            }
-
-                            log_clone(LogLevel::Debug, format!("Received RCP: {}", line.trim()));
-
-                            let osc_message = match rcp_to_osc(line) {
-                                Ok(cmd) => cmd,
-                                Err(e) => {
-                                    log_clone(
-                                        LogLevel::Error,
-                                        format!("Failed to convert RCP to OSC: {}", e),
-                                    );
-                                    continue;
-                                }
-                            };
-
-                            //This is a special work around for the Yamaha RCP
-                            //The Yamaha RCP does not show all of the 'scene' data needed in sscurrent_ex
-                            //So we need to send the ssinfo_ex command to get the current scene information
-                            if parts[0].as_str() == "NOTIFY" && parts[1].as_str() == "sscurrent_ex"
-                            {
-                                let rcp_command = format!("ssinfo_ex {}\n", parts[2..].join(" "));
-
-                                if let Err(e) = rcp_write
-                                    .lock()
-                                    .await
-                                    .write_all(rcp_command.as_bytes())
-                                    .await
-                                {
-                                    log_clone(
-                                        LogLevel::Error,
-                                        format!("Failed to write to RCP stream: {}", e),
-                                    );
-                                }
-                            }
-
-                            log_clone(LogLevel::Debug, format!("Sending OSC: {}", osc_message));
-
-                            // Convert to packet and send
-                            let packet = rosc::OscPacket::Message(osc_message);
-                            let encoded = rosc::encoder::encode(&packet)?;
-                            socket_out.send_to(&encoded, osc_out_addr.clone()).await?;
-                        }
-                    }
-                    Err(e) => {
-                        log_clone(LogLevel::Error, format!("Failed to receive data: {}", e));
-                        break;
-                    }
-                }
-            }
+    let bridges = Bridge::connect_all(config, log).await?;
+    Bridge::run_all(&bridges).await
+}
+
+/// The kind of a message received from the console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RcpMessageKind {
+    /// An unsolicited change notification.
+    Notify,
+    /// A successful reply to a command.
+    Ok,
+    /// A failed reply to a command.
+    Error,
+}
+
+impl RcpMessageKind {
+    /// The kind of a message starting with `token`, ignoring case, so
+    /// `notify` and `Notify` are read as `NOTIFY` for sources that don't
+    /// write it in capitals.
+    fn from_token(token: &str) -> Option<RcpMessageKind> {
+        [
+            RcpMessageKind::Notify,
+            RcpMessageKind::Ok,
+            RcpMessageKind::Error,
+        ]
+        .into_iter()
+        .find(|kind| token.eq_ignore_ascii_case(kind.as_rcp()))
+    }
+
+    /// The token that starts a message of this kind, e.g. `NOTIFY`.
+    fn as_rcp(self) -> &'static str {
+        match self {
+            RcpMessageKind::Notify => "NOTIFY",
+            RcpMessageKind::Ok => "OK",
+            RcpMessageKind::Error => "ERROR",
         }
-        Err(e) => {
-            log(LogLevel::Error, format!("Failed to connect: {}", e));
-            // Return error to stop the bridge gracefully
-            return Err(format!("Connection failed: {}", e).into());
+    }
+}
+
+impl std::fmt::Display for RcpMessageKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RcpMessageKind::Notify => "notify",
+            RcpMessageKind::Ok => "ok",
+            RcpMessageKind::Error => "error",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for RcpMessageKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "notify" => Ok(RcpMessageKind::Notify),
+            "ok" => Ok(RcpMessageKind::Ok),
+            "error" => Ok(RcpMessageKind::Error),
+            _ => Err(format!(
+                "Unknown RCP message type '{}' (expected 'notify', 'ok' or 'error')",
+                s
+            )),
         }
     }
+}
 
-    Ok(())
+/// A message received from the console, split into its kind and the
+/// remaining tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RcpMessage {
+    /// Whether this is a `NOTIFY`, `OK` or `ERROR`
+    pub kind: RcpMessageKind,
+    /// The tokens following the kind, as split by [`split_respecting_quotes`]
+    /// (quoted strings keep their quotes)
+    pub args: Vec<String>,
 }
 
-async fn handle_incoming_osc(
-    socket: Arc<UdpSocket>,
-    stream: Arc<Mutex<tokio::net::tcp::OwnedWriteHalf>>,
-    log: Arc<LogFn>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut buf = [0u8; 1024];
-
-    loop {
-        match socket.recv_from(&mut buf).await {
-            Ok((size, _addr)) => {
-                if let Ok((_remaining, packet)) = rosc::decoder::decode_udp(&buf[..size]) {
-                    match packet {
-                        rosc::OscPacket::Message(msg) => {
-                            log(LogLevel::Debug, format!("Received OSC: {}", msg));
-                            let rcp_command = match osc_to_rcp(&msg) {
-                                Ok(cmd) => cmd,
-                                Err(e) => {
-                                    log(
-                                        LogLevel::Error,
-                                        format!("Failed to convert OSC to RCP: {}", e),
-                                    );
-                                    continue;
-                                }
-                            };
-                            log(LogLevel::Debug, format!("Sending RCP: {}", rcp_command));
-                            if let Err(e) = stream
-                                .lock()
-                                .await
-                                .write_all(format!("{}\n", rcp_command).as_bytes())
-                                .await
-                            {
-                                log(
-                                    LogLevel::Error,
-                                    format!("Failed to write to RCP stream: {}", e),
-                                );
-                                continue;
-                            }
-                        }
-                        rosc::OscPacket::Bundle(_) => {
-                            log(
-                                LogLevel::Warn,
-                                "Received OSC bundle - not implemented".to_string(),
-                            );
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                log(
-                    LogLevel::Error,
-                    format!("Error receiving OSC message: {}", e),
-                );
-                break;
-            }
+impl RcpMessage {
+    /// Parses a line received from the console.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the line is empty or isn't a `NOTIFY`, `OK` or
+    /// `ERROR` message.
+    pub fn parse(line: &str) -> Result<RcpMessage, ConversionError> {
+        RcpMessage::from_parts(&split_respecting_quotes(line.trim()))
+    }
+
+    /// Builds a message from a line that has already been split by
+    /// [`split_respecting_quotes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `parts` is empty or isn't a `NOTIFY`, `OK` or
+    /// `ERROR` message.
+    pub fn from_parts(parts: &[String]) -> Result<RcpMessage, ConversionError> {
+        let mut parts = parts.iter();
+        let kind = match parts.next() {
+            Some(kind) => RcpMessageKind::from_token(kind)
+                .ok_or_else(|| ConversionError::UnsupportedMessageType(kind.clone()))?,
+            None => return Err(ConversionError::Empty),
+        };
+        Ok(RcpMessage {
+            kind,
+            args: parts.cloned().collect(),
+        })
+    }
+}
+
+impl std::fmt::Display for RcpMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind.as_rcp())?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
         }
+        Ok(())
     }
-    Ok(())
+}
+
+/// Decodes a string argument from a Yamaha RCP command into an OSC blob.
+///
+/// This is the inverse of how [`osc_to_rcp_arg_with`] writes blobs: the
+/// surrounding quotes are removed and the rest is decoded with `encoding`.
+///
+/// # Errors
+///
+/// Returns [`ConversionError::InvalidBlob`] if the argument isn't valid in
+/// the given encoding.
+pub fn rcp_to_osc_blob(arg: &str, encoding: BlobEncoding) -> Result<OscType, ConversionError> {
+    encoding
+        .decode(&unquote(arg.trim()))
+        .map(OscType::Blob)
+        .ok_or_else(|| ConversionError::InvalidBlob(arg.to_string()))
 }
 
 /// Converts a string argument from a Yamaha RCP command into an OSC type.
 ///
 /// If the argument can be parsed as an i32, it is converted to an `OscType::Int`.
-/// If the argument can be parsed as an f32, it is converted to an `OscType::Float`.
-/// Otherwise, it is converted to an `OscType::String`.
-pub fn rcp_to_osc_type(arg: &String) -> OscType {
+/// If the argument can be parsed as a finite f32 (including scientific
+/// notation such as `1.5e3`), it is converted to an `OscType::Float`.
+/// Otherwise, it is converted to an `OscType::String`, so words like `inf`
+/// and `nan` stay words.
+///
+/// Surrounding whitespace is trimmed first, and numbers may carry an explicit
+/// leading `+` (e.g. `+3000`), as some console firmwares format them that way.
+pub fn rcp_to_osc_type(arg: &str) -> OscType {
+    let arg = arg.trim();
     if let Ok(i) = arg.parse::<i32>() {
         OscType::Int(i)
-    } else if let Ok(f) = arg.parse::<f32>() {
+    } else if let Ok(f) = arg.parse::<f32>()
+        && f.is_finite()
+    {
         OscType::Float(f)
     } else {
         OscType::String(arg.to_string())
     }
 }
 
+/// Converts a Yamaha RCP argument to an OSC argument like
+/// [`rcp_to_osc_type`], but with [`ConversionOptions::keep_leading_zeros`]
+/// set, a whole number written with a leading zero, such as `01`, stays the
+/// string `01` rather than becoming the integer 1. `0` itself and decimals
+/// such as `0.5` are still numbers.
+pub fn rcp_to_osc_type_with(arg: &str, options: &ConversionOptions) -> OscType {
+    let trimmed = arg.trim();
+    if options.keep_leading_zeros && has_leading_zero(trimmed) {
+        OscType::String(trimmed.to_string())
+    } else {
+        rcp_to_osc_type(arg)
+    }
+}
+
+/// Whether `arg` is a number starting with a `0` followed by another digit.
+fn has_leading_zero(arg: &str) -> bool {
+    let digits = arg.strip_prefix(['+', '-']).unwrap_or(arg);
+    let mut chars = digits.chars();
+    chars.next() == Some('0')
+        && chars.next().is_some_and(|c| c.is_ascii_digit())
+        && digits.parse::<f64>().is_ok()
+}
+
+/// Percent-encodes an RCP name so it can be used in an OSC address.
+///
+/// OSC addresses may only contain printable ASCII other than space and
+/// `#*,?[]{}`, so each of those bytes, `%` itself, and any byte outside
+/// printable ASCII (including each byte of a multi-byte UTF-8 character) is
+/// written as `%` followed by two uppercase hex digits, e.g. a space becomes
+/// `%20`. `/` is left as is, as it separates segments in both protocols.
+/// [`unescape_osc_address`] reverses the encoding exactly.
+pub fn escape_osc_address(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b' ' | b'#' | b'*' | b',' | b'?' | b'[' | b']' | b'{' | b'}' | b'%' => {
+                escaped.push_str(&format!("%{:02X}", byte))
+            }
+            0x21..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Decodes an OSC address produced by [`escape_osc_address`] back into the
+/// original RCP name.
+///
+/// A `%` that isn't followed by two hex digits is kept as is, so addresses
+/// that were never escaped pass through unchanged.
+pub fn unescape_osc_address(address: &str) -> String {
+    let bytes = address.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = address
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Quotes `value` as an RCP string argument, e.g. `Vocal 1` becomes
+/// `"Vocal 1"`. A value that is already quoted is returned as is, so
+/// quoting twice does nothing.
+///
+/// RCP has no way to escape a quote inside a string, so any are written as
+/// they are.
+pub fn quote(value: &str) -> String {
+    if is_quoted(value) {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
+/// Removes the quotes around an RCP string argument, e.g. `"Vocal 1"`
+/// becomes `Vocal 1`. A token that isn't quoted is returned as is; this is
+/// the inverse of [`quote`].
+pub fn unquote(token: &str) -> String {
+    if is_quoted(token) {
+        token[1..token.len() - 1].to_string()
+    } else {
+        token.to_string()
+    }
+}
+
+/// Whether `token` starts and ends with a quote, a lone `"` not counting.
+fn is_quoted(token: &str) -> bool {
+    token.len() >= 2 && token.starts_with('"') && token.ends_with('"')
+}
+
 /// Splits a string into parts, respecting quotes.
 ///
 /// This function splits the input string into parts, where each part is separated by a space.
 /// However, if a part is enclosed in quotes, it is treated as a single part, even if it contains
 /// spaces.
 pub fn split_respecting_quotes(s: &str) -> Vec<String> {
+    split_respecting_quotes_with(s, ' ', '"')
+}
+
+/// Splits a string into parts separated by `delim`, treating a part enclosed
+/// in `quote` characters as a single part, as [`split_respecting_quotes`]
+/// does with spaces and double quotes. The quotes are kept in the part.
+pub fn split_respecting_quotes_with(s: &str, delim: char, quote: char) -> Vec<String> {
     let mut result = Vec::new();
     let mut current = String::new();
     let mut in_quotes = false;
 
     for c in s.chars() {
         match c {
-            '"' => {
+            c if c == quote => {
                 in_quotes = !in_quotes;
                 if !in_quotes {
                     // Only add quotes when closing a quoted section
@@ -328,7 +1792,7 @@ pub fn split_respecting_quotes(s: &str) -> Vec<String> {
                     current.push(c);
                 }
             }
-            ' ' if !in_quotes => {
+            c if c == delim && !in_quotes => {
                 if !current.is_empty() {
                     result.push(current);
                     current = String::new();
@@ -345,52 +1809,443 @@ pub fn split_respecting_quotes(s: &str) -> Vec<String> {
     result
 }
 
+/// Rewrites an RCP line in a canonical form, so lines that differ only in
+/// spacing or number formatting compare equal.
+///
+/// Tokens are split as by [`split_respecting_quotes`], on tabs as well as
+/// spaces, and joined with single spaces. Whole numbers that fit in an
+/// `i64` are written without a sign or leading zeros, so `+0050` becomes
+/// `50`, and decimals always with a decimal point, so `1.5e3` becomes
+/// `1500.0`. Larger whole numbers, quoted strings and other words are kept
+/// exactly as they are. Canonical lines are returned unchanged.
+///
+/// # Errors
+///
+/// Returns [`ConversionError::Empty`] if the line has no tokens.
+pub fn canonicalize_rcp(line: &str) -> Result<String, ConversionError> {
+    let parts: Vec<String> = split_respecting_quotes(line.trim())
+        .iter()
+        .flat_map(|part| split_respecting_quotes_with(part, '\t', '"'))
+        .collect();
+    if parts.is_empty() {
+        return Err(ConversionError::Empty);
+    }
+    let tokens: Vec<String> = parts
+        .into_iter()
+        .map(|part| {
+            if part.starts_with('"') {
+                return part;
+            }
+            canonical_number(&part).unwrap_or(part)
+        })
+        .collect();
+    Ok(tokens.join(" "))
+}
+
+/// The canonical form of `token` if it is a number, for
+/// [`canonicalize_rcp`]. Decimals are read as `f64` and whole numbers as
+/// `i64`, so neither loses precision on the way.
+fn canonical_number(token: &str) -> Option<String> {
+    if let Ok(i) = token.parse::<i64>() {
+        return Some(i.to_string());
+    }
+    // Anything else without a decimal point or exponent is a whole number
+    // too large for an `i64`, or not a number at all
+    if !token.contains(['.', 'e', 'E']) {
+        return None;
+    }
+    let f = token.parse::<f64>().ok().filter(|f| f.is_finite())?;
+    let s = f.to_string();
+    Some(if s.contains('.') {
+        s
+    } else {
+        format!("{}.0", s)
+    })
+}
+
 /// Converts an OSC argument to a Yamaha RCP argument.
 ///
 /// The RCP argument seems to be a string representation of the OSC argument.
+/// Floats are always written with a decimal point (see [`FloatFormat::Decimal`]).
+///
+/// Arrays are flattened depth-first into space-separated arguments, so
+/// `[255, 0, 0]` becomes `255 0 0`. An empty array produces no arguments,
+/// and so does `Nil` (see [`NilArg`]). Blobs are written as a quoted string
+/// in the configured [`BlobEncoding`]. Timetags are written as whole
+/// seconds since the Unix epoch, dropping the fraction of a second (see
+/// [`osc_time_to_unix`]).
+///
+/// # Errors
+///
+/// Returns an error if the OSC argument type is not supported, or if a
+/// float is NaN or infinite or the argument is an `Infinitum`, which the
+/// console can't accept.
+pub fn osc_to_rcp_arg(arg: &OscType) -> Result<String, ConversionError> {
+    osc_to_rcp_arg_with(arg, &ConversionOptions::default())
+}
+
+/// Converts an OSC argument to a Yamaha RCP argument using the given options.
 ///
 /// # Errors
 ///
 /// Returns an error if the OSC argument type is not supported.
-pub fn osc_to_rcp_arg(arg: &OscType) -> Result<String, String> {
+pub fn osc_to_rcp_arg_with(
+    arg: &OscType,
+    options: &ConversionOptions,
+) -> Result<String, ConversionError> {
     match arg {
         OscType::Int(i) => Ok(i.to_string()),
-        OscType::Float(f) => Ok(f.to_string()),
-        OscType::String(s) => {
-            // If the string is already quoted, return it as is
-            if s.starts_with('"') && s.ends_with('"') {
-                Ok(s.to_string())
-            } else {
-                Ok(format!("\"{}\"", s))
+        OscType::Float(f) if !f.is_finite() => Err(ConversionError::NonFiniteFloat(*f)),
+        OscType::Float(f) => Ok(format_float(*f, options)),
+        OscType::String(s) if s.chars().any(char::is_control) => {
+            Err(ConversionError::ControlCharacter(s.clone()))
+        }
+        OscType::String(s) => Ok(quote(s)),
+        OscType::Blob(bytes) => Ok(quote(&options.blob_encoding.encode(bytes))),
+        OscType::Time(time) => Ok(osc_time_to_unix(*time).to_string()),
+        // Written as nothing, which is left out like an empty array
+        OscType::Nil if options.nil_arg == NilArg::Omit => Ok(String::new()),
+        OscType::Inf => Err(ConversionError::Infinitum),
+        OscType::Array(array) => {
+            let mut args = Vec::with_capacity(array.content.len());
+            for element in &array.content {
+                let converted = osc_to_rcp_arg_with(element, options)?;
+                // Skip nested empty arrays so they don't leave double spaces
+                if !converted.is_empty() {
+                    args.push(converted);
+                }
             }
+            Ok(args.join(" "))
         }
-        _ => Err("Unsupported OSC type".to_string()),
+        _ => Err(ConversionError::UnsupportedArgType(arg.clone())),
+    }
+}
+
+/// Seconds from the NTP epoch OSC timetags count from (1900) to the Unix
+/// epoch (1970).
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// The whole seconds since the Unix epoch of an OSC timetag, which counts
+/// from 1900. Times before 1970 are negative.
+pub fn osc_time_to_unix(time: OscTime) -> i64 {
+    i64::from(time.seconds) - NTP_UNIX_OFFSET
+}
+
+/// The OSC timetag for `seconds` since the Unix epoch, or `None` if it's
+/// outside the range a timetag can hold (1900 to 2036).
+pub fn unix_to_osc_time(seconds: i64) -> Option<OscTime> {
+    let seconds = u32::try_from(seconds + NTP_UNIX_OFFSET).ok()?;
+    Some(OscTime {
+        seconds,
+        fractional: 0,
+    })
+}
+
+/// Formats a float as an RCP argument.
+///
+/// Without fixed decimals, Rust's `Display` gives the shortest form that
+/// parses back to the same `f32`, so `0.1` stays `0.1`. It drops the decimal
+/// point on integer values (`3.0` prints as `3`), so it is added back unless
+/// the float format asks for it to be left off.
+fn format_float(f: f32, options: &ConversionOptions) -> String {
+    if options.float_format == FloatFormat::IntegerWhenWhole && f.fract() == 0.0 {
+        return f.to_string();
+    }
+    let s = match options.float_decimals {
+        Some(decimals) => format!("{:.*}", decimals, f),
+        None => f.to_string(),
+    };
+    match options.float_format {
+        FloatFormat::Decimal if !s.contains('.') => format!("{}.0", s),
+        _ => s,
     }
 }
 
 /// Converts an OSC message to a Yamaha RCP command.
 ///
-/// The RCP command is in the format `<command> <argument1> <argument2> ...`
-/// where `<command>` is the first part of the OSC address, and the
-/// `<argumentN>` are the arguments of the OSC message.
+/// The RCP command is in the format `<command> <path> <argument1> <argument2> ...`
+/// where `<command>` is the first part of the OSC address, `<path>` is the
+/// rest of the address, and the `<argumentN>` are the arguments of the OSC
+/// message.
+///
+/// A single-segment address such as `/devstatus` is a bare command with no
+/// path, so only the command and any arguments are written. Parts are
+/// separated by single spaces with no trailing space.
+///
+/// Every segment after the command belongs to the path, joined with `/`,
+/// so `/set/MIXER:Current/InCh/Fader/Level` is written as
+/// `set MIXER:Current/InCh/Fader/Level`. The module may also be given as a
+/// segment of its own: when the segment after the command is an upper-case
+/// module name such as `MIXER` followed by at least one more segment, the
+/// two are joined with `:`, so `/set/MIXER/Current/InCh/Fader/Level` is
+/// written the same way. Console NOTIFYs always come back in the `:` form.
+///
+/// Percent-encoded characters in the address (see [`escape_osc_address`])
+/// are decoded back into the original RCP name.
+///
+/// `/subscribe/<path> <args>` and `/unsubscribe/<path> <args>` are written
+/// as the RCP `subscribe` and `unsubscribe` commands like any other; see
+/// [`ConversionOptions::verb_mappings`] for consoles that use other
+/// commands.
 ///
 /// # Errors
 ///
-/// Returns an error if the OSC address is empty or invalid.
-pub fn osc_to_rcp(msg: &OscMessage) -> Result<String, String> {
-    let address = msg.addr.clone();
+/// Returns an error if the OSC address is empty or invalid, or the message
+/// can't be written as a single RCP command, e.g. a string argument has a
+/// line break in it.
+pub fn osc_to_rcp(msg: &OscMessage) -> Result<String, ConversionError> {
+    osc_to_rcp_with(msg, &ConversionOptions::default())
+}
+
+/// Joins the OSC address segments after the command into an RCP parameter
+/// path, restoring the `:` after a module given as its own segment (see
+/// [`osc_to_rcp`]).
+fn rcp_path(segments: &[&str]) -> String {
+    match segments {
+        [module, rest @ ..] if !rest.is_empty() && is_rcp_module(module) => {
+            format!("{}:{}", module, rest.join("/"))
+        }
+        _ => segments.join("/"),
+    }
+}
+
+/// Whether `segment` looks like an RCP module name such as `MIXER` or `IO`.
+fn is_rcp_module(segment: &str) -> bool {
+    segment.starts_with(|c: char| c.is_ascii_uppercase())
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Clamps the value of `msg` into the first of `options.value_ranges`
+/// matching its address.
+///
+/// Returns the original and the clamped value if it was out of range and
+/// has been replaced, so the caller can report them. [`osc_to_rcp_with`]
+/// clamps on its own, so this is only needed to find out whether a value
+/// was changed. A trailing [`source_tag`](ConversionOptions::source_tag) or sequence
+/// number isn't the value.
+pub fn clamp_osc_value(
+    msg: &mut OscMessage,
+    options: &ConversionOptions,
+) -> Option<(OscType, OscType)> {
+    let range = options
+        .value_ranges
+        .iter()
+        .find(|range| osc_pattern_matches(&range.address, &msg.addr))?;
+    let mut len = msg.args.len();
+    if msg.args[..len]
+        .last()
+        .is_some_and(|arg| options.is_sequence_number(arg))
+    {
+        len -= 1;
+    }
+    if msg.args[..len]
+        .last()
+        .is_some_and(|arg| options.is_source_tag(arg))
+    {
+        len -= 1;
+    }
+    let value = msg.args[..len].last_mut()?;
+    let clamped = range.clamp(value);
+    if clamped == *value {
+        return None;
+    }
+    let original = std::mem::replace(value, clamped.clone());
+    Some((original, clamped))
+}
+
+/// Converts an OSC message to a Yamaha RCP command using the given options.
+///
+/// Values sent to an address with a configured [`ValueRange`] are clamped
+/// into it first, and a trailing
+/// [`source_tag`](ConversionOptions::source_tag) argument is dropped, after
+/// any trailing [sequence number](ConversionOptions::sequence_numbers).
+/// An array in the position of a matching [`ArgGroup`] is split into
+/// separate arguments.
+///
+/// # Errors
+///
+/// Returns an error if the OSC address is empty or invalid, or the message
+/// can't be written as a single RCP command, e.g. a string argument has a
+/// line break in it.
+pub fn osc_to_rcp_with(
+    msg: &OscMessage,
+    options: &ConversionOptions,
+) -> Result<String, ConversionError> {
+    let mut msg = Cow::Borrowed(msg);
+    if msg
+        .args
+        .last()
+        .is_some_and(|arg| options.is_sequence_number(arg))
+    {
+        msg.to_mut().args.pop();
+    }
+    if msg
+        .args
+        .last()
+        .is_some_and(|arg| options.is_source_tag(arg))
+    {
+        msg.to_mut().args.pop();
+    }
+    if let Some(group) = options.arg_group(&msg.addr) {
+        group.split(&mut msg.to_mut().args);
+    }
+    if !options.value_ranges.is_empty() {
+        clamp_osc_value(msg.to_mut(), options);
+    }
+
+    let address = match options.address_prefix() {
+        Some(prefix) => match msg.addr.strip_prefix(&prefix) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => rest.to_string(),
+            _ => {
+                return Err(ConversionError::MissingPrefix {
+                    address: msg.addr.clone(),
+                    prefix,
+                });
+            }
+        },
+        None => msg.addr.clone(),
+    };
+    let address = options.strip_kind_prefix(&address);
 
     // Split address and remove empty parts
-    let parts: Vec<&str> = address.split('/').filter(|s| !s.is_empty()).collect();
+    let mut parts: Vec<&str> = address.split('/').filter(|s| !s.is_empty()).collect();
 
     if parts.is_empty() {
-        return Err("Invalid OSC address".to_string());
+        return Err(ConversionError::Empty);
+    }
+
+    let mut arg_type = None;
+    if options.type_suffixes
+        && parts.len() > 1
+        && let Some(suffix) = parts.last().and_then(|part| ArgType::from_suffix(part))
+    {
+        arg_type = Some(suffix);
+        parts.pop();
     }
 
-    let rcp_command = format!("{} {}", parts[0], parts[1..].join("/"));
-    let args: Result<Vec<String>, String> = msg.args.iter().map(osc_to_rcp_arg).collect();
-    let args = args.map_err(|e| format!("Failed to convert OSC arg: {}", e))?;
-    Ok(format!("{} {}", rcp_command, args.join(" ")))
+    let mut tokens = Vec::new();
+    let mut arg_types: &[ArgType] = &[];
+    if let Some((path, index_args)) = options
+        .address_aliases
+        .iter()
+        .find_map(|alias| alias.rcp_for(&parts))
+    {
+        tokens.push(if msg.args.is_empty() { "get" } else { "set" }.to_string());
+        arg_types = options.rcp_arg_types(&path);
+        tokens.push(path);
+        msg.to_mut().args.splice(0..0, index_args);
+        parts.clear();
+    } else if !options.default_verb.is_empty() && !options.is_verb(parts[0]) {
+        tokens.push(options.default_verb.clone());
+    } else {
+        tokens.push(unescape_osc_address(parts[0]));
+        parts.remove(0);
+    }
+    if let Some(mapping) = options.verb_mappings.iter().find(|m| m.osc == tokens[0]) {
+        tokens[0] = mapping.rcp.clone();
+    }
+    if !parts.is_empty() {
+        let name = unescape_osc_address(&rcp_path(&parts));
+        tokens.push(options.rcp_parameter(&name).to_string());
+        arg_types = options.osc_arg_types(&name);
+    }
+    let coercions: Vec<&TypeCoercion> = options
+        .type_coercions
+        .iter()
+        .filter(|coercion| osc_pattern_matches(&coercion.address, &msg.addr))
+        .collect();
+    let is_list = options.is_list_address(&msg.addr);
+    for (i, arg) in msg.args.iter().enumerate() {
+        let coercion = coercions
+            .iter()
+            .find(|coercion| coercion.applies_to(i))
+            .map(|coercion| coercion.arg_type);
+        let arg = match arg_type.or(coercion).or_else(|| arg_types.get(i).copied()) {
+            Some(arg_type) => Cow::Owned(arg_type.coerce(arg, options.round_mode)?),
+            None => Cow::Borrowed(arg),
+        };
+        let arg = match &*arg {
+            OscType::Array(array) if is_list => array
+                .content
+                .iter()
+                .map(|arg| osc_to_rcp_arg_with(arg, options))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(","),
+            arg => osc_to_rcp_arg_with(arg, options)?,
+        };
+        // An empty array has no arguments to write
+        if !arg.is_empty() {
+            tokens.push(arg);
+        }
+    }
+    let command = tokens.join(" ");
+    // The address can carry escaped control characters, e.g. `%0A`, and a
+    // line break would send the console part of the command as another
+    if command.chars().any(char::is_control) {
+        return Err(ConversionError::ControlCharacter(command));
+    }
+    Ok(command)
+}
+
+/// The `get` commands for a message sent to `address`, if it is one of the
+/// [`ConversionOptions::bulk_gets`]: one for each index in its range, in
+/// order.
+pub fn expand_bulk_get(address: &str, options: &ConversionOptions) -> Option<Vec<String>> {
+    options
+        .bulk_gets
+        .iter()
+        .find(|bulk_get| bulk_get.address == address)
+        .map(BulkGet::commands)
+}
+
+/// Converts an OSC packet to Yamaha RCP commands.
+///
+/// A message converts to a single command, or to one for each index of a
+/// bulk get (see [`expand_bulk_get`]). Bundles are flattened
+/// recursively, depth-first, so the commands are in the order the messages
+/// appear in the packet.
+///
+/// # Errors
+///
+/// Returns an error if any message in the packet can't be converted, in
+/// which case none of its commands are returned.
+pub fn osc_packet_to_rcp(packet: &OscPacket) -> Result<Vec<String>, ConversionError> {
+    osc_packet_to_rcp_with(packet, &ConversionOptions::default())
+}
+
+/// Converts an OSC packet to Yamaha RCP commands using the given options.
+///
+/// # Errors
+///
+/// Returns an error if any message in the packet can't be converted.
+pub fn osc_packet_to_rcp_with(
+    packet: &OscPacket,
+    options: &ConversionOptions,
+) -> Result<Vec<String>, ConversionError> {
+    let mut commands = Vec::new();
+    collect_rcp_commands(packet, options, &mut commands)?;
+    Ok(commands)
+}
+
+fn collect_rcp_commands(
+    packet: &OscPacket,
+    options: &ConversionOptions,
+    commands: &mut Vec<String>,
+) -> Result<(), ConversionError> {
+    match packet {
+        OscPacket::Message(msg) => match expand_bulk_get(&msg.addr, options) {
+            Some(expanded) => commands.extend(expanded),
+            None => commands.push(osc_to_rcp_with(msg, options)?),
+        },
+        OscPacket::Bundle(bundle) => {
+            for packet in &bundle.content {
+                collect_rcp_commands(packet, options, commands)?;
+            }
+        }
+    }
+    Ok(())
 }
 
 /// Converts a Yamaha RCP message to an OSC message.
@@ -398,30 +2253,172 @@ pub fn osc_to_rcp(msg: &OscMessage) -> Result<String, String> {
 /// The RCP message is expected to be in one of the following formats:
 /// * `NOTIFY <type> <name> <arg1> <arg2> ...`
 /// * `OK <type> <name> <arg1> <arg2> ...`
+/// * `ERROR <code> <message>`
 /// * `ERROR <arg1> <arg2> ...`
 ///
 /// The corresponding OSC messages are:
 /// * `/type/name <arg1> <arg2> ...`
 /// * `/type/name <arg1> <arg2> ...`
+/// * `/error/<code> <code> <message>`
 /// * `/error <arg1> <arg2> ...`
 ///
+/// An ERROR is treated as structured when its first token is an integer
+/// code; the remaining tokens are kept together as a single string message.
+///
+/// The type and name are escaped with [`escape_osc_address`], so characters
+/// that aren't legal in an OSC address (such as spaces or `*`) are
+/// percent-encoded.
+///
+/// # Errors
+///
+/// Returns an error if the RCP message type is not supported, or a `NOTIFY`
+/// or `OK` lacks its type and name.
+pub fn rcp_to_osc(line: String) -> Result<OscMessage, ConversionError> {
+    rcp_to_osc_with(line, &ConversionOptions::default())
+}
+
+/// Converts a Yamaha RCP message to an OSC message using the given options.
+///
+/// If an OSC address prefix is configured, it is prepended to the address,
+/// and a source tag is appended as the last argument. Structured errors
+/// whose code is in [`ConversionOptions::error_addresses`] are sent under
+/// the mapped base address instead of `/error`.
+///
+/// # Errors
+///
+/// Returns an error if the RCP message type is not supported.
+pub fn rcp_to_osc_with(
+    line: String,
+    options: &ConversionOptions,
+) -> Result<OscMessage, ConversionError> {
+    rcp_parts_to_osc_with(&split_respecting_quotes(line.trim()), options)
+}
+
+/// Converts a Yamaha RCP message that has already been split by
+/// [`split_respecting_quotes`] to an OSC message.
+///
+/// This avoids splitting the line a second time when the caller needs to
+/// inspect the parts itself; see [`rcp_to_osc`] for the message formats.
+///
+/// # Errors
+///
+/// Returns an error if the RCP message type is not supported, or a `NOTIFY`
+/// or `OK` lacks its type and name.
+pub fn rcp_parts_to_osc(parts: &[String]) -> Result<OscMessage, ConversionError> {
+    rcp_parts_to_osc_with(parts, &ConversionOptions::default())
+}
+
+/// Converts an already-split Yamaha RCP message to an OSC message using the
+/// given options.
+///
 /// # Errors
 ///
 /// Returns an error if the RCP message type is not supported.
-pub fn rcp_to_osc(line: String) -> Result<OscMessage, String> {
-    // Process the complete line
-    let parts = split_respecting_quotes(line.trim());
+pub fn rcp_parts_to_osc_with(
+    parts: &[String],
+    options: &ConversionOptions,
+) -> Result<OscMessage, ConversionError> {
+    let mut msg = rcp_parts_to_osc_unprefixed(parts, options)?;
+    if let Some(prefix) = options.address_prefix() {
+        msg.addr = format!("{}{}", prefix, msg.addr);
+    }
+    if options.blob_addresses.contains(&msg.addr) {
+        for arg in &mut msg.args {
+            // Blobs are always written quoted, so only quoted strings are
+            // decoded; numbers and bare words are left as they are
+            if let OscType::String(s) = arg
+                && s.starts_with('"')
+            {
+                *arg = rcp_to_osc_blob(s, options.blob_encoding)?;
+            }
+        }
+    }
+    // The value is the last argument; the index arguments before it are
+    // numbers too, but not times
+    if options.time_addresses.contains(&msg.addr)
+        && let Some(arg) = msg.args.last_mut()
+        && let Some(time) = parts
+            .last()
+            .and_then(|part| part.trim().parse::<i64>().ok())
+            .and_then(unix_to_osc_time)
+    {
+        *arg = OscType::Time(time);
+    }
+    if options.is_list_address(&msg.addr) {
+        for arg in &mut msg.args {
+            if let OscType::String(s) = arg
+                && !s.starts_with('"')
+                && s.contains(',')
+            {
+                *arg = OscType::Array(rosc::OscArray {
+                    content: s
+                        .split(',')
+                        .map(|arg| rcp_to_osc_type_with(arg, options))
+                        .collect(),
+                });
+            }
+        }
+    }
+    // Last, so blob and list arguments can still be told apart by quotes
+    if options.unquote_strings {
+        for arg in &mut msg.args {
+            if let OscType::String(s) = arg {
+                *s = unquote(s);
+            }
+        }
+    }
+    if let Some(group) = options.arg_group(&msg.addr) {
+        group.group(&mut msg.args);
+    }
+    if !options.source_tag.is_empty() {
+        msg.args.push(OscType::String(options.source_tag.clone()));
+    }
+    if let Some(kind) = RcpMessageKind::from_token(&parts[0]).and_then(|k| options.kind_prefix(k)) {
+        let at = options.address_prefix().map_or(0, |prefix| prefix.len());
+        msg.addr.insert_str(at, &kind);
+    }
+    Ok(msg)
+}
 
+fn rcp_parts_to_osc_unprefixed(
+    parts: &[String],
+    options: &ConversionOptions,
+) -> Result<OscMessage, ConversionError> {
     if parts.is_empty() {
-        return Err("Invalid OSC address".to_string());
+        return Err(ConversionError::Empty);
     }
 
-    match parts[0].as_str() {
-        "NOTIFY" | "OK" => {
+    match RcpMessageKind::from_token(&parts[0]) {
+        Some(RcpMessageKind::Notify | RcpMessageKind::Ok) if parts.len() < 3 => {
+            Err(ConversionError::TooFewParts)
+        }
+        Some(RcpMessageKind::Notify | RcpMessageKind::Ok) => {
+            let alias = match parts[1].as_str() {
+                "set" | "get" => options
+                    .address_aliases
+                    .iter()
+                    .find_map(|alias| alias.osc_for(parts)),
+                _ => None,
+            };
             // Create OSC message
-            let osc_addr_pattern = format!("/{}/{}", parts[1], parts[2]);
+            let (osc_addr_pattern, first_arg) = alias.unwrap_or_else(|| {
+                let address = format!(
+                    "/{}/{}",
+                    escape_osc_address(&parts[1]),
+                    escape_osc_address(options.osc_parameter(&parts[2]))
+                );
+                (address, 3)
+            });
 
-            let args: Vec<OscType> = parts[3..].iter().map(rcp_to_osc_type).collect();
+            let arg_types = options.rcp_arg_types(&parts[2]);
+            let args: Vec<OscType> = parts[first_arg..]
+                .iter()
+                .enumerate()
+                .map(|(i, p)| match arg_types.get(first_arg - 3 + i) {
+                    Some(arg_type) => arg_type.read_rcp(p, options.round_mode),
+                    None => rcp_to_osc_type_with(p, options),
+                })
+                .collect();
 
             let msg: OscMessage = OscMessage {
                 addr: osc_addr_pattern.clone(),
@@ -429,8 +2426,26 @@ pub fn rcp_to_osc(line: String) -> Result<OscMessage, String> {
             };
             Ok(msg)
         }
-        "ERROR" => {
-            let args: Vec<OscType> = parts[1..].iter().map(rcp_to_osc_type).collect();
+        Some(RcpMessageKind::Error) => {
+            // Structured errors carry a numeric code followed by a message,
+            // e.g. `ERROR 404 "No such parameter"`
+            if let Some(code) = parts.get(1).and_then(|p| p.parse::<i32>().ok()) {
+                let mut args = vec![OscType::Int(code)];
+                if parts.len() > 2 {
+                    let message: Vec<String> = parts[2..].iter().map(|p| unquote(p)).collect();
+                    args.push(OscType::String(message.join(" ")));
+                }
+
+                return Ok(OscMessage {
+                    addr: format!("{}/{}", options.error_address(code), code),
+                    args,
+                });
+            }
+
+            let args: Vec<OscType> = parts[1..]
+                .iter()
+                .map(|p| rcp_to_osc_type_with(p, options))
+                .collect();
 
             let msg = OscMessage {
                 addr: "/error".to_string(),
@@ -439,6 +2454,6 @@ pub fn rcp_to_osc(line: String) -> Result<OscMessage, String> {
 
             Ok(msg)
         }
-        _ => Err("Unsupported message type".to_string()),
+        None => Err(ConversionError::UnsupportedMessageType(parts[0].clone())),
     }
 }
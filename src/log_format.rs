@@ -0,0 +1,160 @@
+use crate::LogLevel;
+use crate::csv_log::{Direction, utc_timestamp};
+use std::time::SystemTime;
+
+/// How log messages are written by the command line bridge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `[LEVEL] message`, for people. This is the default.
+    #[default]
+    Text,
+    /// One JSON object per message, for log aggregators, with `timestamp`,
+    /// `level` and `message` fields. Messages about traffic also carry
+    /// `direction` (`rcp->osc` or `osc->rcp`), and `raw` (the RCP line) or
+    /// `osc_address`.
+    Json,
+}
+
+impl LogFormat {
+    /// Formats a log message, without a trailing newline.
+    pub fn format(self, level: LogLevel, message: &str) -> String {
+        match self {
+            LogFormat::Text => format!("[{}] {}", level, message),
+            LogFormat::Json => {
+                let mut record = serde_json::json!({
+                    "timestamp": utc_timestamp(SystemTime::now()),
+                    "level": level,
+                    "message": message,
+                });
+                if let Some((direction, field, value)) = traffic(message) {
+                    record["direction"] = direction.as_str().into();
+                    record[field] = value.into();
+                }
+                record.to_string()
+            }
+        }
+    }
+
+    /// Formats a log message like [`format`](Self::format), coloring text
+    /// with ANSI escape codes: the level by severity, and messages about
+    /// traffic by direction, cyan from the console and magenta to it. JSON
+    /// is never colored.
+    pub fn format_colored(self, level: LogLevel, message: &str) -> String {
+        if self == LogFormat::Json {
+            return self.format(level, message);
+        }
+        let level_color = match level {
+            LogLevel::Debug => "2",
+            LogLevel::Info => "32",
+            LogLevel::Warn => "33",
+            LogLevel::Error => "1;31",
+        };
+        let level = paint(level_color, &format!("[{}]", level));
+        match traffic(message) {
+            Some((Direction::RcpToOsc, ..)) => format!("{} {}", level, paint("36", message)),
+            Some((Direction::OscToRcp, ..)) => format!("{} {}", level, paint("35", message)),
+            None => format!("{} {}", level, message),
+        }
+    }
+}
+
+/// `text` wrapped in the ANSI escape codes for the SGR parameters `code`.
+fn paint(code: &str, text: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+/// When text log messages are colored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Only when writing to a terminal. This is the default.
+    #[default]
+    Auto,
+    /// Always, e.g. when piping through `less -R`.
+    Always,
+    /// Never.
+    Never,
+}
+
+impl ColorMode {
+    /// Whether to color messages written to a stream that is or isn't a
+    /// terminal.
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Auto => is_terminal,
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+impl std::fmt::Display for ColorMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ColorMode::Auto => "auto",
+            ColorMode::Always => "always",
+            ColorMode::Never => "never",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(format!(
+                "Unknown color mode '{}' (expected 'auto', 'always' or 'never')",
+                s
+            )),
+        }
+    }
+}
+
+/// The direction and RCP line or OSC address of a message logged for a
+/// line or packet passing through the bridge.
+fn traffic(message: &str) -> Option<(Direction, &'static str, &str)> {
+    // OSC messages are logged as `<address>, <args>`
+    fn address(rest: &str) -> &str {
+        rest.split_once(", ").map_or(rest, |(address, _)| address)
+    }
+    if let Some(raw) = message.strip_prefix("Received RCP: ") {
+        Some((Direction::RcpToOsc, "raw", raw))
+    } else if let Some(raw) = message.strip_prefix("Sending RCP: ") {
+        Some((Direction::OscToRcp, "raw", raw))
+    } else if let Some(rest) = message.strip_prefix("Received OSC: ") {
+        Some((Direction::OscToRcp, "osc_address", address(rest)))
+    } else if let Some(rest) = message.strip_prefix("Sending OSC: ") {
+        Some((Direction::RcpToOsc, "osc_address", address(rest)))
+    } else {
+        None
+    }
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "Unknown log format '{}' (expected 'text' or 'json')",
+                s
+            )),
+        }
+    }
+}
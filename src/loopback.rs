@@ -0,0 +1,68 @@
+use crate::transport::{OscSender, Transport, bind_osc};
+use crate::{BridgeConfig, LogFn};
+use rosc::OscPacket;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A [`Transport`] that stands in for the console by answering every
+/// command written to it with `OK <command>`, as a console confirms a
+/// `set`, while OSC uses the configured sockets as usual.
+///
+/// Each OSC message received is so converted to RCP and back, and sent to
+/// the OSC out address, for trying out a controller layout and its mappings
+/// without a console.
+pub struct LoopbackTransport {
+    replies_tx: UnboundedSender<String>,
+    replies: Mutex<UnboundedReceiver<String>>,
+    osc_out: OscSender,
+    osc_in: Mutex<UnboundedReceiver<OscPacket>>,
+}
+
+impl LoopbackTransport {
+    /// Binds the OSC sockets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a socket can't be bound.
+    pub async fn open(config: &BridgeConfig, log: Arc<LogFn>) -> Result<Self, BoxError> {
+        let (osc_out, osc_in) = bind_osc(config, &log).await?;
+        let (replies_tx, replies) = unbounded_channel();
+        Ok(LoopbackTransport {
+            replies_tx,
+            replies: Mutex::new(replies),
+            osc_out,
+            osc_in: Mutex::new(osc_in),
+        })
+    }
+}
+
+impl Transport for LoopbackTransport {
+    async fn read_line(&self) -> std::io::Result<Option<String>> {
+        Ok(self.replies.lock().await.recv().await)
+    }
+
+    async fn write_line(&self, line: &str) -> std::io::Result<()> {
+        self.replies_tx
+            .send(format!("OK {}", line))
+            .map_err(|_| std::io::ErrorKind::BrokenPipe.into())
+    }
+
+    async fn close(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    async fn reconnect(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    async fn recv(&self) -> std::io::Result<Option<OscPacket>> {
+        Ok(self.osc_in.lock().await.recv().await)
+    }
+
+    async fn send(&self, packet: &OscPacket) -> Result<(), BoxError> {
+        self.osc_out.send(packet).await
+    }
+}
@@ -1,22 +1,69 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use yamaha_rcp_to_osc as lib;
 
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 /// Converts Yamaha RCP commands to OSC messages
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// The remote console IP
+    /// Log more detail: -v for every message bridged, -vv also for a hexdump
+    /// of the raw bytes received from the console
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Only log warnings and errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// How log messages are written: "text", or "json" for one object per
+    /// message with timestamp, level, direction, raw and osc_address fields
+    #[arg(long, default_value_t = lib::LogFormat::Text)]
+    log_format: lib::LogFormat,
+
+    /// When to color text log messages: "auto" when writing to a terminal,
+    /// "always" or "never"
+    #[arg(long, default_value_t = lib::ColorMode::Auto)]
+    color: lib::ColorMode,
+
+    /// Never color log messages, the same as --color=never
+    #[arg(long, conflicts_with = "color")]
+    no_color: bool,
+
+    /// JSON config file with the same fields as the GUI's config. Options
+    /// given on the command line take precedence over it.
     #[arg(long)]
-    console_ip: String,
+    config: Option<PathBuf>,
+
+    /// Reload the --config file on SIGHUP, applying changed mappings,
+    /// filters and other per-message settings without reconnecting (Unix
+    /// only)
+    #[arg(long, requires = "config")]
+    config_reload_on_sighup: bool,
+
+    /// The remote console IP. Repeat it to bridge several consoles at once,
+    /// each with its own --rcp-port (unless they share one) and
+    /// --osc-address-prefix.
+    #[arg(long, required_unless_present_any = ["config", "replay", "loopback"])]
+    console_ip: Vec<String>,
 
-    /// The remote RCP port
-    #[arg(long, default_value_t = 49280)]
-    rcp_port: u16,
+    /// The remote RCP port (repeatable, one per --console-ip)
+    #[arg(long, default_value = "49280")]
+    rcp_port: Vec<u16>,
 
     /// The remote OSC port
     #[arg(long, default_value_t = 3999)]
     udp_osc_out_port: u16,
 
+    /// Local port to send OSC from over UDP, for a stable source port (0
+    /// for any free one)
+    #[arg(long, default_value_t = 0)]
+    udp_osc_out_local_port: u16,
+
     /// The remote OSC address
     #[arg(long, default_value = "127.0.0.1")]
     udp_osc_out_addr: String,
@@ -25,28 +72,928 @@ struct Args {
     #[arg(long, default_value_t = 4000)]
     udp_osc_in_port: u16,
 
-    /// The local OSC address
+    /// The local OSC address. Repeat it to receive OSC on several addresses,
+    /// e.g. one per subnet.
     #[arg(long, default_value = "0.0.0.0")]
-    udp_osc_in_addr: String,
+    udp_osc_in_addr: Vec<String>,
+
+    /// The OSC transport: "udp" or "tcp" (length-prefixed packets)
+    #[arg(long, default_value_t = lib::OscTransport::Udp)]
+    osc_transport: lib::OscTransport,
+
+    /// Receive OSC on this Unix datagram socket instead of the network
+    /// (Unix only; needs --osc-uds-out-path)
+    #[arg(long = "osc-uds-path", default_value = "")]
+    osc_uds_path: String,
+
+    /// Send OSC to this Unix datagram socket, with --osc-uds-path
+    #[arg(long = "osc-uds-out-path", default_value = "")]
+    osc_uds_out_path: String,
+
+    /// Refuse to start, rather than warn, if OSC would be sent back to the
+    /// bridge's own OSC in address
+    #[arg(long)]
+    strict: bool,
+
+    /// Split OSC bundles larger than this many bytes before sending them
+    /// over UDP, and drop larger messages with a warning (0 to disable)
+    #[arg(long, default_value_t = 0)]
+    max_udp_size: usize,
+
+    /// Queue up to this many OSC packets for a separate task to send over
+    /// UDP, dropping the oldest when full (0 to send each one directly)
+    #[arg(long, default_value_t = 0)]
+    osc_send_queue_size: usize,
+
+    /// Send OSC to a multicast group. Implied when --udp-osc-out-addr is an
+    /// IPv4 multicast address (UDP only)
+    #[arg(long)]
+    osc_multicast: bool,
+
+    /// Time-to-live of multicast OSC packets (1 stays on the local network)
+    #[arg(long, default_value_t = 1)]
+    osc_multicast_ttl: u32,
+
+    /// Local IPv4 address of the interface to send multicast OSC on
+    #[arg(long, default_value = "")]
+    osc_multicast_interface: String,
+
+    /// Network interface, e.g. "eth1", to pin the OSC sockets and console
+    /// connection to (Linux only; ignored with a warning elsewhere)
+    #[arg(long, default_value = "")]
+    bind_device: String,
+
+    /// How OSC floats are written to RCP: "decimal" (always with a decimal
+    /// point) or "integer-when-whole" (drop it for integer values)
+    #[arg(long, default_value_t = lib::FloatFormat::Decimal)]
+    float_format: lib::FloatFormat,
+
+    /// Round OSC floats to this many decimal places when writing RCP,
+    /// instead of the shortest form that reads back as the same value
+    #[arg(long)]
+    float_decimals: Option<usize>,
+
+    /// Prefix prepended to outgoing OSC addresses and required on incoming
+    /// ones (e.g. "/console1"). Repeatable, one per --console-ip.
+    #[arg(long, default_value = "")]
+    osc_address_prefix: Vec<String>,
+
+    /// String argument appended to every OSC message from the console so
+    /// controllers can tell consoles apart, and dropped from incoming OSC.
+    /// Repeatable, one per --console-ip.
+    #[arg(long)]
+    source_tag: Vec<String>,
+
+    /// Append a sequence number, as a 64-bit integer, to every OSC message
+    /// from the console so controllers can detect lost packets. A trailing
+    /// 64-bit integer is dropped from incoming OSC.
+    #[arg(long)]
+    sequence_numbers: bool,
+
+    /// Read a trailing /i, /f or /s address segment as the type to write the
+    /// arguments as, e.g. "/scene/current/i", and drop it from the command
+    #[arg(long)]
+    type_suffixes: bool,
+
+    /// How floats are rounded when a type coercion or suffix makes them
+    /// integers: "half-away-from-zero", "half-even", "half-up" or
+    /// "truncate". Floats out of the 32-bit integer range are rejected.
+    #[arg(long, default_value_t = lib::RoundMode::HalfAwayFromZero)]
+    round_mode: lib::RoundMode,
+
+    /// Send console numbers written with a leading zero, e.g. "01", as OSC
+    /// strings so the zero isn't lost
+    #[arg(long)]
+    keep_leading_zeros: bool,
+
+    /// Send quoted console strings over OSC without their quotes, e.g.
+    /// "Vocal 1" as Vocal 1
+    #[arg(long)]
+    unquote_strings: bool,
+
+    /// Segment put before the address of messages converted from an OK
+    /// reply, e.g. "/ok", and stripped from incoming OSC (empty to send them
+    /// on the same addresses as NOTIFYs)
+    #[arg(long, default_value = "")]
+    ok_prefix: String,
+
+    /// Segment put before the address of messages converted from a NOTIFY,
+    /// e.g. "/notify", and stripped from incoming OSC
+    #[arg(long, default_value = "")]
+    notify_prefix: String,
+
+    /// RCP command written for incoming OSC addresses that don't start with
+    /// a recognized command, e.g. "set" so "/MIXER/Current/InCh/Fader/Level"
+    /// sets the fader (empty to disable)
+    #[arg(long, default_value = "")]
+    default_verb: String,
+
+    /// RCP command recognized as the start of an address with
+    /// --default-verb, in place of the built-in list (repeatable)
+    #[arg(long = "verb")]
+    verbs: Vec<String>,
+
+    /// How OSC blobs are encoded as RCP strings: "base64" or "hex"
+    #[arg(long, default_value_t = lib::BlobEncoding::Base64)]
+    blob_encoding: lib::BlobEncoding,
+
+    /// What OSC Nil arguments are written to RCP as: "omit" (left out, so a
+    /// Nil-only trigger becomes a bare command) or "reject"
+    #[arg(long, default_value_t = lib::NilArg::Omit)]
+    nil_arg: lib::NilArg,
+
+    /// Outgoing OSC address whose quoted string arguments are decoded back
+    /// into blobs (repeatable)
+    #[arg(long = "blob-address")]
+    blob_addresses: Vec<String>,
+
+    /// Outgoing OSC address whose last argument, in seconds since the Unix
+    /// epoch, is sent as an OSC timetag (repeatable)
+    #[arg(long = "time-address")]
+    time_addresses: Vec<String>,
+
+    /// Inbound OSC address that triggers sending the dump commands to the
+    /// console (empty to disable)
+    #[arg(long, default_value = "/bridge/dump")]
+    dump_address: String,
+
+    /// RCP command to send when a dump is triggered, e.g.
+    /// "get MIXER:Current/Channel/Fader/Level 0 0" (repeatable)
+    #[arg(long = "dump-command")]
+    dump_commands: Vec<String>,
+
+    /// Also send the dump commands whenever the console connects
+    #[arg(long)]
+    dump_on_connect: bool,
+
+    /// Inbound OSC address that triggers sending the panic commands to the
+    /// console (empty to disable)
+    #[arg(long, default_value = "/bridge/panic")]
+    panic_address: String,
+
+    /// RCP command to send, in order, when a panic is triggered, e.g.
+    /// "ssrecall_ex scene_a 1" (repeatable)
+    #[arg(long = "panic-command")]
+    panic_commands: Vec<String>,
+
+    /// File of RCP commands (one per line, "#" for comments) sent every time
+    /// the console connects, e.g. subscriptions
+    #[arg(long)]
+    init_commands: Option<PathBuf>,
+
+    /// Inbound OSC address the bridge answers itself on the --pong-address,
+    /// echoing any arguments, as a liveness check (empty to disable)
+    #[arg(long, default_value = "/bridge/ping")]
+    ping_address: String,
+
+    /// OSC address pings are answered on
+    #[arg(long, default_value = "/bridge/pong")]
+    pong_address: String,
+
+    /// OSC address the connection state is sent on: 1 when the console
+    /// connects, 0 when it disconnects (empty to disable)
+    #[arg(long, default_value = "")]
+    status_address: String,
+
+    /// Inbound OSC address the bridge answers on the --status-reply-address
+    /// with the connection state, uptime in seconds and reconnect count
+    /// (empty to disable)
+    #[arg(long, default_value = "/bridge/status/get")]
+    status_query_address: String,
+
+    /// OSC address status queries are answered on
+    #[arg(long, default_value = "/bridge/status/reply")]
+    status_reply_address: String,
+
+    /// Reconnect to the console when the connection drops instead of exiting
+    #[arg(long)]
+    reconnect: bool,
+
+    /// Delay in milliseconds before the first reconnect attempt, doubled
+    /// after each failure
+    #[arg(long, default_value_t = 1000)]
+    reconnect_delay_ms: u64,
+
+    /// Maximum delay in milliseconds between reconnect attempts
+    #[arg(long, default_value_t = 30_000)]
+    reconnect_max_delay_ms: u64,
+
+    /// Fraction (0 to 1) to randomly vary each reconnect delay by either way
+    #[arg(long, default_value_t = 0.0)]
+    reconnect_jitter: f64,
+
+    /// Exit with an error after this many failed reconnect attempts in a row
+    /// (0 to retry forever)
+    #[arg(long, default_value_t = 0)]
+    reconnect_max_attempts: u32,
+
+    /// Keep up to this many OSC commands received while the console is
+    /// disconnected and send them once it reconnects, dropping the oldest
+    /// when full (0 to drop them)
+    #[arg(long, default_value_t = 0)]
+    reconnect_queue_size: usize,
+
+    /// How long in milliseconds to wait for the console to accept a
+    /// connection
+    #[arg(long, default_value_t = 5000)]
+    connect_timeout_ms: u64,
+
+    /// How long in milliseconds to wait for the console's OK or ERROR
+    /// reply to a command that waits for one (queries, the login and
+    /// --one-shot)
+    #[arg(long, default_value_t = 5000)]
+    response_timeout_ms: u64,
+
+    /// User to log in to the console as, with --rcp-password
+    #[arg(long, default_value = "")]
+    rcp_user: String,
+
+    /// Password to log in to the console with right after connecting, for
+    /// consoles that require a login (empty to skip the login)
+    #[arg(long, default_value = "")]
+    rcp_password: String,
+
+    /// Kinds of console message sent over OSC, e.g. "notify" to drop the
+    /// OK confirmations of commands (comma-separated: notify, ok, error)
+    #[arg(long, value_delimiter = ',', default_value = "notify,ok,error")]
+    forward_types: Vec<lib::RcpMessageKind>,
+
+    /// Only send OSC messages whose address matches this pattern, e.g.
+    /// "/set/MIXER:Current/*/Fader/*" (repeatable)
+    #[arg(long = "osc-out-allow")]
+    osc_out_allow: Vec<String>,
+
+    /// Don't send OSC messages whose address matches this pattern
+    /// (repeatable)
+    #[arg(long = "osc-out-deny")]
+    osc_out_deny: Vec<String>,
+
+    /// Only pass incoming OSC to the console if its address matches this
+    /// pattern (repeatable)
+    #[arg(long = "osc-in-allow")]
+    osc_in_allow: Vec<String>,
+
+    /// Drop incoming OSC whose address matches this pattern instead of
+    /// passing it to the console, e.g. "/ssstore_ex/*/*" (repeatable)
+    #[arg(long = "osc-in-deny")]
+    osc_in_deny: Vec<String>,
+
+    /// Don't send an OSC message identical to the previous one on the same
+    /// address
+    #[arg(long)]
+    dedup: bool,
+
+    /// Write the RCP commands from an OSC bundle with a future timetag at
+    /// the scheduled time instead of immediately
+    #[arg(long)]
+    honor_timetags: bool,
+
+    /// Skip lines from the console that aren't valid UTF-8, logging their
+    /// bytes, instead of converting them with the invalid bytes replaced
+    #[arg(long)]
+    strict_utf8: bool,
+
+    /// What every command written to the console ends with: "lf" or "crlf"
+    /// for firmware that expects a carriage return too
+    #[arg(long, default_value_t = lib::RcpLineEnding::Lf)]
+    rcp_line_ending: lib::RcpLineEnding,
+
+    /// Reconnect if nothing is received from the console for this many
+    /// seconds, even without --reconnect (0 to disable)
+    #[arg(long = "idle-reconnect-secs", default_value_t = 0)]
+    idle_reconnect_secs: u64,
+
+    /// Ramp values sent to the --ramp-address patterns over this many
+    /// milliseconds instead of jumping (0 to disable)
+    #[arg(long, default_value_t = 0)]
+    ramp_ms: u64,
+
+    /// OSC address pattern whose values are ramped over --ramp-ms, e.g.
+    /// "/set/MIXER:Current/*/Fader/Level" (repeatable)
+    #[arg(long = "ramp-address")]
+    ramp_addresses: Vec<String>,
+
+    /// Address to serve Prometheus metrics on at /metrics, e.g.
+    /// "0.0.0.0:9100" (requires the "metrics" feature)
+    #[arg(long, default_value = "")]
+    metrics_addr: String,
+
+    /// Time how long each message takes to pass through the bridge, logged
+    /// with -v and served as histograms on --metrics-addr
+    #[arg(long)]
+    measure_latency: bool,
+
+    /// Also send every line received from the console, unconverted, as a
+    /// string on --raw-address
+    #[arg(long)]
+    echo_raw: bool,
+
+    /// OSC address raw console lines are sent on with --echo-raw, and raw
+    /// commands are received on with --allow-raw
+    #[arg(long, default_value = "/bridge/raw")]
+    raw_address: String,
+
+    /// Write the string sent to --raw-address to the console verbatim, as a
+    /// raw RCP command
+    #[arg(long)]
+    allow_raw: bool,
+
+    /// Don't ask the console for its model with "devinfo" on connect
+    #[arg(long)]
+    no_detect_model: bool,
+
+    /// OSC address the detected console model is sent on (empty to disable)
+    #[arg(long, default_value = "/bridge/model")]
+    model_address: String,
+
+    /// Console model used to pick a mapping table from the config file
+    /// instead of the detected one, e.g. "QL5"
+    #[arg(long, default_value = "")]
+    model: String,
+
+    /// Append every RCP line received from the console to this file, with a
+    /// timestamp, for use with --replay
+    #[arg(long = "record", default_value = "")]
+    record_path: String,
+
+    /// Append a CSV row (timestamp, direction, RCP line, OSC address and
+    /// arguments) to this file for every converted message
+    #[arg(long, default_value = "")]
+    log_csv: String,
+
+    /// Play back a file written with --record in place of the console,
+    /// with the recorded timing, instead of connecting
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Multiplier for the recorded timing of --replay, e.g. 2.0 for twice
+    /// as fast or 0.5 for half speed
+    #[arg(long, default_value_t = 1.0)]
+    replay_speed: f64,
+
+    /// Play back --replay as fast as possible, ignoring the recorded timing
+    #[arg(long)]
+    replay_no_timing: bool,
+
+    /// Answer every command in place of the console, so OSC received is
+    /// converted to RCP and straight back to OSC, for trying out a
+    /// controller or mappings without a console
+    #[arg(long, conflicts_with = "replay")]
+    loopback: bool,
+
+    /// Connect, send the dump commands, and print the distinct OSC
+    /// addresses converted from the console's replies in --list-secs, with
+    /// example arguments, instead of running the bridge
+    #[arg(long)]
+    list_osc_namespace: bool,
+
+    /// How long --list-osc-namespace listens for, in seconds
+    #[arg(long, default_value_t = 5)]
+    list_secs: u64,
+
+    /// Print --list-osc-namespace as JSON
+    #[arg(long)]
+    list_json: bool,
+
+    /// Connect, send the --command, print the console's reply and exit
+    /// with status 0 if it's an OK or 1 if it's an ERROR, instead of
+    /// running the bridge
+    #[arg(long, requires = "rcp_command")]
+    one_shot: bool,
+
+    /// RCP command sent by --one-shot, e.g. 'ssrecall_ex scene_a 5'
+    #[arg(long = "command", requires = "one_shot")]
+    rcp_command: Option<String>,
+
+    /// Exit as soon as --one-shot's command is written, without waiting
+    /// for the reply
+    #[arg(long, requires = "one_shot")]
+    no_wait: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check that the console accepts a connection, print the result and
+    /// exit with status 0 if it does or 1 if it doesn't
+    Healthcheck,
+    /// Print the configuration the bridge would run with, from the config
+    /// file and command-line options, as JSON and exit. The RCP password is
+    /// redacted.
+    PrintConfig,
+}
+
+/// Builds the bridge configuration from the `--config` file, or the
+/// defaults if there is none, overridden by any options given on the
+/// command line.
+fn build_config(args: Args, matches: &ArgMatches) -> Result<lib::BridgeConfig, BoxError> {
+    let mut config = match &args.config {
+        Some(path) => read_config(path)?,
+        None => lib::BridgeConfig::default(),
+    };
+
+    // Without a config file every option applies, defaults included
+    let has_config = args.config.is_some();
+    let from_cli =
+        |id: &str| !has_config || matches.value_source(id) == Some(ValueSource::CommandLine);
+    macro_rules! apply {
+        ($($field:ident).+ = $arg:ident) => {
+            if from_cli(stringify!($arg)) {
+                config.$($field).+ = args.$arg;
+            }
+        };
+    }
+
+    if args.console_ip.len() > 1 {
+        config.consoles = console_configs(
+            args.console_ip,
+            &args.rcp_port,
+            &args.osc_address_prefix,
+            &args.source_tag,
+        )?;
+    } else {
+        if let Some(console_ip) = args.console_ip.into_iter().next() {
+            config.console_ip = console_ip;
+        }
+        if from_cli("rcp_port") {
+            config.rcp_port = single(args.rcp_port, "--rcp-port")?;
+        }
+        if from_cli("osc_address_prefix") {
+            config.conversion.osc_address_prefix =
+                single(args.osc_address_prefix, "--osc-address-prefix")?;
+        }
+        if !args.source_tag.is_empty() {
+            config.conversion.source_tag = single(args.source_tag, "--source-tag")?;
+        }
+    }
+    apply!(udp_osc_out_addr = udp_osc_out_addr);
+    apply!(udp_osc_out_port = udp_osc_out_port);
+    apply!(udp_osc_out_local_port = udp_osc_out_local_port);
+    if from_cli("udp_osc_in_addr") {
+        if args.udp_osc_in_addr.len() > 1 {
+            config.udp_osc_in_addrs = args.udp_osc_in_addr;
+        } else if let Some(addr) = args.udp_osc_in_addr.into_iter().next() {
+            config.udp_osc_in_addr = addr;
+        }
+    }
+    apply!(udp_osc_in_port = udp_osc_in_port);
+    apply!(osc_transport = osc_transport);
+    apply!(osc_uds_path = osc_uds_path);
+    apply!(osc_uds_out_path = osc_uds_out_path);
+    apply!(strict = strict);
+    apply!(max_udp_size = max_udp_size);
+    apply!(osc_send_queue_size = osc_send_queue_size);
+    apply!(osc_multicast = osc_multicast);
+    apply!(osc_multicast_ttl = osc_multicast_ttl);
+    apply!(osc_multicast_interface = osc_multicast_interface);
+    apply!(bind_device = bind_device);
+    apply!(conversion.float_format = float_format);
+    apply!(conversion.float_decimals = float_decimals);
+    apply!(conversion.type_suffixes = type_suffixes);
+    apply!(conversion.sequence_numbers = sequence_numbers);
+    apply!(conversion.round_mode = round_mode);
+    apply!(conversion.keep_leading_zeros = keep_leading_zeros);
+    apply!(conversion.unquote_strings = unquote_strings);
+    apply!(conversion.ok_prefix = ok_prefix);
+    apply!(conversion.notify_prefix = notify_prefix);
+    apply!(conversion.default_verb = default_verb);
+    apply!(conversion.verbs = verbs);
+    apply!(conversion.blob_encoding = blob_encoding);
+    apply!(conversion.nil_arg = nil_arg);
+    apply!(conversion.blob_addresses = blob_addresses);
+    apply!(conversion.time_addresses = time_addresses);
+    apply!(dump_address = dump_address);
+    apply!(dump_commands = dump_commands);
+    apply!(panic_address = panic_address);
+    apply!(panic_commands = panic_commands);
+    apply!(dump_on_connect = dump_on_connect);
+    apply!(ping_address = ping_address);
+    apply!(pong_address = pong_address);
+    if let Some(path) = &args.init_commands {
+        config.init_commands = read_commands(path)?;
+    }
+    apply!(status_address = status_address);
+    apply!(status_query_address = status_query_address);
+    apply!(status_reply_address = status_reply_address);
+    apply!(reconnect = reconnect);
+    apply!(reconnect_delay_ms = reconnect_delay_ms);
+    apply!(reconnect_max_delay_ms = reconnect_max_delay_ms);
+    apply!(reconnect_jitter = reconnect_jitter);
+    apply!(reconnect_max_attempts = reconnect_max_attempts);
+    apply!(reconnect_queue_size = reconnect_queue_size);
+    apply!(connect_timeout_ms = connect_timeout_ms);
+    apply!(response_timeout_ms = response_timeout_ms);
+    apply!(rcp_user = rcp_user);
+    apply!(rcp_password = rcp_password);
+    apply!(forward_types = forward_types);
+    apply!(osc_out_filter.allow = osc_out_allow);
+    apply!(osc_out_filter.deny = osc_out_deny);
+    apply!(osc_in_filter.allow = osc_in_allow);
+    apply!(osc_in_filter.deny = osc_in_deny);
+    apply!(dedup = dedup);
+    apply!(honor_timetags = honor_timetags);
+    apply!(strict_utf8 = strict_utf8);
+    apply!(rcp_line_ending = rcp_line_ending);
+    apply!(idle_reconnect_secs = idle_reconnect_secs);
+    apply!(ramp_ms = ramp_ms);
+    apply!(ramp_addresses = ramp_addresses);
+    apply!(metrics_addr = metrics_addr);
+    apply!(measure_latency = measure_latency);
+    apply!(record_path = record_path);
+    apply!(replay_speed = replay_speed);
+    apply!(replay_no_timing = replay_no_timing);
+    apply!(csv_log_path = log_csv);
+    if args.verbose >= 2 {
+        config.log_raw_rcp = true;
+    }
+    if from_cli("no_detect_model") {
+        config.detect_model = !args.no_detect_model;
+    }
+    apply!(model_address = model_address);
+    apply!(echo_raw = echo_raw);
+    apply!(raw_address = raw_address);
+    apply!(allow_raw = allow_raw);
+    apply!(model = model);
+
+    // A replay discards what is written to the console, and a loopback
+    // only echoes it, so nothing would answer a devinfo
+    let no_console = args.replay.is_some() || args.loopback;
+    if no_console {
+        config.detect_model = false;
+    }
+
+    if args.replay.is_some() && !config.consoles.is_empty() {
+        return Err("--replay can't be used with multiple consoles".into());
+    }
+    if args.loopback && !config.consoles.is_empty() {
+        return Err("--loopback can't be used with multiple consoles".into());
+    }
+    if config.console_ip.is_empty() && config.consoles.is_empty() && !no_console {
+        return Err("--console-ip is required (or console_ip in the --config file)".into());
+    }
+    Ok(config)
+}
+
+/// Pairs each console IP with its RCP port, OSC address prefix and source
+/// tag, if any. A single port is shared by every console.
+fn console_configs(
+    console_ips: Vec<String>,
+    rcp_ports: &[u16],
+    prefixes: &[String],
+    source_tags: &[String],
+) -> Result<Vec<lib::ConsoleConfig>, BoxError> {
+    if rcp_ports.len() != 1 && rcp_ports.len() != console_ips.len() {
+        return Err("Give one --rcp-port, or one per --console-ip".into());
+    }
+    if prefixes.len() != console_ips.len() {
+        return Err("Give one --osc-address-prefix per --console-ip".into());
+    }
+    if !source_tags.is_empty() && source_tags.len() != console_ips.len() {
+        return Err("Give one --source-tag per --console-ip, or none".into());
+    }
+    Ok(console_ips
+        .into_iter()
+        .enumerate()
+        .map(|(i, console_ip)| lib::ConsoleConfig {
+            console_ip,
+            rcp_port: rcp_ports[i.min(rcp_ports.len() - 1)],
+            osc_address_prefix: prefixes[i].clone(),
+            source_tag: source_tags.get(i).cloned().unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// The one value of an option that can only be repeated for multiple
+/// consoles.
+fn single<T>(mut values: Vec<T>, flag: &str) -> Result<T, BoxError> {
+    if values.len() != 1 {
+        return Err(format!("{} can only be repeated along with --console-ip", flag).into());
+    }
+    Ok(values.remove(0))
+}
+
+/// Reads a JSON [`lib::BridgeConfig`] from `path`.
+fn read_config(path: &Path) -> Result<lib::BridgeConfig, BoxError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&contents)
+        .map_err(|e| format!("Invalid config file {}: {}", path.display(), e).into())
+}
+
+/// Reads RCP commands from `path`, one per line, skipping blank lines and
+/// `#` comments.
+fn read_commands(path: &Path) -> Result<Vec<String>, BoxError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+async fn main() -> Result<(), BoxError> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let replay = args.replay.clone();
+    let loopback = args.loopback;
+    let command = args.command.take();
+    let one_shot = args
+        .rcp_command
+        .take()
+        .map(|command| (command, !args.no_wait));
+    let list = args
+        .list_osc_namespace
+        .then(|| (Duration::from_secs(args.list_secs), args.list_json));
+    let min_level = if args.quiet {
+        lib::LogLevel::Warn
+    } else if args.verbose > 0 {
+        lib::LogLevel::Debug
+    } else {
+        lib::LogLevel::Info
+    };
+    let log_format = args.log_format;
+    let color = if args.no_color {
+        lib::ColorMode::Never
+    } else {
+        args.color
+    };
+    let format = move |colored: bool, level: lib::LogLevel, msg: &str| {
+        if colored {
+            log_format.format_colored(level, msg)
+        } else {
+            log_format.format(level, msg)
+        }
+    };
+    let reload = args.config_reload_on_sighup.then(|| matches.clone());
+    let config = build_config(args, &matches)?;
+    match command {
+        Some(Command::Healthcheck) => healthcheck(&config).await,
+        Some(Command::PrintConfig) => return print_config(config, log_format),
+        None => {}
+    }
+    if let Some((command, wait)) = one_shot {
+        let succeeded = send_one_shot(&config, &command, wait).await?;
+        std::process::exit(if succeeded { 0 } else { 1 });
+    }
+    if let Some((window, json)) = list {
+        // Logs go to stderr, leaving stdout for the listing
+        let colored = color.enabled(std::io::stderr().is_terminal());
+        let log: lib::LogFn = Box::new(move |level, msg| {
+            if level >= min_level {
+                eprintln!("{}", format(colored, level, &msg));
+            }
+        });
+        return list_osc_namespace(config, window, json, log).await;
+    }
+    let colored = color.enabled(std::io::stdout().is_terminal());
+    let print_log = move |level: lib::LogLevel, msg: String| {
+        if level >= min_level {
+            println!("{}", format(colored, level, &msg));
+        }
+    };
+    let log: lib::LogFn = Box::new(print_log);
+
+    match replay {
+        Some(path) => {
+            let bridge = lib::Bridge::replay(config, &path, log).await?;
+            run(vec![bridge], reload, print_log).await
+        }
+        None if loopback => {
+            let bridge = lib::Bridge::loopback(config, log).await?;
+            run(vec![bridge], reload, print_log).await
+        }
+        None if !config.consoles.is_empty() => {
+            let bridges = lib::Bridge::connect_all(config, log).await?;
+            run(bridges, reload, print_log).await
+        }
+        None => {
+            let bridge = lib::Bridge::connect(config, log).await?;
+            run(vec![bridge], reload, print_log).await
+        }
+    }
+}
 
-    let config = lib::BridgeConfig {
-        console_ip: args.console_ip,
-        rcp_port: args.rcp_port,
-        udp_osc_out_addr: args.udp_osc_out_addr,
-        udp_osc_out_port: args.udp_osc_out_port,
-        udp_osc_in_addr: args.udp_osc_in_addr,
-        udp_osc_in_port: args.udp_osc_in_port,
+/// Tries to connect to every configured console, prints a one-line status
+/// and exits: 0 if they all accepted the connection, 1 otherwise.
+async fn healthcheck(config: &lib::BridgeConfig) -> ! {
+    let consoles: Vec<(&str, u16)> = if config.consoles.is_empty() {
+        vec![(config.console_ip.as_str(), config.rcp_port)]
+    } else {
+        config
+            .consoles
+            .iter()
+            .map(|console| (console.console_ip.as_str(), console.rcp_port))
+            .collect()
     };
+    let timeout = Duration::from_millis(config.connect_timeout_ms);
+
+    for &(host, port) in &consoles {
+        if let Err(e) = lib::probe_console(host, port, timeout).await {
+            println!("UNHEALTHY: {}:{}: {}", host, port, e);
+            std::process::exit(1);
+        }
+    }
+    let reachable: Vec<String> = consoles
+        .iter()
+        .map(|(host, port)| format!("{}:{}", host, port))
+        .collect();
+    println!("OK: {}", reachable.join(", "));
+    std::process::exit(0);
+}
+
+/// Prints `config` as pretty-printed JSON, in the format `--config` reads,
+/// with the RCP password redacted.
+fn print_config(mut config: lib::BridgeConfig, log_format: lib::LogFormat) -> Result<(), BoxError> {
+    // Checked as the bridge would at startup, so mistakes show up here too
+    if let Some(problem) = lib::osc_feedback_loop(&config) {
+        if config.strict {
+            return Err(problem.into());
+        }
+        eprintln!("{}", log_format.format(lib::LogLevel::Warn, &problem));
+    }
+    if !config.rcp_password.is_empty() {
+        config.rcp_password = "<redacted>".to_string();
+    }
+    println!("{}", serde_json::to_string_pretty(&config)?);
+    Ok(())
+}
+
+/// Sends `command` to the console and prints its reply if `wait` is set.
+/// Returns whether it succeeded, i.e. wasn't answered with an ERROR.
+async fn send_one_shot(
+    config: &lib::BridgeConfig,
+    command: &str,
+    wait: bool,
+) -> Result<bool, BoxError> {
+    if !config.consoles.is_empty() {
+        return Err("--one-shot can't be used with multiple consoles".into());
+    }
+    let reply = lib::send_console_command(config, command, wait)
+        .await
+        .map_err(|e| format!("Failed to send {:?}: {}", command, e))?;
+    if let Some(reply) = &reply {
+        println!("{}", reply);
+    }
+    Ok(!reply.is_some_and(|reply| reply.starts_with("ERROR")))
+}
+
+/// Connects to the console, sends the dump commands, and prints the
+/// distinct OSC addresses converted from what the console sends back within
+/// `window`.
+async fn list_osc_namespace(
+    mut config: lib::BridgeConfig,
+    window: Duration,
+    json: bool,
+    log: lib::LogFn,
+) -> Result<(), BoxError> {
+    if !config.consoles.is_empty() {
+        return Err("--list-osc-namespace can't be used with multiple consoles".into());
+    }
+    if config.dump_commands.is_empty() {
+        return Err(
+            "--list-osc-namespace needs a --dump-command (or dump_commands in the --config \
+             file) to ask the console for its values"
+                .into(),
+        );
+    }
+    config.dump_on_connect = true;
+    config.reconnect = false;
+
+    let bridge = lib::Bridge::connect(config, log).await?;
+    let mut converted = bridge.subscribe();
+    let task = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
 
-    lib::run_bridge(config).await.map_err(|e| {
-        let boxed: Box<dyn std::error::Error> = e;
-        boxed
-    })?;
+    let mut namespace = lib::OscNamespace::new();
+    let deadline = tokio::time::sleep(window);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            packet = converted.recv() => match packet {
+                Ok(packet) => namespace.observe(&packet),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+    bridge.shutdown();
+    task.await??;
 
+    if json {
+        println!("{}", namespace.to_json());
+    } else {
+        print!("{}", namespace.to_text());
+    }
     Ok(())
 }
+
+/// Reloads the configuration of `bridges` each time the process gets
+/// SIGHUP, building it from the config file and command line in `matches`
+/// as at startup. A config that fails to load is logged and ignored.
+async fn reload_on_sighup<T: lib::Transport>(
+    bridges: Vec<lib::Bridge<T>>,
+    matches: ArgMatches,
+    log: impl Fn(lib::LogLevel, String),
+) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut sighup = match signal(SignalKind::hangup()) {
+            Ok(sighup) => sighup,
+            Err(e) => {
+                log(
+                    lib::LogLevel::Error,
+                    format!("Failed to listen for SIGHUP: {}", e),
+                );
+                return;
+            }
+        };
+        while sighup.recv().await.is_some() {
+            let config = Args::from_arg_matches(&matches)
+                .map_err(BoxError::from)
+                .and_then(|args| build_config(args, &matches));
+            match config {
+                Ok(config) => {
+                    for bridge in &bridges {
+                        bridge.reload(config.clone());
+                    }
+                }
+                Err(e) => log(
+                    lib::LogLevel::Error,
+                    format!(
+                        "Failed to reload the config, keeping the current one: {}",
+                        e
+                    ),
+                ),
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (bridges, matches);
+        log(
+            lib::LogLevel::Warn,
+            "--config-reload-on-sighup is only supported on Unix".to_string(),
+        );
+    }
+}
+
+/// Runs `bridges` until they all stop or a shutdown signal arrives,
+/// reloading their configuration on SIGHUP if `reload` holds the command
+/// line to rebuild it from.
+async fn run<T: lib::Transport>(
+    bridges: Vec<lib::Bridge<T>>,
+    reload: Option<ArgMatches>,
+    log: impl Fn(lib::LogLevel, String) + Send + 'static,
+) -> Result<(), BoxError> {
+    if let Some(matches) = reload {
+        tokio::spawn(reload_on_sighup(bridges.clone(), matches, log));
+    }
+    tokio::spawn({
+        let bridges = bridges.clone();
+        async move {
+            wait_for_signal().await;
+            for bridge in &bridges {
+                bridge.shutdown();
+            }
+        }
+    });
+
+    lib::Bridge::run_all(&bridges).await
+}
+
+/// Waits for Ctrl-C, or SIGTERM on Unix. Never returns if the signal
+/// handlers can't be installed.
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::{fmt::Write, sync::Arc};
+
+/// Counters describing what a bridge has done, updated from its message
+/// loops, read with [`Bridge::stats`](crate::Bridge::stats) and served in
+/// the Prometheus text format on
+/// [`BridgeConfig::metrics_addr`](crate::BridgeConfig::metrics_addr) when
+/// the `metrics` feature is enabled.
+///
+/// The counters are cumulative from the bridge's creation, across
+/// reconnects and config reloads, and can be read from any thread while
+/// the bridge runs. Each is read on its own, so a set of readings taken
+/// while messages are passing through may be slightly out of step.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub(crate) rcp_to_osc: AtomicU64,
+    pub(crate) osc_to_rcp: AtomicU64,
+    pub(crate) conversion_errors: AtomicU64,
+    pub(crate) filtered: AtomicU64,
+    pub(crate) reconnects: AtomicU64,
+    pub(crate) bytes_read: AtomicU64,
+    pub(crate) bytes_written: AtomicU64,
+    pub(crate) connected: AtomicBool,
+    pub(crate) osc_to_rcp_latency: Latency,
+    pub(crate) rcp_to_osc_latency: Latency,
+}
+
+/// Upper bounds of the latency histogram buckets, in microseconds.
+const LATENCY_BUCKETS_US: [u64; 10] = [
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000,
+];
+
+/// A histogram of how long the bridge took to pass messages on, recorded
+/// when [`BridgeConfig::measure_latency`](crate::BridgeConfig::measure_latency)
+/// is set.
+#[derive(Debug, Default)]
+pub(crate) struct Latency {
+    /// Measurements per bucket, not cumulative; those over the last bound
+    /// are only counted in `count`
+    buckets: [AtomicU64; LATENCY_BUCKETS_US.len()],
+    sum_us: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Latency {
+    pub(crate) fn record(&self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        if let Some(bucket) = LATENCY_BUCKETS_US.iter().position(|&bound| us <= bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders the histogram as `name` in the Prometheus text exposition
+    /// format, in seconds.
+    #[cfg(feature = "metrics")]
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (bound, bucket) in LATENCY_BUCKETS_US.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = *bound as f64 / 1e6;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let sum = self.sum_us.load(Ordering::Relaxed) as f64 / 1e6;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+impl Stats {
+    /// RCP messages converted and sent over OSC.
+    pub fn rcp_to_osc(&self) -> u64 {
+        self.rcp_to_osc.load(Ordering::Relaxed)
+    }
+
+    /// RCP commands converted from OSC and written to the console.
+    pub fn osc_to_rcp(&self) -> u64 {
+        self.osc_to_rcp.load(Ordering::Relaxed)
+    }
+
+    /// Messages that couldn't be converted, in either direction.
+    pub fn conversion_errors(&self) -> u64 {
+        self.conversion_errors.load(Ordering::Relaxed)
+    }
+
+    /// Messages dropped by the address filters or
+    /// [`BridgeConfig::forward_types`](crate::BridgeConfig::forward_types).
+    pub fn filtered(&self) -> u64 {
+        self.filtered.load(Ordering::Relaxed)
+    }
+
+    /// Successful reconnects to the console.
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of the RCP lines read from the console, not counting line
+    /// endings.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of the RCP commands written to the console, not counting line
+    /// endings.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Whether the console is connected.
+    pub fn connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        metric(
+            "bridge_rcp_to_osc_total",
+            "counter",
+            "RCP messages converted and sent over OSC",
+            self.rcp_to_osc.load(Ordering::Relaxed),
+        );
+        metric(
+            "bridge_osc_to_rcp_total",
+            "counter",
+            "RCP commands converted from OSC and written to the console",
+            self.osc_to_rcp.load(Ordering::Relaxed),
+        );
+        metric(
+            "bridge_conversion_errors_total",
+            "counter",
+            "Messages that could not be converted in either direction",
+            self.conversion_errors.load(Ordering::Relaxed),
+        );
+        metric(
+            "bridge_filtered_total",
+            "counter",
+            "Messages dropped by the address filters",
+            self.filtered.load(Ordering::Relaxed),
+        );
+        metric(
+            "bridge_reconnects_total",
+            "counter",
+            "Successful reconnects to the console",
+            self.reconnects.load(Ordering::Relaxed),
+        );
+        metric(
+            "bridge_rcp_bytes_read_total",
+            "counter",
+            "Bytes of RCP lines read from the console",
+            self.bytes_read.load(Ordering::Relaxed),
+        );
+        metric(
+            "bridge_rcp_bytes_written_total",
+            "counter",
+            "Bytes of RCP commands written to the console",
+            self.bytes_written.load(Ordering::Relaxed),
+        );
+        metric(
+            "bridge_connected",
+            "gauge",
+            "Whether the console is currently connected (1) or not (0)",
+            self.connected.load(Ordering::Relaxed) as u64,
+        );
+        self.osc_to_rcp_latency.render(
+            &mut out,
+            "bridge_osc_to_rcp_latency_seconds",
+            "Time from receiving an OSC packet to writing its RCP commands",
+        );
+        self.rcp_to_osc_latency.render(
+            &mut out,
+            "bridge_rcp_to_osc_latency_seconds",
+            "Time from receiving an RCP line to sending its OSC message",
+        );
+        out
+    }
+}
+
+/// Serves `GET /metrics` on `listener` until `shutdown` is cancelled.
+#[cfg(feature = "metrics")]
+pub(crate) async fn serve(
+    listener: tokio::net::TcpListener,
+    stats: Arc<Stats>,
+    shutdown: tokio_util::sync::CancellationToken,
+) -> std::io::Result<()> {
+    use http_body_util::Full;
+    use hyper::body::{Bytes, Incoming};
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper::{Request, Response, StatusCode};
+    use hyper_util::rt::TokioIo;
+
+    loop {
+        let (stream, _) = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            accepted = listener.accept() => accepted?,
+        };
+
+        let stats = Arc::clone(&stats);
+        let service = service_fn(move |request: Request<Incoming>| {
+            let response = if request.uri().path() == "/metrics" {
+                Response::builder()
+                    .header("Content-Type", "text/plain; version=0.0.4")
+                    .body(Full::new(Bytes::from(stats.render())))
+            } else {
+                Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Full::new(Bytes::new()))
+            };
+            async move { response }
+        });
+        tokio::spawn(async move {
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
@@ -0,0 +1,124 @@
+use crate::osc_to_rcp_arg;
+use rosc::{OscPacket, OscType};
+use std::collections::BTreeMap;
+
+/// The distinct OSC addresses converted from a console's messages, each
+/// with the arguments it was first seen with, for discovering what a
+/// console sends when building a controller layout.
+///
+/// Addresses are kept sorted, and bundles are flattened into their
+/// messages.
+#[derive(Debug, Clone, Default)]
+pub struct OscNamespace {
+    addresses: BTreeMap<String, Vec<OscType>>,
+}
+
+impl OscNamespace {
+    /// Creates an empty namespace.
+    pub fn new() -> Self {
+        OscNamespace::default()
+    }
+
+    /// Adds the addresses of the messages in `packet` that haven't been
+    /// seen yet.
+    pub fn observe(&mut self, packet: &OscPacket) {
+        match packet {
+            OscPacket::Message(msg) => {
+                self.addresses
+                    .entry(msg.addr.clone())
+                    .or_insert_with(|| msg.args.clone());
+            }
+            OscPacket::Bundle(bundle) => {
+                for packet in &bundle.content {
+                    self.observe(packet);
+                }
+            }
+        }
+    }
+
+    /// The number of distinct addresses seen.
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    /// Whether no addresses have been seen.
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    /// One line per address, in order: the address, its OSC type tags and
+    /// the example arguments as RCP writes them, e.g.
+    /// `/set/MIXER:Current/InCh/Fader/Level ,iii 0 0 -1000`.
+    pub fn to_text(&self) -> String {
+        self.addresses
+            .iter()
+            .map(|(address, args)| {
+                let mut line = format!("{} {}", address, type_tags(args));
+                for arg in args {
+                    line.push(' ');
+                    line.push_str(&example(arg));
+                }
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
+
+    /// The addresses as a JSON array of objects with `address`, `types`
+    /// (the OSC type tags) and `example` (the arguments as RCP writes them).
+    pub fn to_json(&self) -> String {
+        let entries: Vec<serde_json::Value> = self
+            .addresses
+            .iter()
+            .map(|(address, args)| {
+                serde_json::json!({
+                    "address": address,
+                    "types": type_tags(args),
+                    "example": args.iter().map(example).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&entries).unwrap()
+    }
+}
+
+/// The OSC type tag string for `args`, e.g. `,iis`.
+fn type_tags(args: &[OscType]) -> String {
+    let mut tags = String::from(",");
+    for arg in args {
+        push_type_tag(&mut tags, arg);
+    }
+    tags
+}
+
+fn push_type_tag(tags: &mut String, arg: &OscType) {
+    match arg {
+        OscType::Int(_) => tags.push('i'),
+        OscType::Float(_) => tags.push('f'),
+        OscType::String(_) => tags.push('s'),
+        OscType::Blob(_) => tags.push('b'),
+        OscType::Time(_) => tags.push('t'),
+        OscType::Long(_) => tags.push('h'),
+        OscType::Double(_) => tags.push('d'),
+        OscType::Char(_) => tags.push('c'),
+        OscType::Color(_) => tags.push('r'),
+        OscType::Midi(_) => tags.push('m'),
+        OscType::Bool(true) => tags.push('T'),
+        OscType::Bool(false) => tags.push('F'),
+        OscType::Array(array) => {
+            tags.push('[');
+            for arg in &array.content {
+                push_type_tag(tags, arg);
+            }
+            tags.push(']');
+        }
+        OscType::Nil => tags.push('N'),
+        OscType::Inf => tags.push('I'),
+    }
+}
+
+/// An example argument as RCP would write it, or its debug form if RCP
+/// has no way to.
+fn example(arg: &OscType) -> String {
+    osc_to_rcp_arg(arg).unwrap_or_else(|_| format!("{:?}", arg))
+}
@@ -0,0 +1,75 @@
+/// Whether `address` matches the OSC address pattern `pattern`.
+///
+/// Supports the OSC 1.0 pattern syntax, none of which matches across a `/`:
+/// * `?` matches any single character
+/// * `*` matches any run of characters, including none
+/// * `[abc]` and `[a-z]` match one character in the set, and `[!a-z]` one
+///   character not in it
+/// * `{foo,bar}` matches any one of the comma-separated alternatives
+///
+/// Everything else, including an unterminated `[` or `{`, matches literally,
+/// so a pattern without special characters is an exact comparison.
+pub fn osc_pattern_matches(pattern: &str, address: &str) -> bool {
+    matches_from(pattern.as_bytes(), address.as_bytes())
+}
+
+fn matches_from(pattern: &[u8], address: &[u8]) -> bool {
+    match pattern.first() {
+        None => address.is_empty(),
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            for i in 0..=address.len() {
+                if matches_from(rest, &address[i..]) {
+                    return true;
+                }
+                if i == address.len() || address[i] == b'/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => match address.first() {
+            Some(&c) if c != b'/' => matches_from(&pattern[1..], &address[1..]),
+            _ => false,
+        },
+        Some(b'[') if pattern.contains(&b']') => {
+            let end = pattern.iter().position(|&c| c == b']').unwrap();
+            match address.first() {
+                Some(&c) if c != b'/' && class_matches(&pattern[1..end], c) => {
+                    matches_from(&pattern[end + 1..], &address[1..])
+                }
+                _ => false,
+            }
+        }
+        Some(b'{') if pattern.contains(&b'}') => {
+            let end = pattern.iter().position(|&c| c == b'}').unwrap();
+            let rest = &pattern[end + 1..];
+            pattern[1..end].split(|&c| c == b',').any(|alternative| {
+                address.starts_with(alternative)
+                    && matches_from(rest, &address[alternative.len()..])
+            })
+        }
+        Some(&c) => address.first() == Some(&c) && matches_from(&pattern[1..], &address[1..]),
+    }
+}
+
+/// Whether `c` is in a `[...]` character class, given without its brackets.
+fn class_matches(class: &[u8], c: u8) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some((b'!', rest)) => (true, rest),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == b'-' {
+            found |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
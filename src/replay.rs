@@ -0,0 +1,153 @@
+use crate::transport::{OscSender, Transport, bind_osc};
+use crate::{BridgeConfig, LogFn};
+use rosc::OscPacket;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Appends each RCP line received from the console to a file.
+///
+/// Each line is written as the time it was received, in milliseconds since
+/// the Unix epoch, then a space, then the line as received, e.g.
+/// `1718000000123 NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000`.
+/// [`ReplayTransport`] reads the same format back.
+pub(crate) struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    /// Opens `path` for appending, creating it if needed.
+    pub(crate) async fn create(path: &str) -> std::io::Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Recorder { file })
+    }
+
+    pub(crate) async fn record(&mut self, line: &str) -> std::io::Result<()> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        self.file
+            .write_all(format!("{} {}\n", millis, line).as_bytes())
+            .await?;
+        // Flushed per line so a crash doesn't lose what led up to it
+        self.file.flush().await
+    }
+}
+
+/// A [`Transport`] that plays back a file written with
+/// [`BridgeConfig::record_path`] in place of the console, with the recorded
+/// timing between lines (sped up or slowed down by
+/// [`BridgeConfig::replay_speed`]), while OSC uses the configured sockets as usual.
+///
+/// Commands written to the console are discarded, and the replay can't be
+/// reconnected once the file ends.
+pub struct ReplayTransport {
+    lines: Mutex<ReplayReader>,
+    /// The factor the recorded gaps are divided by, or `None` to skip them
+    /// ([`BridgeConfig::replay_speed`], [`BridgeConfig::replay_no_timing`])
+    speed: Option<f64>,
+    osc_out: OscSender,
+    osc_in: Mutex<UnboundedReceiver<OscPacket>>,
+}
+
+struct ReplayReader {
+    lines: Lines<BufReader<File>>,
+    /// Timestamp of the previous line, which the next one is timed from
+    last_millis: Option<u64>,
+}
+
+impl ReplayTransport {
+    /// Opens the recording at `path` and binds the OSC sockets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the replay speed isn't a positive number, the
+    /// file can't be opened or a socket can't be bound.
+    pub async fn open(
+        config: &BridgeConfig,
+        path: &Path,
+        log: Arc<LogFn>,
+    ) -> Result<Self, BoxError> {
+        if !(config.replay_speed.is_finite() && config.replay_speed > 0.0) {
+            return Err(format!(
+                "Replay speed must be a positive number, not {}",
+                config.replay_speed
+            )
+            .into());
+        }
+        let file = File::open(path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let (osc_out, osc_in) = bind_osc(config, &log).await?;
+
+        Ok(ReplayTransport {
+            lines: Mutex::new(ReplayReader {
+                lines: BufReader::new(file).lines(),
+                last_millis: None,
+            }),
+            speed: (!config.replay_no_timing).then_some(config.replay_speed),
+            osc_out,
+            osc_in: Mutex::new(osc_in),
+        })
+    }
+}
+
+impl Transport for ReplayTransport {
+    async fn read_line(&self) -> std::io::Result<Option<String>> {
+        let mut reader = self.lines.lock().await;
+        let Some(recorded) = reader.lines.next_line().await? else {
+            return Ok(None);
+        };
+
+        let (millis, line) = recorded
+            .split_once(' ')
+            .and_then(|(millis, line)| Some((millis.parse::<u64>().ok()?, line)))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Invalid recorded line: {}", recorded),
+                )
+            })?;
+
+        if let (Some(last), Some(speed)) = (reader.last_millis, self.speed) {
+            let gap = Duration::from_millis(millis.saturating_sub(last));
+            tokio::time::sleep(gap.div_f64(speed)).await;
+        }
+        reader.last_millis = Some(millis);
+        Ok(Some(line.to_string()))
+    }
+
+    async fn write_line(&self, _line: &str) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    async fn close(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    async fn reconnect(&self) -> std::io::Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "A replay can't be reconnected",
+        ))
+    }
+
+    async fn recv(&self) -> std::io::Result<Option<OscPacket>> {
+        Ok(self.osc_in.lock().await.recv().await)
+    }
+
+    async fn send(&self, packet: &OscPacket) -> Result<(), BoxError> {
+        self.osc_out.send(packet).await
+    }
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+/// Sends `state` (e.g. `READY=1`) to systemd's notification socket, as
+/// `sd_notify` does. Does nothing unless the bridge was started by systemd
+/// with `NotifyAccess` set, i.e. `NOTIFY_SOCKET` is set, on Unix.
+pub(crate) fn notify(state: &str) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(path) = std::env::var_os("NOTIFY_SOCKET") {
+        use std::os::unix::net::UnixDatagram;
+
+        let socket = UnixDatagram::unbound()?;
+        // A leading "@" names a socket in Linux's abstract namespace
+        #[cfg(target_os = "linux")]
+        if let Some(name) = path.as_encoded_bytes().strip_prefix(b"@") {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+            return Ok(());
+        }
+        socket.send_to(state.as_bytes(), path)?;
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+    Ok(())
+}
+
+/// How often to send `WATCHDOG=1`: half the `WatchdogSec` systemd passes in
+/// `WATCHDOG_USEC`, as recommended, or `None` if the watchdog isn't enabled
+/// for this process.
+pub(crate) fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse() != Ok(std::process::id())
+    {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
@@ -0,0 +1,1512 @@
+use crate::{BridgeConfig, LogFn, LogLevel, OscTransport, RcpLineFramer, quote};
+use rosc::{OscBundle, OscMessage, OscPacket};
+use socket2::{Domain, Socket, Type};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpSocket, TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The connections a [`Bridge`](crate::Bridge) reads from and writes to: RCP
+/// lines to and from the console, and OSC packets to and from the OSC
+/// application.
+///
+/// Methods take `&self` because the bridge reads and writes concurrently
+/// from separate tasks; implementations synchronize internally.
+pub trait Transport: Send + Sync + 'static {
+    /// Reads the next line from the console, without its line terminator.
+    /// Returns `Ok(None)` once the console closes the connection.
+    fn read_line(&self) -> impl Future<Output = std::io::Result<Option<String>>> + Send;
+
+    /// Writes a single command to the console, adding the line terminator.
+    fn write_line(&self, line: &str) -> impl Future<Output = std::io::Result<()>> + Send;
+
+    /// Writes several commands to the console in order, each with its own
+    /// line terminator. Implementations may send them together, e.g. in a
+    /// single TCP write; by default each is written with [`write_line`].
+    ///
+    /// [`write_line`]: Transport::write_line
+    fn write_lines(&self, lines: &[String]) -> impl Future<Output = std::io::Result<()>> + Send {
+        async move {
+            for line in lines {
+                self.write_line(line).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Flushes any pending writes and closes the console connection. Called
+    /// once when the bridge stops.
+    fn close(&self) -> impl Future<Output = std::io::Result<()>> + Send;
+
+    /// Re-establishes the console connection after [`read_line`] reported it
+    /// closed or failed, or makes the first connection for a transport that
+    /// was set up without one. Any partial line from the old connection is
+    /// discarded.
+    ///
+    /// [`read_line`]: Transport::read_line
+    fn reconnect(&self) -> impl Future<Output = std::io::Result<()>> + Send;
+
+    /// Receives the next OSC packet. Returns `Ok(None)` once no more packets
+    /// can arrive.
+    fn recv(&self) -> impl Future<Output = std::io::Result<Option<OscPacket>>> + Send;
+
+    /// Sends an OSC packet to the OSC application.
+    fn send(&self, packet: &OscPacket) -> impl Future<Output = Result<(), BoxError>> + Send;
+}
+
+/// The real [`Transport`]: a TCP connection to the console, and UDP or TCP
+/// sockets for OSC as configured.
+pub struct SocketTransport {
+    rcp_host: String,
+    rcp_port: u16,
+    connect_timeout: Duration,
+    /// How long the login waits for its reply
+    /// ([`BridgeConfig::response_timeout_ms`])
+    response_timeout: Duration,
+    /// Network interface the console connection is pinned to
+    /// ([`BridgeConfig::bind_device`])
+    bind_device: String,
+    /// User and password to log in with after connecting
+    /// ([`BridgeConfig::rcp_user`], [`BridgeConfig::rcp_password`])
+    rcp_user: String,
+    rcp_password: String,
+    rcp_read: Mutex<RcpReader>,
+    /// The write half of the console connection, `None` until the first
+    /// connection for a transport created by [`SocketTransport::unconnected`]
+    rcp_write: Mutex<Option<OwnedWriteHalf>>,
+    osc_out: Arc<OscSender>,
+    osc_in: Mutex<UnboundedReceiver<OscPacket>>,
+    log: Arc<LogFn>,
+    /// Hexdump the received bytes ([`BridgeConfig::log_raw_rcp`])
+    log_raw_rcp: bool,
+    /// Skip lines that aren't valid UTF-8 ([`BridgeConfig::strict_utf8`])
+    strict_utf8: bool,
+    /// Ends every command written ([`BridgeConfig::rcp_line_ending`])
+    line_ending: &'static str,
+}
+
+/// The read half of the console connection, plus the lines received but
+/// not yet read and any partial line.
+struct RcpReader {
+    stream: Option<OwnedReadHalf>,
+    framer: RcpLineFramer,
+    lines: VecDeque<String>,
+}
+
+impl SocketTransport {
+    /// Binds the OSC sockets and connects to the console.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a socket can't be bound or the console can't be
+    /// reached.
+    pub async fn connect(config: &BridgeConfig, log: Arc<LogFn>) -> Result<Self, BoxError> {
+        let (osc_out, osc_in) = bind_osc(config, &log).await?;
+        SocketTransport::connect_rcp(config, Arc::new(osc_out), osc_in, log).await
+    }
+
+    /// Connects to the console, using OSC sockets that are already bound.
+    async fn connect_rcp(
+        config: &BridgeConfig,
+        osc_out: Arc<OscSender>,
+        osc_in: UnboundedReceiver<OscPacket>,
+        log: Arc<LogFn>,
+    ) -> Result<Self, BoxError> {
+        // RCP (TCP) settings
+        let rcp_port = config.rcp_port;
+        let rcp_host = config.console_ip.clone();
+        let connect_timeout = Duration::from_millis(config.connect_timeout_ms);
+
+        log(
+            LogLevel::Info,
+            format!("Attempting to connect to Yamaha RCP: {}", rcp_host),
+        );
+
+        // Connect to TCP RCP
+        let mut stream = match connect_console(
+            &rcp_host,
+            rcp_port,
+            connect_timeout,
+            &config.bind_device,
+        )
+        .await
+        {
+            Ok(stream) => stream,
+            Err(e) => {
+                log(LogLevel::Error, format!("Failed to connect: {}", e));
+                // Return error to stop the bridge gracefully
+                return Err(format!("Connection failed: {}", e).into());
+            }
+        };
+        log(
+            LogLevel::Info,
+            format!("Connected to Yamaha RCP: {}", rcp_host),
+        );
+        let mut framer = RcpLineFramer::quote_aware();
+        let mut lines = VecDeque::new();
+        if !config.rcp_password.is_empty() {
+            if let Err(e) = login(
+                &mut stream,
+                &config.rcp_user,
+                &config.rcp_password,
+                config.rcp_line_ending.as_str(),
+                Duration::from_millis(config.response_timeout_ms),
+                &mut framer,
+                &mut lines,
+            )
+            .await
+            {
+                log(LogLevel::Error, format!("Failed to log in: {}", e));
+                return Err(format!("Login failed: {}", e).into());
+            }
+            log(LogLevel::Info, "Logged in to Yamaha RCP".to_string());
+        }
+        let (rcp_read, rcp_write) = stream.into_split();
+
+        let mut transport = SocketTransport::unconnected(config, osc_out, osc_in, log);
+        *transport.rcp_read.get_mut() = RcpReader {
+            stream: Some(rcp_read),
+            framer,
+            lines,
+        };
+        *transport.rcp_write.get_mut() = Some(rcp_write);
+        Ok(transport)
+    }
+
+    /// Sets up the console connection without opening it, using OSC
+    /// sockets that are already bound. Reading and writing fail with
+    /// [`NotConnected`](std::io::ErrorKind::NotConnected) until
+    /// [`Transport::reconnect`] first connects.
+    pub(crate) fn unconnected(
+        config: &BridgeConfig,
+        osc_out: Arc<OscSender>,
+        osc_in: UnboundedReceiver<OscPacket>,
+        log: Arc<LogFn>,
+    ) -> Self {
+        SocketTransport {
+            rcp_host: config.console_ip.clone(),
+            rcp_port: config.rcp_port,
+            connect_timeout: Duration::from_millis(config.connect_timeout_ms),
+            response_timeout: Duration::from_millis(config.response_timeout_ms),
+            bind_device: config.bind_device.clone(),
+            rcp_user: config.rcp_user.clone(),
+            rcp_password: config.rcp_password.clone(),
+            rcp_read: Mutex::new(RcpReader {
+                stream: None,
+                framer: RcpLineFramer::quote_aware(),
+                lines: VecDeque::new(),
+            }),
+            rcp_write: Mutex::new(None),
+            osc_out,
+            osc_in: Mutex::new(osc_in),
+            log,
+            log_raw_rcp: config.log_raw_rcp,
+            strict_utf8: config.strict_utf8,
+            line_ending: config.rcp_line_ending.as_str(),
+        }
+    }
+}
+
+/// The error for reading or writing before the console was first connected.
+fn not_connected() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotConnected,
+        "not connected to the console yet",
+    )
+}
+
+/// Opens the RCP connection to the console through the network interface
+/// `device` (if not empty), failing with
+/// [`TimedOut`](std::io::ErrorKind::TimedOut) if it isn't accepted within
+/// `timeout`.
+async fn connect_console(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    device: &str,
+) -> std::io::Result<TcpStream> {
+    tokio::time::timeout(timeout, connect_tcp((host, port), device))
+        .await
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("timed out after {} ms", timeout.as_millis()),
+            )
+        })?
+}
+
+/// Logs in to the console with `login <user> <password>` (the user left
+/// out if empty) and waits up to `timeout` for its `OK login`, as
+/// [`read_reply`] does.
+///
+/// # Errors
+///
+/// Returns a [`PermissionDenied`](std::io::ErrorKind::PermissionDenied)
+/// error if the console answers with an `ERROR`, so the caller knows not to
+/// retry, or the connection or timeout error.
+async fn login(
+    stream: &mut TcpStream,
+    user: &str,
+    password: &str,
+    line_ending: &str,
+    timeout: Duration,
+    framer: &mut RcpLineFramer,
+    lines: &mut VecDeque<String>,
+) -> std::io::Result<()> {
+    let mut command = String::from("login");
+    if !user.is_empty() {
+        command = format!("{} {}", command, quote(user));
+    }
+    stream
+        .write_all(format!("{} {}{}", command, quote(password), line_ending).as_bytes())
+        .await?;
+
+    let reply = read_reply(stream, "login", timeout, framer, lines).await?;
+    if reply.starts_with("ERROR") {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("the console rejected the login: {}", reply.trim()),
+        ));
+    }
+    Ok(())
+}
+
+/// Waits up to `timeout` for the console's reply to a `verb` command, an
+/// `OK <verb>` or any `ERROR`, and returns it. Lines the console sends
+/// before the reply are dropped, and any after it are left in `lines` with
+/// `framer` holding a partial one, for the reader to carry on from.
+///
+/// # Errors
+///
+/// Returns the connection error, or a `TimedOut` error.
+async fn read_reply(
+    stream: &mut TcpStream,
+    verb: &str,
+    timeout: Duration,
+    framer: &mut RcpLineFramer,
+    lines: &mut VecDeque<String>,
+) -> std::io::Result<String> {
+    let reply = async {
+        let mut buffer = [0; 1024];
+        loop {
+            while let Some(line) = lines.pop_front() {
+                let mut parts = line.split_whitespace();
+                match (parts.next(), parts.next()) {
+                    (Some("OK"), Some(replied)) if replied == verb => return Ok(line),
+                    (Some("ERROR"), _) => return Ok(line),
+                    _ => {}
+                }
+            }
+            let n = stream.read(&mut buffer).await?;
+            if n == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            lines.extend(framer.push(&buffer[..n]));
+        }
+    };
+    tokio::time::timeout(timeout, reply).await.map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("no {} reply after {} ms", verb, timeout.as_millis()),
+        )
+    })?
+}
+
+/// Checks that the console at `host`:`port` accepts an RCP connection
+/// within `timeout`, then closes it, the same way the bridge connects.
+///
+/// # Errors
+///
+/// Returns the connection error, or a `TimedOut` error.
+pub async fn probe_console(host: &str, port: u16, timeout: Duration) -> std::io::Result<()> {
+    connect_console(host, port, timeout, "").await.map(drop)
+}
+
+/// Connects to the console as the bridge does, logging in if
+/// [`BridgeConfig::rcp_password`] is set, writes the single RCP command
+/// `command` and closes the connection, without binding any OSC sockets.
+///
+/// With `wait` set, the console's reply to the command (its `OK` or
+/// `ERROR` line) is waited for up to [`BridgeConfig::response_timeout_ms`]
+/// and returned; otherwise `None` is returned once the command is written.
+///
+/// # Errors
+///
+/// Returns the connection, login or timeout error.
+pub async fn send_console_command(
+    config: &BridgeConfig,
+    command: &str,
+    wait: bool,
+) -> std::io::Result<Option<String>> {
+    let response_timeout = Duration::from_millis(config.response_timeout_ms);
+    let mut stream = connect_console(
+        &config.console_ip,
+        config.rcp_port,
+        Duration::from_millis(config.connect_timeout_ms),
+        &config.bind_device,
+    )
+    .await?;
+    let mut framer = RcpLineFramer::quote_aware();
+    let mut lines = VecDeque::new();
+    if !config.rcp_password.is_empty() {
+        login(
+            &mut stream,
+            &config.rcp_user,
+            &config.rcp_password,
+            config.rcp_line_ending.as_str(),
+            response_timeout,
+            &mut framer,
+            &mut lines,
+        )
+        .await?;
+    }
+
+    stream
+        .write_all(format!("{}{}", command, config.rcp_line_ending.as_str()).as_bytes())
+        .await?;
+    let reply = if wait {
+        let verb = command.split_whitespace().next().unwrap_or_default();
+        Some(read_reply(&mut stream, verb, response_timeout, &mut framer, &mut lines).await?)
+    } else {
+        None
+    };
+    stream.shutdown().await?;
+    Ok(reply)
+}
+
+/// Opens a TCP connection to `addr` through the network interface `device`,
+/// or through whichever the routing table picks if it's empty. Each address
+/// `addr` resolves to is tried in turn.
+async fn connect_tcp(
+    addr: impl tokio::net::ToSocketAddrs,
+    device: &str,
+) -> std::io::Result<TcpStream> {
+    if device.is_empty() {
+        return TcpStream::connect(addr).await;
+    }
+    let mut last_error = None;
+    for addr in tokio::net::lookup_host(addr).await? {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        bind_to_device(&socket, device)?;
+        match socket.connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "could not resolve to any address",
+        )
+    }))
+}
+
+/// Whether [`BridgeConfig::bind_device`] is supported on this platform.
+const BIND_DEVICE_SUPPORTED: bool = cfg!(any(
+    target_os = "android",
+    target_os = "fuchsia",
+    target_os = "linux"
+));
+
+/// Pins `socket` to the network interface `device` with SO_BINDTODEVICE,
+/// so its traffic can't go out through another one. Does nothing if
+/// `device` is empty.
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_to_device(socket: &impl std::os::fd::AsRawFd, device: &str) -> std::io::Result<()> {
+    if device.is_empty() {
+        return Ok(());
+    }
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            device.as_ptr() as *const libc::c_void,
+            device.len() as libc::socklen_t,
+        )
+    };
+    if result == -1 {
+        let e = std::io::Error::last_os_error();
+        return Err(std::io::Error::new(
+            e.kind(),
+            format!("failed to bind to device {}: {}", device, e),
+        ));
+    }
+    Ok(())
+}
+
+/// Binding to a device isn't supported here; [`bind_osc`] warns about it
+/// once instead.
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_to_device<S>(_socket: &S, _device: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Binds the OSC sockets as configured, returning the sender and a channel
+/// of received packets fed by a background task per OSC in address.
+///
+/// An OSC in address that can't be bound is logged and skipped; it is only
+/// an error if none of them can be.
+pub(crate) async fn bind_osc(
+    config: &BridgeConfig,
+    log: &Arc<LogFn>,
+) -> Result<(OscSender, UnboundedReceiver<OscPacket>), BoxError> {
+    if !config.bind_device.is_empty() && !BIND_DEVICE_SUPPORTED {
+        log(
+            LogLevel::Warn,
+            format!(
+                "Binding to a network interface isn't supported on this platform, \
+                 ignoring --bind-device {}",
+                config.bind_device
+            ),
+        );
+    }
+    if let Some(problem) = osc_feedback_loop(config) {
+        if config.strict {
+            return Err(problem.into());
+        }
+        log(LogLevel::Warn, problem);
+    }
+    if !config.osc_uds_path.is_empty() {
+        return bind_osc_uds(config, log);
+    }
+
+    let osc_out_addr = format!("{}:{}", config.udp_osc_out_addr, config.udp_osc_out_port);
+    let multicast = multicast_options(config)?;
+    let osc_out = OscSender::new(config, osc_out_addr.clone(), multicast, Arc::clone(log)).await?;
+
+    let in_addrs = if config.udp_osc_in_addrs.is_empty() {
+        std::slice::from_ref(&config.udp_osc_in_addr)
+    } else {
+        config.udp_osc_in_addrs.as_slice()
+    };
+    let (tx, rx) = unbounded_channel();
+    let mut bound = 0;
+    let mut last_error = None;
+    for in_addr in in_addrs {
+        let osc_in_addr = format!("{}:{}", in_addr, config.udp_osc_in_port);
+        match OscReceiver::bind(config.osc_transport, &osc_in_addr, &config.bind_device) {
+            Ok(osc_in) => {
+                log(
+                    LogLevel::Info,
+                    format!(
+                        "Listening for OSC messages on: {} ({})",
+                        osc_in_addr, config.osc_transport
+                    ),
+                );
+                tokio::spawn(osc_in.run(tx.clone(), Arc::clone(log)));
+                bound += 1;
+            }
+            Err(e) => {
+                log(
+                    LogLevel::Error,
+                    format!("Failed to bind OSC address {}: {}", osc_in_addr, e),
+                );
+                last_error = Some(e);
+            }
+        }
+    }
+    if bound == 0 {
+        return Err(last_error.unwrap_or_else(|| "No OSC in address to bind".into()));
+    }
+
+    let transport = match multicast {
+        Some(multicast) => format!("{} multicast, TTL {}", config.osc_transport, multicast.ttl),
+        None => config.osc_transport.to_string(),
+    };
+    log(
+        LogLevel::Info,
+        format!("Sending OSC messages to: {} ({})", osc_out_addr, transport),
+    );
+    Ok((osc_out, rx))
+}
+
+/// Describes how `config` would send OSC back to the bridge's own OSC in
+/// socket, flooding it with its own messages, or returns `None` if it
+/// doesn't: the OSC out address is one of the OSC in addresses, or, over
+/// UDP, the OSC out socket is sent from the OSC in port.
+///
+/// An OSC in address of `0.0.0.0` or `::` counts as every address, but
+/// only the loopback addresses are recognized as the bridge's own host.
+pub fn osc_feedback_loop(config: &BridgeConfig) -> Option<String> {
+    if !config.osc_uds_path.is_empty() {
+        return (config.osc_uds_path == config.osc_uds_out_path).then(|| {
+            format!(
+                "The OSC Unix socket out path {} is the bridge's own OSC in path, \
+                 so everything it sends would come straight back",
+                config.osc_uds_out_path
+            )
+        });
+    }
+    if config.osc_transport == OscTransport::Udp
+        && config.udp_osc_out_local_port != 0
+        && config.udp_osc_out_local_port == config.udp_osc_in_port
+    {
+        return Some(format!(
+            "The OSC out local port {} is also the OSC in port, \
+             so replies to the bridge's OSC would be mixed up with controllers' messages",
+            config.udp_osc_in_port
+        ));
+    }
+    if config.udp_osc_out_port != config.udp_osc_in_port {
+        return None;
+    }
+    let out_ip = match config.udp_osc_out_addr.as_str() {
+        "localhost" => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        addr => addr.parse::<IpAddr>().ok()?,
+    };
+    let in_addrs = if config.udp_osc_in_addrs.is_empty() {
+        std::slice::from_ref(&config.udp_osc_in_addr)
+    } else {
+        config.udp_osc_in_addrs.as_slice()
+    };
+    let reaches = |in_addr: &String| match in_addr.parse::<IpAddr>() {
+        Ok(in_ip) if in_ip.is_unspecified() => out_ip.is_loopback() || out_ip.is_unspecified(),
+        Ok(in_ip) => in_ip == out_ip,
+        Err(_) => false,
+    };
+    in_addrs.iter().any(reaches).then(|| {
+        format!(
+            "The OSC out address {}:{} is the bridge's own OSC in address, \
+             so everything it sends would come straight back",
+            config.udp_osc_out_addr, config.udp_osc_out_port
+        )
+    })
+}
+
+/// How the OSC out socket is set up to send to a multicast group.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MulticastOptions {
+    ttl: u32,
+    /// Unspecified for the routing table's choice
+    interface: Ipv4Addr,
+}
+
+/// The multicast options for OSC out, if [`BridgeConfig::osc_multicast`] is
+/// set or the OSC out address is an IPv4 multicast address.
+fn multicast_options(config: &BridgeConfig) -> Result<Option<MulticastOptions>, BoxError> {
+    let is_multicast = config
+        .udp_osc_out_addr
+        .parse::<Ipv4Addr>()
+        .is_ok_and(|addr| addr.is_multicast());
+    if !config.osc_multicast && !is_multicast {
+        return Ok(None);
+    }
+    if config.osc_transport != OscTransport::Udp {
+        return Err(format!(
+            "Multicast OSC out address {} needs the UDP OSC transport",
+            config.udp_osc_out_addr
+        )
+        .into());
+    }
+    let interface = if config.osc_multicast_interface.is_empty() {
+        Ipv4Addr::UNSPECIFIED
+    } else {
+        config.osc_multicast_interface.parse().map_err(|_| {
+            format!(
+                "Invalid --osc-multicast-interface '{}' (expected an IPv4 address)",
+                config.osc_multicast_interface
+            )
+        })?
+    };
+    Ok(Some(MulticastOptions {
+        ttl: config.osc_multicast_ttl,
+        interface,
+    }))
+}
+
+/// Binds [`BridgeConfig::osc_uds_path`] to receive OSC on, sending from the
+/// same socket to [`BridgeConfig::osc_uds_out_path`].
+#[cfg(unix)]
+fn bind_osc_uds(
+    config: &BridgeConfig,
+    log: &Arc<LogFn>,
+) -> Result<(OscSender, UnboundedReceiver<OscPacket>), BoxError> {
+    if config.osc_uds_out_path.is_empty() {
+        return Err("An OSC Unix socket path needs an OSC Unix socket out path too".into());
+    }
+    // A socket file left behind by a previous run would make the bind fail
+    let _ = std::fs::remove_file(&config.osc_uds_path);
+    let socket = Arc::new(
+        tokio::net::UnixDatagram::bind(&config.osc_uds_path)
+            .map_err(|e| format!("Failed to bind {}: {}", config.osc_uds_path, e))?,
+    );
+
+    let (tx, rx) = unbounded_channel();
+    log(
+        LogLevel::Info,
+        format!(
+            "Listening for OSC messages on: {} (unix)",
+            config.osc_uds_path
+        ),
+    );
+    tokio::spawn(OscReceiver::Unix(Arc::clone(&socket)).run(tx, Arc::clone(log)));
+    log(
+        LogLevel::Info,
+        format!(
+            "Sending OSC messages to: {} (unix)",
+            config.osc_uds_out_path
+        ),
+    );
+    let osc_out = OscSender::Unix {
+        socket,
+        path: config.osc_uds_out_path.clone(),
+    };
+    Ok((osc_out, rx))
+}
+
+#[cfg(not(unix))]
+fn bind_osc_uds(
+    _config: &BridgeConfig,
+    _log: &Arc<LogFn>,
+) -> Result<(OscSender, UnboundedReceiver<OscPacket>), BoxError> {
+    Err("OSC over Unix sockets isn't supported on this platform".into())
+}
+
+/// Forwards each packet received on `osc_in` to the route whose address
+/// prefix it starts with. A bundle is split so each route gets only its own
+/// messages, and anything matching no route is dropped.
+pub(crate) async fn route_osc(
+    mut osc_in: UnboundedReceiver<OscPacket>,
+    routes: Vec<(String, UnboundedSender<OscPacket>)>,
+    log: Arc<LogFn>,
+) {
+    while let Some(packet) = osc_in.recv().await {
+        let mut routed = false;
+        for (prefix, tx) in &routes {
+            if let Some(packet) = filter_by_prefix(&packet, prefix) {
+                routed = true;
+                let _ = tx.send(packet);
+            }
+        }
+        if !routed {
+            log(
+                LogLevel::Warn,
+                "Dropped OSC packet not addressed to any console".to_string(),
+            );
+        }
+    }
+}
+
+/// The part of `packet` addressed under `prefix`, or `None` if there is none.
+fn filter_by_prefix(packet: &OscPacket, prefix: &str) -> Option<OscPacket> {
+    match packet {
+        OscPacket::Message(msg) => msg
+            .addr
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            .then(|| packet.clone()),
+        OscPacket::Bundle(bundle) => {
+            let content: Vec<OscPacket> = bundle
+                .content
+                .iter()
+                .filter_map(|packet| filter_by_prefix(packet, prefix))
+                .collect();
+            (!content.is_empty()).then_some(OscPacket::Bundle(rosc::OscBundle {
+                timetag: bundle.timetag,
+                content,
+            }))
+        }
+    }
+}
+
+/// Formats `bytes` as a hexdump, 16 bytes per line, with the offset, the
+/// bytes in hex and the printable ASCII characters, e.g.
+/// `00000000  4f 4b 0a                                          |OK.|`
+fn hexdump(bytes: &[u8]) -> String {
+    let mut lines = Vec::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        lines.push(format!(
+            "{:08x}  {:<47}  |{}|",
+            i * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    lines.join("\n")
+}
+
+impl Transport for SocketTransport {
+    async fn read_line(&self) -> std::io::Result<Option<String>> {
+        let mut reader = self.rcp_read.lock().await;
+        let mut buffer = [0; 1024];
+
+        //RCP commands can sometimes be sent in bundles and should be split by newline
+        loop {
+            if let Some(line) = reader.lines.pop_front() {
+                return Ok(Some(line));
+            }
+
+            let Some(stream) = &mut reader.stream else {
+                return Err(not_connected());
+            };
+            let n = stream.read(&mut buffer).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            if self.log_raw_rcp {
+                (self.log)(
+                    LogLevel::Debug,
+                    format!("Read {} bytes from RCP:\n{}", n, hexdump(&buffer[..n])),
+                );
+            }
+            if !self.strict_utf8 {
+                let lines = reader.framer.push(&buffer[..n]);
+                reader.lines.extend(lines);
+                continue;
+            }
+            for line in reader.framer.push_raw(&buffer[..n]) {
+                match String::from_utf8(line) {
+                    Ok(line) => reader.lines.push_back(line),
+                    Err(e) => (self.log)(
+                        LogLevel::Warn,
+                        format!(
+                            "Skipped RCP line that isn't valid UTF-8:\n{}",
+                            hexdump(e.as_bytes())
+                        ),
+                    ),
+                }
+            }
+        }
+    }
+
+    async fn write_line(&self, line: &str) -> std::io::Result<()> {
+        self.rcp_write
+            .lock()
+            .await
+            .as_mut()
+            .ok_or_else(not_connected)?
+            .write_all(format!("{}{}", line, self.line_ending).as_bytes())
+            .await
+    }
+
+    async fn write_lines(&self, lines: &[String]) -> std::io::Result<()> {
+        let mut batch = String::new();
+        for line in lines {
+            batch.push_str(line);
+            batch.push_str(self.line_ending);
+        }
+        self.rcp_write
+            .lock()
+            .await
+            .as_mut()
+            .ok_or_else(not_connected)?
+            .write_all(batch.as_bytes())
+            .await
+    }
+
+    async fn close(&self) -> std::io::Result<()> {
+        let mut rcp_write = self.rcp_write.lock().await;
+        let Some(rcp_write) = rcp_write.as_mut() else {
+            return Ok(());
+        };
+        rcp_write.flush().await?;
+        rcp_write.shutdown().await
+    }
+
+    async fn reconnect(&self) -> std::io::Result<()> {
+        let mut stream = connect_console(
+            &self.rcp_host,
+            self.rcp_port,
+            self.connect_timeout,
+            &self.bind_device,
+        )
+        .await?;
+
+        let mut reader = self.rcp_read.lock().await;
+        reader.framer.clear();
+        reader.lines.clear();
+        if !self.rcp_password.is_empty() {
+            let reader = &mut *reader;
+            login(
+                &mut stream,
+                &self.rcp_user,
+                &self.rcp_password,
+                self.line_ending,
+                self.response_timeout,
+                &mut reader.framer,
+                &mut reader.lines,
+            )
+            .await?;
+        }
+        let (rcp_read, rcp_write) = stream.into_split();
+        reader.stream = Some(rcp_read);
+        *self.rcp_write.lock().await = Some(rcp_write);
+        Ok(())
+    }
+
+    async fn recv(&self) -> std::io::Result<Option<OscPacket>> {
+        Ok(self.osc_in.lock().await.recv().await)
+    }
+
+    async fn send(&self, packet: &OscPacket) -> Result<(), BoxError> {
+        self.osc_out.send(packet).await
+    }
+}
+
+/// An in-memory [`Transport`] for testing, driven by its [`MockHandle`].
+///
+/// Lines and packets are passed through unbounded channels, so nothing is
+/// ever dropped or blocked on.
+pub struct MockTransport {
+    rcp_in: Mutex<UnboundedReceiver<String>>,
+    rcp_reconnects: Mutex<UnboundedReceiver<UnboundedReceiver<String>>>,
+    rcp_out: UnboundedSender<String>,
+    osc_in: Mutex<UnboundedReceiver<OscPacket>>,
+    osc_out: UnboundedSender<OscPacket>,
+}
+
+/// The far side of a [`MockTransport`], playing both the console and the OSC
+/// application.
+pub struct MockHandle {
+    rcp_in: Option<UnboundedSender<String>>,
+    rcp_reconnects: UnboundedSender<UnboundedReceiver<String>>,
+    rcp_out: UnboundedReceiver<String>,
+    osc_in: Option<UnboundedSender<OscPacket>>,
+    osc_out: UnboundedReceiver<OscPacket>,
+}
+
+impl MockTransport {
+    /// Creates a connected transport and handle pair.
+    pub fn pair() -> (MockTransport, MockHandle) {
+        let (rcp_in_tx, rcp_in_rx) = unbounded_channel();
+        let (rcp_reconnects_tx, rcp_reconnects_rx) = unbounded_channel();
+        let (rcp_out_tx, rcp_out_rx) = unbounded_channel();
+        let (osc_in_tx, osc_in_rx) = unbounded_channel();
+        let (osc_out_tx, osc_out_rx) = unbounded_channel();
+        (
+            MockTransport {
+                rcp_in: Mutex::new(rcp_in_rx),
+                rcp_reconnects: Mutex::new(rcp_reconnects_rx),
+                rcp_out: rcp_out_tx,
+                osc_in: Mutex::new(osc_in_rx),
+                osc_out: osc_out_tx,
+            },
+            MockHandle {
+                rcp_in: Some(rcp_in_tx),
+                rcp_reconnects: rcp_reconnects_tx,
+                rcp_out: rcp_out_rx,
+                osc_in: Some(osc_in_tx),
+                osc_out: osc_out_rx,
+            },
+        )
+    }
+}
+
+impl Transport for MockTransport {
+    async fn read_line(&self) -> std::io::Result<Option<String>> {
+        Ok(self.rcp_in.lock().await.recv().await)
+    }
+
+    async fn write_line(&self, line: &str) -> std::io::Result<()> {
+        self.rcp_out
+            .send(line.to_string())
+            .map_err(|_| std::io::ErrorKind::BrokenPipe.into())
+    }
+
+    async fn close(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Waits for the handle to [`reopen_rcp`](MockHandle::reopen_rcp), and
+    /// fails if the handle has been dropped.
+    async fn reconnect(&self) -> std::io::Result<()> {
+        match self.rcp_reconnects.lock().await.recv().await {
+            Some(rcp_in) => {
+                *self.rcp_in.lock().await = rcp_in;
+                Ok(())
+            }
+            None => Err(std::io::ErrorKind::ConnectionRefused.into()),
+        }
+    }
+
+    async fn recv(&self) -> std::io::Result<Option<OscPacket>> {
+        Ok(self.osc_in.lock().await.recv().await)
+    }
+
+    async fn send(&self, packet: &OscPacket) -> Result<(), BoxError> {
+        self.osc_out
+            .send(packet.clone())
+            .map_err(|_| "OSC receiver dropped".into())
+    }
+}
+
+impl MockHandle {
+    /// Sends a line to the bridge as if from the console.
+    pub fn send_rcp(&self, line: &str) {
+        if let Some(tx) = &self.rcp_in {
+            let _ = tx.send(line.to_string());
+        }
+    }
+
+    /// Waits for the next line the bridge writes to the console. Returns
+    /// `None` once the bridge has stopped.
+    pub async fn recv_rcp(&mut self) -> Option<String> {
+        self.rcp_out.recv().await
+    }
+
+    /// Sends a packet to the bridge as if from the OSC application.
+    pub fn send_osc(&self, packet: OscPacket) {
+        if let Some(tx) = &self.osc_in {
+            let _ = tx.send(packet);
+        }
+    }
+
+    /// Waits for the next packet the bridge sends to the OSC application.
+    /// Returns `None` once the bridge has stopped.
+    pub async fn recv_osc(&mut self) -> Option<OscPacket> {
+        self.osc_out.recv().await
+    }
+
+    /// Closes the console side of the connection, as if the console hung up.
+    pub fn close_rcp(&mut self) {
+        self.rcp_in = None;
+    }
+
+    /// Accepts the bridge's next reconnect, as if the console came back.
+    /// The current connection is closed if it is still open, and later
+    /// [`send_rcp`](MockHandle::send_rcp)s go to the new one.
+    pub fn reopen_rcp(&mut self) {
+        let (tx, rx) = unbounded_channel();
+        self.rcp_in = Some(tx);
+        let _ = self.rcp_reconnects.send(rx);
+    }
+
+    /// Closes the OSC side, so no more packets reach the bridge.
+    pub fn close_osc(&mut self) {
+        self.osc_in = None;
+    }
+}
+
+/// Largest OSC packet accepted over TCP. The length prefix comes straight
+/// off the wire, so it's capped to avoid allocating whatever a buggy sender
+/// claims.
+const MAX_TCP_PACKET_SIZE: usize = 1024 * 1024;
+
+/// Size of the buffer OSC datagrams are received into, so even the largest
+/// UDP datagram fits. Unix datagrams can be larger; one that fills the
+/// buffer may have been cut short, so it is dropped with a warning.
+const MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+/// Sends OSC packets to the configured OSC out address.
+pub(crate) enum OscSender {
+    /// Packets over `max_size` bytes are split or dropped, as
+    /// [`split_osc_packet`] does, unless it is 0. With a `queue`, datagrams
+    /// are sent from its task rather than by the caller.
+    Udp {
+        socket: Arc<UdpSocket>,
+        addr: String,
+        max_size: usize,
+        queue: Option<SendQueue>,
+        log: Arc<LogFn>,
+    },
+    /// The TCP connection is made lazily on the first send, and re-made on
+    /// the next send after a failure, so the OSC application doesn't have to
+    /// be running before the bridge starts.
+    Tcp {
+        addr: String,
+        stream: Mutex<Option<TcpStream>>,
+        bind_device: String,
+    },
+    /// Sent from the socket OSC is received on, so the OSC application can
+    /// reply to the sender
+    #[cfg(unix)]
+    Unix {
+        socket: Arc<tokio::net::UnixDatagram>,
+        path: String,
+    },
+}
+
+impl OscSender {
+    pub(crate) async fn new(
+        config: &BridgeConfig,
+        addr: String,
+        multicast: Option<MulticastOptions>,
+        log: Arc<LogFn>,
+    ) -> Result<Self, BoxError> {
+        let bind_device = config.bind_device.as_str();
+        match config.osc_transport {
+            OscTransport::Udp => {
+                let local_port = config.udp_osc_out_local_port;
+                let socket = UdpSocket::bind(("0.0.0.0", local_port))
+                    .await
+                    .map_err(|e| format!("Failed to bind OSC out port {}: {}", local_port, e))?;
+                bind_to_device(&socket, bind_device)?;
+                if let Some(multicast) = multicast {
+                    socket.set_multicast_ttl_v4(multicast.ttl)?;
+                    socket.set_multicast_loop_v4(true)?;
+                    if !multicast.interface.is_unspecified() {
+                        socket2::SockRef::from(&socket)
+                            .set_multicast_if_v4(&multicast.interface)?;
+                    }
+                }
+                let socket = Arc::new(socket);
+                let queue = (config.osc_send_queue_size > 0).then(|| {
+                    SendQueue::spawn(
+                        Arc::clone(&socket),
+                        addr.clone(),
+                        config.osc_send_queue_size,
+                        Arc::clone(&log),
+                    )
+                });
+                Ok(OscSender::Udp {
+                    socket,
+                    addr,
+                    max_size: config.max_udp_size,
+                    queue,
+                    log,
+                })
+            }
+            OscTransport::Tcp => Ok(OscSender::Tcp {
+                addr,
+                stream: Mutex::new(None),
+                bind_device: bind_device.to_string(),
+            }),
+        }
+    }
+
+    pub(crate) async fn send(&self, packet: &OscPacket) -> Result<(), BoxError> {
+        match self {
+            OscSender::Udp {
+                socket,
+                addr,
+                max_size,
+                queue,
+                log,
+            } => {
+                let send = async |encoded: Vec<u8>| match queue {
+                    Some(queue) => {
+                        queue.push(encoded);
+                        Ok::<_, std::io::Error>(())
+                    }
+                    None => socket.send_to(&encoded, addr.as_str()).await.map(|_| ()),
+                };
+                let encoded = rosc::encoder::encode(packet)?;
+                if *max_size == 0 || encoded.len() <= *max_size {
+                    send(encoded).await?;
+                    return Ok(());
+                }
+
+                let (packets, oversized) = split_osc_packet(packet.clone(), *max_size)?;
+                for msg in &oversized {
+                    log(
+                        LogLevel::Warn,
+                        format!(
+                            "Dropped OSC message to {}: it is over --max-udp-size ({} bytes) \
+                             and would be lost on the network",
+                            msg.addr, max_size
+                        ),
+                    );
+                }
+                if !packets.is_empty() {
+                    log(
+                        LogLevel::Warn,
+                        format!(
+                            "Split a {} byte OSC bundle into {} packets to fit --max-udp-size \
+                             ({} bytes)",
+                            encoded.len(),
+                            packets.len(),
+                            max_size
+                        ),
+                    );
+                }
+                for packet in &packets {
+                    send(rosc::encoder::encode(packet)?).await?;
+                }
+            }
+            OscSender::Tcp {
+                addr,
+                stream,
+                bind_device,
+            } => {
+                let encoded = rosc::encoder::encode_tcp(packet)?;
+                let mut stream = stream.lock().await;
+                if stream.is_none() {
+                    *stream = Some(connect_tcp(addr.as_str(), bind_device).await?);
+                }
+                if let Some(connected) = stream.as_mut()
+                    && let Err(e) = connected.write_all(&encoded).await
+                {
+                    // Drop the broken connection so the next send reconnects
+                    *stream = None;
+                    return Err(e.into());
+                }
+            }
+            #[cfg(unix)]
+            OscSender::Unix { socket, path } => {
+                let encoded = rosc::encoder::encode(packet)?;
+                socket.send_to(&encoded, path).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A bounded queue of encoded OSC datagrams in front of the UDP out socket,
+/// sent by a task of its own so a slow network never holds up reading from
+/// the console. When it is full the oldest datagram is dropped; drops are
+/// counted and logged once the queue catches up.
+pub(crate) struct SendQueue {
+    shared: Arc<SendQueueShared>,
+    task: tokio::task::AbortHandle,
+}
+
+struct SendQueueShared {
+    datagrams: std::sync::Mutex<VecDeque<Vec<u8>>>,
+    ready: tokio::sync::Notify,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+impl SendQueue {
+    fn spawn(socket: Arc<UdpSocket>, addr: String, capacity: usize, log: Arc<LogFn>) -> Self {
+        let shared = Arc::new(SendQueueShared {
+            datagrams: std::sync::Mutex::new(VecDeque::with_capacity(capacity)),
+            ready: tokio::sync::Notify::new(),
+            capacity,
+            dropped: AtomicU64::new(0),
+        });
+        let task = tokio::spawn({
+            let shared = Arc::clone(&shared);
+            async move {
+                loop {
+                    let next = shared.datagrams.lock().unwrap().pop_front();
+                    let Some(datagram) = next else {
+                        let dropped = shared.dropped.swap(0, Ordering::Relaxed);
+                        if dropped > 0 {
+                            log(
+                                LogLevel::Warn,
+                                format!(
+                                    "Dropped {} OSC packets to {}: the OSC out queue was full",
+                                    dropped, addr
+                                ),
+                            );
+                        }
+                        shared.ready.notified().await;
+                        continue;
+                    };
+                    if let Err(e) = socket.send_to(&datagram, addr.as_str()).await {
+                        log(LogLevel::Error, format!("Failed to send OSC: {}", e));
+                    }
+                }
+            }
+        });
+        SendQueue {
+            shared,
+            task: task.abort_handle(),
+        }
+    }
+
+    /// Queues a datagram for sending, dropping the oldest if it is full.
+    fn push(&self, datagram: Vec<u8>) {
+        let mut datagrams = self.shared.datagrams.lock().unwrap();
+        if datagrams.len() >= self.shared.capacity {
+            datagrams.pop_front();
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        datagrams.push_back(datagram);
+        drop(datagrams);
+        self.shared.ready.notify_one();
+    }
+}
+
+impl Drop for SendQueue {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Splits `packet` into packets that each encode to at most `max_size`
+/// bytes, for sending over UDP.
+///
+/// A bundle that is too large is split into bundles with the same time tag,
+/// keeping its contents in order. A message that can't fit, even on its own,
+/// is returned separately rather than split, as there's no way to send it
+/// whole. An element that only fits outside a bundle is sent by itself.
+///
+/// # Errors
+///
+/// Returns an error if a packet can't be encoded.
+pub fn split_osc_packet(
+    packet: OscPacket,
+    max_size: usize,
+) -> Result<(Vec<OscPacket>, Vec<OscMessage>), rosc::OscError> {
+    let mut packets = Vec::new();
+    let mut oversized = Vec::new();
+    split_into(packet, max_size, &mut packets, &mut oversized)?;
+    Ok((packets, oversized))
+}
+
+/// The `#bundle` tag and time tag that start every encoded bundle.
+const BUNDLE_HEADER_SIZE: usize = 16;
+
+fn split_into(
+    packet: OscPacket,
+    max_size: usize,
+    packets: &mut Vec<OscPacket>,
+    oversized: &mut Vec<OscMessage>,
+) -> Result<(), rosc::OscError> {
+    if rosc::encoder::encode(&packet)?.len() <= max_size {
+        packets.push(packet);
+        return Ok(());
+    }
+    let bundle = match packet {
+        OscPacket::Message(msg) => {
+            oversized.push(msg);
+            return Ok(());
+        }
+        OscPacket::Bundle(bundle) => bundle,
+    };
+
+    let timetag = bundle.timetag;
+    let flush = |content: &mut Vec<OscPacket>, packets: &mut Vec<OscPacket>| {
+        if !content.is_empty() {
+            packets.push(OscPacket::Bundle(OscBundle {
+                timetag,
+                content: std::mem::take(content),
+            }));
+        }
+    };
+    let mut content = Vec::new();
+    let mut size = BUNDLE_HEADER_SIZE;
+    for element in bundle.content {
+        // Each element is prefixed with its size
+        let element_size = 4 + rosc::encoder::encode(&element)?.len();
+        if BUNDLE_HEADER_SIZE + element_size > max_size {
+            flush(&mut content, packets);
+            size = BUNDLE_HEADER_SIZE;
+            split_into(element, max_size, packets, oversized)?;
+            continue;
+        }
+        if size + element_size > max_size {
+            flush(&mut content, packets);
+            size = BUNDLE_HEADER_SIZE;
+        }
+        content.push(element);
+        size += element_size;
+    }
+    flush(&mut content, packets);
+    Ok(())
+}
+
+/// Receives OSC packets on the configured OSC in address.
+pub(crate) enum OscReceiver {
+    Udp(UdpSocket),
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(Arc<tokio::net::UnixDatagram>),
+}
+
+impl OscReceiver {
+    /// Binds the OSC in socket with SO_REUSEADDR (and SO_REUSEPORT on Unix)
+    /// so the bridge can be restarted immediately, pinned to the network
+    /// interface `device` if it isn't empty.
+    pub(crate) fn bind(
+        transport: OscTransport,
+        addr: &str,
+        device: &str,
+    ) -> Result<Self, BoxError> {
+        let addr: SocketAddr = addr
+            .parse()
+            .map_err(|e| format!("Invalid OSC address: {}", e))?;
+        let socket_type = match transport {
+            OscTransport::Udp => Type::DGRAM,
+            OscTransport::Tcp => Type::STREAM,
+        };
+        let socket = Socket::new(Domain::for_address(addr), socket_type, None)?;
+        socket.set_reuse_address(true)?;
+
+        // On Unix systems, also set SO_REUSEPORT for immediate reuse
+        #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
+        {
+            use std::os::unix::io::AsRawFd;
+            let fd = socket.as_raw_fd();
+            unsafe {
+                let optval: libc::c_int = 1;
+                libc::setsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    libc::SO_REUSEPORT,
+                    &optval as *const _ as *const libc::c_void,
+                    std::mem::size_of_val(&optval) as libc::socklen_t,
+                );
+            }
+        }
+
+        bind_to_device(&socket, device)?;
+        socket.bind(&addr.into())?;
+        socket.set_nonblocking(true)?;
+
+        match transport {
+            OscTransport::Udp => {
+                let std_socket: std::net::UdpSocket = socket.into();
+                Ok(OscReceiver::Udp(UdpSocket::from_std(std_socket)?))
+            }
+            OscTransport::Tcp => {
+                socket.listen(128)?;
+                let std_listener: std::net::TcpListener = socket.into();
+                Ok(OscReceiver::Tcp(TcpListener::from_std(std_listener)?))
+            }
+        }
+    }
+
+    /// Receives packets until the socket fails, forwarding each decoded
+    /// packet to `tx`. Each accepted TCP connection is read in its own task.
+    pub(crate) async fn run(self, tx: UnboundedSender<OscPacket>, log: Arc<LogFn>) {
+        match self {
+            OscReceiver::Udp(socket) => {
+                let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+                loop {
+                    match socket.recv_from(&mut buf).await {
+                        Ok((size, _addr)) => {
+                            if !forward_datagram(&buf, size, &tx, &log) {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            log(
+                                LogLevel::Error,
+                                format!("Error receiving OSC message: {}", e),
+                            );
+                            break;
+                        }
+                    }
+                }
+            }
+            #[cfg(unix)]
+            OscReceiver::Unix(socket) => {
+                let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+                loop {
+                    match socket.recv(&mut buf).await {
+                        Ok(size) => {
+                            if !forward_datagram(&buf, size, &tx, &log) {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            log(
+                                LogLevel::Error,
+                                format!("Error receiving OSC message: {}", e),
+                            );
+                            break;
+                        }
+                    }
+                }
+            }
+            OscReceiver::Tcp(listener) => loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        log(LogLevel::Info, format!("OSC client connected: {}", peer));
+                        let tx = tx.clone();
+                        let log = Arc::clone(&log);
+                        tokio::spawn(async move {
+                            read_tcp_packets(stream, tx, &log).await;
+                            log(LogLevel::Info, format!("OSC client disconnected: {}", peer));
+                        });
+                    }
+                    Err(e) => {
+                        log(
+                            LogLevel::Error,
+                            format!("Error accepting OSC connection: {}", e),
+                        );
+                        break;
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Reads length-prefixed OSC packets from a TCP stream until it closes.
+async fn read_tcp_packets(mut stream: TcpStream, tx: UnboundedSender<OscPacket>, log: &LogFn) {
+    loop {
+        let size = match stream.read_u32().await {
+            Ok(size) => size as usize,
+            // Clean EOF between packets
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => {
+                log(
+                    LogLevel::Error,
+                    format!("Error receiving OSC message: {}", e),
+                );
+                break;
+            }
+        };
+        if size > MAX_TCP_PACKET_SIZE {
+            log(
+                LogLevel::Error,
+                format!("OSC packet of {} bytes exceeds the TCP size limit", size),
+            );
+            break;
+        }
+
+        let mut buf = vec![0u8; size];
+        if let Err(e) = stream.read_exact(&mut buf).await {
+            log(
+                LogLevel::Error,
+                format!("Error receiving OSC message: {}", e),
+            );
+            break;
+        }
+        if !forward_osc(&buf, &tx, log) {
+            break;
+        }
+    }
+}
+
+/// Forwards the `size` bytes of a datagram received into `buf` as
+/// [`forward_osc`] does, logging and skipping one that filled the buffer,
+/// as the rest of it was cut off.
+fn forward_datagram(buf: &[u8], size: usize, tx: &UnboundedSender<OscPacket>, log: &LogFn) -> bool {
+    if size >= buf.len() {
+        log(
+            LogLevel::Warn,
+            format!(
+                "Ignoring OSC packet of {} bytes or more: it doesn't fit the receive buffer",
+                buf.len()
+            ),
+        );
+        return true;
+    }
+    forward_osc(&buf[..size], tx, log)
+}
+
+/// Decodes an OSC packet and forwards it to `tx`, logging and skipping one
+/// that is malformed, e.g. with fewer arguments than its type tags declare.
+/// Returns `false` once the receiving side has gone away.
+fn forward_osc(buf: &[u8], tx: &UnboundedSender<OscPacket>, log: &LogFn) -> bool {
+    match rosc::decoder::decode_udp(buf) {
+        Ok((_remaining, packet)) => tx.send(packet).is_ok(),
+        Err(e) => {
+            log(
+                LogLevel::Warn,
+                format!("Ignoring malformed OSC packet: {:?}", e),
+            );
+            true
+        }
+    }
+}
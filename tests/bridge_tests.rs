@@ -0,0 +1,2770 @@
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::time::timeout;
+use yamaha_rcp_to_osc::{
+    AddressFilter, Bridge, BridgeConfig, ConsoleConfig, ConsoleModel, ConversionOptions, Deadband,
+    LogLevel, MappingTable, MockHandle, MockTransport, OscTransport, ParameterMapping, QueryError,
+    RcpLineEnding, RcpMessageKind, VerbMapping, osc_feedback_loop, probe_console,
+    run_bridge_with_logger, send_console_command, split_osc_packet,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns a local port that was free at the time of the call.
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn config(rcp_port: u16, osc_out_port: u16, osc_in_port: u16) -> BridgeConfig {
+    BridgeConfig {
+        console_ip: "127.0.0.1".to_string(),
+        rcp_port,
+        udp_osc_out_addr: "127.0.0.1".to_string(),
+        udp_osc_out_port: osc_out_port,
+        udp_osc_in_addr: "127.0.0.1".to_string(),
+        udp_osc_in_port: osc_in_port,
+        ..base_config()
+    }
+}
+
+/// The defaults without model detection, so the only RCP written to the
+/// console is what each test triggers.
+fn base_config() -> BridgeConfig {
+    BridgeConfig {
+        detect_model: false,
+        ..Default::default()
+    }
+}
+
+/// Starts a bridge over a mock transport, returning the handle that plays
+/// the console and OSC application.
+fn run_mock_bridge(config: BridgeConfig) -> (Bridge<MockTransport>, MockHandle) {
+    let (transport, handle) = MockTransport::pair();
+    let bridge = Bridge::new(config, transport, Box::new(|_, _| {}));
+    tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+    (bridge, handle)
+}
+
+fn message(packet: OscPacket) -> OscMessage {
+    match packet {
+        OscPacket::Message(msg) => msg,
+        OscPacket::Bundle(_) => panic!("expected a message"),
+    }
+}
+
+async fn read_tcp_packet(stream: &mut TcpStream) -> OscPacket {
+    let size = stream.read_u32().await.unwrap() as usize;
+    let mut buf = vec![0u8; size];
+    stream.read_exact(&mut buf).await.unwrap();
+    rosc::decoder::decode_udp(&buf).unwrap().1
+}
+
+#[tokio::test]
+async fn test_osc_over_tcp() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let controller = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+
+    let config = BridgeConfig {
+        osc_transport: OscTransport::Tcp,
+        ..config(
+            console.local_addr().unwrap().port(),
+            controller.local_addr().unwrap().port(),
+            osc_in_port,
+        )
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    let (console_read, mut console_write) = console_stream.into_split();
+
+    // RCP -> OSC: delivered to the controller as a length-prefixed packet
+    console_write
+        .write_all(b"NOTIFY set MIXER:Current/Channel/Fader/Level 0 0 -1000\n")
+        .await
+        .unwrap();
+    let (mut controller_stream, _) = timeout(TIMEOUT, controller.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let packet = timeout(TIMEOUT, read_tcp_packet(&mut controller_stream))
+        .await
+        .unwrap();
+    match packet {
+        OscPacket::Message(msg) => {
+            assert_eq!(msg.addr, "/set/MIXER:Current/Channel/Fader/Level");
+            assert_eq!(
+                msg.args,
+                vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)]
+            );
+        }
+        OscPacket::Bundle(_) => panic!("expected a message"),
+    }
+
+    // OSC -> RCP: a length-prefixed packet sent to the bridge reaches the console
+    let mut osc_in = TcpStream::connect(("127.0.0.1", osc_in_port))
+        .await
+        .unwrap();
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/Channel/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    });
+    osc_in
+        .write_all(&rosc::encoder::encode_tcp(&packet).unwrap())
+        .await
+        .unwrap();
+
+    let mut line = String::new();
+    timeout(TIMEOUT, BufReader::new(console_read).read_line(&mut line))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(line, "get MIXER:Current/Channel/Fader/Level 0 0\n");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_osc_over_unix_socket() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let dir = std::env::temp_dir().join(format!("bridge-uds-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (bridge_path, controller_path) = (dir.join("bridge.sock"), dir.join("controller.sock"));
+    let _ = std::fs::remove_file(&controller_path);
+    let controller = tokio::net::UnixDatagram::bind(&controller_path).unwrap();
+
+    let config = BridgeConfig {
+        osc_uds_path: bridge_path.to_str().unwrap().to_string(),
+        osc_uds_out_path: controller_path.to_str().unwrap().to_string(),
+        ..config(console.local_addr().unwrap().port(), 0, 0)
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    let (console_read, mut console_write) = console_stream.into_split();
+
+    // RCP -> OSC
+    console_write
+        .write_all(b"NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1\n")
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let len = timeout(TIMEOUT, controller.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..len]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/On");
+
+    // OSC -> RCP
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    });
+    controller
+        .send_to(&rosc::encoder::encode(&packet).unwrap(), &bridge_path)
+        .await
+        .unwrap();
+    let mut line = String::new();
+    timeout(TIMEOUT, BufReader::new(console_read).read_line(&mut line))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(line, "get MIXER:Current/InCh/Fader/On 0 0\n");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_large_osc_message() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    tokio::spawn(run_bridge_with_logger(
+        config(console.local_addr().unwrap().port(), 0, osc_in_port),
+        Box::new(|_, _| {}),
+    ));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+
+    // Far larger than an Ethernet frame, so it can't be read 1 KB at a time
+    let name = "x".repeat(8000);
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+        args: vec![
+            OscType::Int(0),
+            OscType::Int(0),
+            OscType::String(name.clone()),
+        ],
+    });
+    controller
+        .send_to(
+            &rosc::encoder::encode(&packet).unwrap(),
+            ("127.0.0.1", osc_in_port),
+        )
+        .await
+        .unwrap();
+    let mut line = String::new();
+    timeout(TIMEOUT, BufReader::new(console_stream).read_line(&mut line))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        line,
+        format!("set MIXER:Current/InCh/Label/Name 0 0 \"{}\"\n", name)
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_oversized_osc_datagram_is_reported() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let dir = std::env::temp_dir().join(format!("bridge-uds-large-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (bridge_path, controller_path) = (dir.join("bridge.sock"), dir.join("controller.sock"));
+    let _ = std::fs::remove_file(&controller_path);
+    let controller = tokio::net::UnixDatagram::bind(&controller_path).unwrap();
+
+    let uds = BridgeConfig {
+        osc_uds_path: bridge_path.to_str().unwrap().to_string(),
+        osc_uds_out_path: controller_path.to_str().unwrap().to_string(),
+        ..config(console.local_addr().unwrap().port(), 0, 0)
+    };
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(run_bridge_with_logger(
+        uds,
+        Box::new(move |level, msg| {
+            let _ = log_tx.send((level, msg));
+        }),
+    ));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+
+    // Unix datagrams can be larger than any UDP one
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+        args: vec![OscType::String("x".repeat(70_000))],
+    });
+    controller
+        .send_to(&rosc::encoder::encode(&packet).unwrap(), &bridge_path)
+        .await
+        .unwrap();
+    let warning = timeout(TIMEOUT, async {
+        loop {
+            let (level, msg) = log_rx.recv().await.unwrap();
+            if level == LogLevel::Warn {
+                return msg;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(warning.starts_with("Ignoring OSC packet of 65536 bytes or more"));
+
+    // Smaller packets still get through
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    });
+    controller
+        .send_to(&rosc::encoder::encode(&packet).unwrap(), &bridge_path)
+        .await
+        .unwrap();
+    let mut line = String::new();
+    timeout(TIMEOUT, BufReader::new(console_stream).read_line(&mut line))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(line, "get MIXER:Current/InCh/Fader/On 0 0\n");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_bind_device() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let config = BridgeConfig {
+        bind_device: "lo".to_string(),
+        ..config(
+            console.local_addr().unwrap().port(),
+            controller.local_addr().unwrap().port(),
+            osc_in_port,
+        )
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    let (console_read, mut console_write) = console_stream.into_split();
+
+    console_write
+        .write_all(b"NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1\n")
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let len = timeout(TIMEOUT, controller.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..len]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/On");
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    });
+    controller
+        .send_to(
+            &rosc::encoder::encode(&packet).unwrap(),
+            ("127.0.0.1", osc_in_port),
+        )
+        .await
+        .unwrap();
+    let mut line = String::new();
+    timeout(TIMEOUT, BufReader::new(console_read).read_line(&mut line))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(line, "get MIXER:Current/InCh/Fader/On 0 0\n");
+}
+
+#[tokio::test]
+async fn test_rcp_login() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = BridgeConfig {
+        rcp_user: "admin".to_string(),
+        rcp_password: "secret".to_string(),
+        init_commands: vec!["mtrstart MIXER:Current/InCh/Fader/Level 100".to_string()],
+        ..config(
+            console.local_addr().unwrap().port(),
+            free_port(),
+            free_port(),
+        )
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    let (console_read, mut console_write) = console_stream.into_split();
+    let mut console_read = BufReader::new(console_read);
+
+    // The login comes before the init commands, which wait for the reply
+    let mut line = String::new();
+    timeout(TIMEOUT, console_read.read_line(&mut line))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(line, "login \"admin\" \"secret\"\n");
+    console_write
+        .write_all(b"Welcome\nOK login\n")
+        .await
+        .unwrap();
+    line.clear();
+    timeout(TIMEOUT, console_read.read_line(&mut line))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(line, "mtrstart MIXER:Current/InCh/Fader/Level 100\n");
+}
+
+#[tokio::test]
+async fn test_rcp_line_ending() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let crlf = BridgeConfig {
+        rcp_password: "secret".to_string(),
+        init_commands: vec!["devinfo productname".to_string()],
+        rcp_line_ending: RcpLineEnding::Crlf,
+        ..config(
+            console.local_addr().unwrap().port(),
+            controller.local_addr().unwrap().port(),
+            osc_in_port,
+        )
+    };
+    tokio::spawn(run_bridge_with_logger(crlf, Box::new(|_, _| {})));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    let (console_read, mut console_write) = console_stream.into_split();
+    let mut console_read = BufReader::new(console_read);
+    let mut read_line = async || {
+        let mut line = String::new();
+        timeout(TIMEOUT, console_read.read_line(&mut line))
+            .await
+            .unwrap()
+            .unwrap();
+        line
+    };
+
+    // The login and init commands
+    assert_eq!(read_line().await, "login \"secret\"\r\n");
+    console_write.write_all(b"OK login\n").await.unwrap();
+    assert_eq!(read_line().await, "devinfo productname\r\n");
+
+    // The query the bridge sends itself for a scene recall
+    console_write
+        .write_all(b"NOTIFY sscurrent_ex scene_a 5\n")
+        .await
+        .unwrap();
+    assert_eq!(read_line().await, "ssinfo_ex scene_a 5\r\n");
+
+    // A command converted from OSC
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    });
+    controller
+        .send_to(
+            &rosc::encoder::encode(&packet).unwrap(),
+            ("127.0.0.1", osc_in_port),
+        )
+        .await
+        .unwrap();
+    assert_eq!(read_line().await, "get MIXER:Current/InCh/Fader/On 0 0\r\n");
+}
+
+#[tokio::test]
+async fn test_send_console_command() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = config(
+        console.local_addr().unwrap().port(),
+        free_port(),
+        free_port(),
+    );
+    for (reply, wait) in [
+        ("OK ssrecall_ex scene_a 5", true),
+        ("ERROR ssrecall_ex InvalidArgument", true),
+        ("OK ssrecall_ex scene_a 5", false),
+    ] {
+        let sent = tokio::spawn({
+            let config = config.clone();
+            async move { send_console_command(&config, "ssrecall_ex scene_a 5", wait).await }
+        });
+        let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+        let (console_read, mut console_write) = console_stream.into_split();
+        let mut line = String::new();
+        timeout(TIMEOUT, BufReader::new(console_read).read_line(&mut line))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(line, "ssrecall_ex scene_a 5\n");
+
+        // Notifications before the reply are skipped
+        let _ = console_write
+            .write_all(format!("NOTIFY sscurrent_ex scene_a 5\n{}\n", reply).as_bytes())
+            .await;
+        let sent = timeout(TIMEOUT, sent).await.unwrap().unwrap().unwrap();
+        assert_eq!(sent.as_deref(), wait.then_some(reply));
+    }
+}
+
+#[tokio::test]
+async fn test_rcp_login_rejected() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = BridgeConfig {
+        rcp_password: "wrong".to_string(),
+        ..config(
+            console.local_addr().unwrap().port(),
+            free_port(),
+            free_port(),
+        )
+    };
+    let bridge = tokio::spawn(Bridge::connect(config, Box::new(|_, _| {})));
+    let (mut console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    let mut buf = [0u8; 64];
+    let _ = console_stream.read(&mut buf).await.unwrap();
+    console_stream
+        .write_all(b"ERROR login InvalidCredentials\n")
+        .await
+        .unwrap();
+
+    let error = timeout(TIMEOUT, bridge)
+        .await
+        .unwrap()
+        .unwrap()
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("rejected the login"));
+}
+
+#[tokio::test]
+async fn test_udp_osc_out_local_port() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let local_port = free_port();
+    let config = BridgeConfig {
+        udp_osc_out_local_port: local_port,
+        ..config(
+            console.local_addr().unwrap().port(),
+            controller.local_addr().unwrap().port(),
+            free_port(),
+        )
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+    let (mut console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+
+    console_stream
+        .write_all(b"NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1\n")
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let (_, from) = timeout(TIMEOUT, controller.recv_from(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(from.port(), local_port);
+}
+
+#[tokio::test]
+async fn test_osc_in_addr_that_cant_be_bound_is_skipped() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let config = BridgeConfig {
+        // A documentation-only address no host has
+        udp_osc_in_addrs: vec!["192.0.2.1".to_string(), "127.0.0.1".to_string()],
+        ..config(
+            console.local_addr().unwrap().port(),
+            free_port(),
+            osc_in_port,
+        )
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(1)],
+    });
+    let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    sender
+        .send_to(
+            &rosc::encoder::encode(&packet).unwrap(),
+            ("127.0.0.1", osc_in_port),
+        )
+        .await
+        .unwrap();
+
+    let mut lines = BufReader::new(console_stream).lines();
+    let line = timeout(TIMEOUT, lines.next_line()).await.unwrap().unwrap();
+    assert_eq!(line.unwrap(), "set MIXER:Current/InCh/Fader/On 0 0 1");
+}
+
+#[tokio::test]
+async fn test_bundle_written_to_console_in_order() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let config = config(
+        console.local_addr().unwrap().port(),
+        free_port(),
+        osc_in_port,
+    );
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+
+    let fader = |channel| {
+        OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+            args: vec![OscType::Int(channel), OscType::Int(0), OscType::Int(0)],
+        })
+    };
+    let bundle = OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content: (0..3).map(fader).collect(),
+    });
+    let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    sender
+        .send_to(
+            &rosc::encoder::encode(&bundle).unwrap(),
+            ("127.0.0.1", osc_in_port),
+        )
+        .await
+        .unwrap();
+
+    let mut lines = BufReader::new(console_stream).lines();
+    for channel in 0..3 {
+        let line = timeout(TIMEOUT, lines.next_line()).await.unwrap().unwrap();
+        assert_eq!(
+            line.unwrap(),
+            format!("set MIXER:Current/InCh/Fader/Level {} 0 0", channel)
+        );
+    }
+}
+
+#[test]
+fn test_split_osc_packet() {
+    let fader = |channel| {
+        OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+            args: vec![OscType::Int(channel), OscType::Int(0), OscType::Int(0)],
+        })
+    };
+    let size = |packet: &OscPacket| rosc::encoder::encode(packet).unwrap().len();
+    let bundle = OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content: (0..10).map(fader).collect(),
+    });
+
+    // A packet that fits is left alone
+    let (packets, oversized) = split_osc_packet(bundle.clone(), size(&bundle)).unwrap();
+    assert_eq!(packets, vec![bundle.clone()]);
+    assert!(oversized.is_empty());
+
+    // A bundle is split into bundles that fit, in order
+    let (packets, oversized) = split_osc_packet(bundle.clone(), 200).unwrap();
+    assert!(packets.len() > 1);
+    assert!(oversized.is_empty());
+    let mut content = Vec::new();
+    for packet in packets {
+        assert!(size(&packet) <= 200);
+        match packet {
+            OscPacket::Bundle(split) => {
+                assert_eq!(split.timetag, OscTime::from((0, 1)));
+                content.extend(split.content);
+            }
+            OscPacket::Message(_) => panic!("expected a bundle"),
+        }
+    }
+    assert_eq!(content, (0..10).map(fader).collect::<Vec<_>>());
+
+    // A message that can't fit is returned separately
+    let label = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+        args: vec![OscType::String("x".repeat(300))],
+    };
+    let bundle = OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content: vec![fader(0), OscPacket::Message(label.clone()), fader(1)],
+    });
+    let (packets, oversized) = split_osc_packet(bundle, 200).unwrap();
+    assert_eq!(oversized, vec![label]);
+    assert_eq!(packets.len(), 2);
+}
+
+#[tokio::test]
+async fn test_oversized_udp_message_is_dropped() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = BridgeConfig {
+        max_udp_size: 200,
+        ..config(
+            console.local_addr().unwrap().port(),
+            controller.local_addr().unwrap().port(),
+            free_port(),
+        )
+    };
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(run_bridge_with_logger(
+        config,
+        Box::new(move |level, msg| {
+            let _ = log_tx.send((level, msg));
+        }),
+    ));
+    let (mut console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+
+    let label = "x".repeat(300);
+    console_stream
+        .write_all(
+            format!(
+                "NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"{}\"\n\
+                 NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1\n",
+                label
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+
+    // Only the message that fits arrives
+    let mut buf = [0u8; 1024];
+    let len = timeout(TIMEOUT, controller.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..len]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/On");
+
+    let warning = loop {
+        let (level, msg) = timeout(TIMEOUT, log_rx.recv()).await.unwrap().unwrap();
+        if level == LogLevel::Warn {
+            break msg;
+        }
+    };
+    assert!(warning.contains("/set/MIXER:Current/InCh/Label/Name"));
+}
+
+#[tokio::test]
+async fn test_osc_in_filter_never_writes_denied() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let config = BridgeConfig {
+        osc_in_filter: AddressFilter {
+            allow: Vec::new(),
+            deny: vec!["/ssstore_ex/*/*".to_string()],
+        },
+        ..config(
+            console.local_addr().unwrap().port(),
+            free_port(),
+            osc_in_port,
+        )
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+
+    let store = OscPacket::Message(OscMessage {
+        addr: "/ssstore_ex/MIXER:Lib/Scene".to_string(),
+        args: vec![OscType::Int(1)],
+    });
+    let fader = OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(0)],
+    });
+    let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for packet in [
+        store.clone(),
+        OscPacket::Bundle(OscBundle {
+            timetag: OscTime::from((0, 1)),
+            content: vec![store, fader],
+        }),
+    ] {
+        sender
+            .send_to(
+                &rosc::encoder::encode(&packet).unwrap(),
+                ("127.0.0.1", osc_in_port),
+            )
+            .await
+            .unwrap();
+    }
+
+    // Only the allowed command from the bundle reaches the console
+    let mut lines = BufReader::new(console_stream).lines();
+    let line = timeout(TIMEOUT, lines.next_line()).await.unwrap().unwrap();
+    assert_eq!(line.unwrap(), "set MIXER:Current/InCh/Fader/Level 0 0 0");
+}
+
+#[tokio::test]
+async fn test_query_awaits_matching_reply() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = config(
+        console.local_addr().unwrap().port(),
+        free_port(),
+        free_port(),
+    );
+
+    let bridge = Bridge::connect(config, Box::new(|_, _| {})).await.unwrap();
+    tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    let (console_read, mut console_write) = console_stream.into_split();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(console_read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            // An unrelated NOTIFY and a reply to a different command arrive
+            // before the actual reply
+            let reply = match line.as_str() {
+                "get MIXER:Current/Channel/Fader/Level 0 0" => concat!(
+                    "NOTIFY set MIXER:Current/Channel/Fader/Level 1 0 -500\n",
+                    "OK get MIXER:Current/Channel/Fader/Level 1 0 -500\n",
+                    "OK get MIXER:Current/Channel/Fader/Level 0 0 -1000\n",
+                ),
+                _ => "ERROR get UnknownAddress\n",
+            };
+            console_write.write_all(reply.as_bytes()).await.unwrap();
+        }
+    });
+
+    let reply = bridge
+        .query("get MIXER:Current/Channel/Fader/Level 0 0")
+        .await
+        .unwrap();
+    assert_eq!(reply.kind, RcpMessageKind::Ok);
+    assert_eq!(
+        reply.to_string(),
+        "OK get MIXER:Current/Channel/Fader/Level 0 0 -1000"
+    );
+
+    let reply = bridge.query("get MIXER:Nonexistent 0 0").await.unwrap();
+    assert_eq!(reply.kind, RcpMessageKind::Error);
+    assert_eq!(reply.args, vec!["get", "UnknownAddress"]);
+}
+
+#[tokio::test]
+async fn test_mock_query_times_out() {
+    let (bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        response_timeout_ms: 100,
+        ..base_config()
+    });
+
+    // The console never replies
+    let error = bridge
+        .query("get MIXER:Current/InCh/Fader/Level 0 0")
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QueryError::Timeout(timeout) if timeout == Duration::from_millis(100)));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp())
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("get MIXER:Current/InCh/Fader/Level 0 0")
+    );
+
+    // The timed-out query is forgotten, so a late reply only goes out over
+    // OSC, and the next query gets its own
+    handle.send_rcp("OK get MIXER:Current/InCh/Fader/Level 0 0 -1000");
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(message(packet).addr, "/get/MIXER:Current/InCh/Fader/Level");
+    let query = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.query("get MIXER:Current/InCh/Fader/Level 0 0").await }
+    });
+    timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    handle.send_rcp("OK get MIXER:Current/InCh/Fader/Level 0 0 -500");
+    let reply = timeout(TIMEOUT, query).await.unwrap().unwrap().unwrap();
+    assert_eq!(
+        reply.to_string(),
+        "OK get MIXER:Current/InCh/Fader/Level 0 0 -500"
+    );
+}
+
+#[tokio::test]
+async fn test_dump_trigger_writes_dump_commands() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let config = BridgeConfig {
+        dump_commands: vec![
+            "get MIXER:Current/Channel/Fader/Level 0 0".to_string(),
+            "get MIXER:Current/Channel/Fader/On 0 0".to_string(),
+        ],
+        ..config(
+            console.local_addr().unwrap().port(),
+            free_port(),
+            osc_in_port,
+        )
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+
+    let (console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    let mut lines = BufReader::new(console_stream).lines();
+
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/bridge/dump".to_string(),
+        args: vec![],
+    });
+    controller
+        .send_to(
+            &rosc::encoder::encode(&packet).unwrap(),
+            ("127.0.0.1", osc_in_port),
+        )
+        .await
+        .unwrap();
+
+    for expected in [
+        "get MIXER:Current/Channel/Fader/Level 0 0",
+        "get MIXER:Current/Channel/Fader/On 0 0",
+    ] {
+        let line = timeout(TIMEOUT, lines.next_line()).await.unwrap().unwrap();
+        assert_eq!(line.as_deref(), Some(expected));
+    }
+}
+
+#[tokio::test]
+async fn test_mock_rcp_to_osc() {
+    let (_bridge, mut handle) = run_mock_bridge(base_config());
+
+    handle.send_rcp("NOTIFY set MIXER:Current/Channel/Fader/Level 0 0 -1000");
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/set/MIXER:Current/Channel/Fader/Level");
+    assert_eq!(
+        msg.args,
+        vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)]
+    );
+}
+
+#[tokio::test]
+async fn test_mock_echo_raw() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        echo_raw: true,
+        ..base_config()
+    });
+
+    handle.send_rcp("NOTIFY set  MIXER:Current/InCh/Fader/On 0 0 1");
+    let raw = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(raw.addr, "/bridge/raw");
+    assert_eq!(
+        raw.args,
+        vec![OscType::String(
+            "NOTIFY set  MIXER:Current/InCh/Fader/On 0 0 1".to_string()
+        )]
+    );
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/On");
+
+    // Lines that can't be converted are echoed too
+    handle.send_rcp("ERROR set InvalidArgument");
+    let raw = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(
+        raw.args,
+        vec![OscType::String("ERROR set InvalidArgument".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_mock_osc_to_rcp() {
+    let (_bridge, mut handle) = run_mock_bridge(base_config());
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/Channel/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    }));
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(
+        line.as_deref(),
+        Some("set MIXER:Current/Channel/Fader/Level 0 0 -1000")
+    );
+}
+
+#[tokio::test]
+async fn test_mock_honor_timetags() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        honor_timetags: true,
+        ..base_config()
+    });
+    let fader = |channel| {
+        OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+            args: vec![OscType::Int(channel), OscType::Int(0), OscType::Int(0)],
+        })
+    };
+
+    let delay = Duration::from_millis(300);
+    let scheduled = std::time::Instant::now();
+    handle.send_osc(OscPacket::Bundle(OscBundle {
+        timetag: OscTime::try_from(std::time::SystemTime::now() + delay).unwrap(),
+        content: vec![fader(1)],
+    }));
+    // An immediate bundle is written straight away, ahead of the scheduled one
+    handle.send_osc(OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content: vec![fader(2)],
+    }));
+
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(
+        line.as_deref(),
+        Some("set MIXER:Current/InCh/Fader/Level 2 0 0")
+    );
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(
+        line.as_deref(),
+        Some("set MIXER:Current/InCh/Fader/Level 1 0 0")
+    );
+    assert!(scheduled.elapsed() >= delay - Duration::from_millis(20));
+}
+
+#[tokio::test]
+async fn test_mock_ping_is_answered_without_console() {
+    let (_bridge, mut handle) = run_mock_bridge(base_config());
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/bridge/ping".to_string(),
+        args: vec![OscType::Int(42)],
+    }));
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(
+        message(packet),
+        OscMessage {
+            addr: "/bridge/pong".to_string(),
+            args: vec![OscType::Int(42)],
+        }
+    );
+
+    // Nothing reaches the console
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "get MIXER:Current/InCh/Fader/On 0 0"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_status_query_is_answered_without_console() {
+    let (_bridge, mut handle) = run_mock_bridge(base_config());
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/bridge/status/get".to_string(),
+        args: vec![],
+    }));
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    let reply = message(packet);
+    assert_eq!(reply.addr, "/bridge/status/reply");
+    // Connected, a few seconds at most of uptime and no reconnects yet
+    assert_eq!(reply.args[0], OscType::Int(1));
+    assert!(matches!(reply.args[1], OscType::Int(0..=5)));
+    assert_eq!(reply.args[2], OscType::Int(0));
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "get MIXER:Current/InCh/Fader/On 0 0"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_panic_writes_panic_commands() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        panic_commands: vec![
+            "set MIXER:Current/InCh/Fader/On 0 0 0".to_string(),
+            "ssrecall_ex scene_a 1".to_string(),
+        ],
+        ..base_config()
+    });
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/bridge/panic".to_string(),
+        args: vec![],
+    }));
+    for expected in [
+        "set MIXER:Current/InCh/Fader/On 0 0 0",
+        "ssrecall_ex scene_a 1",
+    ] {
+        let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+        assert_eq!(line.as_deref(), Some(expected));
+    }
+
+    // The trigger itself isn't passed on to the console
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "get MIXER:Current/InCh/Fader/On 0 0"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_triggers_in_bundles() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        panic_commands: vec!["set MIXER:Current/InCh/Fader/On 0 0 0".to_string()],
+        ..base_config()
+    });
+
+    let msg = |addr: &str, args| {
+        OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args,
+        })
+    };
+    let get = msg(
+        "/get/MIXER:Current/InCh/Fader/On",
+        vec![OscType::Int(0), OscType::Int(0)],
+    );
+    handle.send_osc(OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content: vec![
+            get,
+            OscPacket::Bundle(OscBundle {
+                timetag: OscTime::from((0, 1)),
+                content: vec![
+                    msg("/bridge/panic", vec![]),
+                    msg("/bridge/ping", vec![OscType::Int(7)]),
+                ],
+            }),
+        ],
+    }));
+
+    // The triggers are handled rather than written to the console as
+    // commands, and the rest of the bundle is converted as usual
+    for expected in [
+        "set MIXER:Current/InCh/Fader/On 0 0 0",
+        "get MIXER:Current/InCh/Fader/On 0 0",
+    ] {
+        let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+        assert_eq!(line.as_deref(), Some(expected));
+    }
+    let pong = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(pong.addr, "/bridge/pong");
+    assert_eq!(pong.args, vec![OscType::Int(7)]);
+
+    // A bundle of nothing but triggers writes nothing else
+    handle.send_osc(OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content: vec![msg("/bridge/panic", vec![])],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "set MIXER:Current/InCh/Fader/On 0 0 0"
+    );
+    handle.send_osc(msg(
+        "/get/MIXER:Current/InCh/Fader/Level",
+        vec![OscType::Int(0), OscType::Int(0)],
+    ));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "get MIXER:Current/InCh/Fader/Level 0 0"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_sequence_numbers() {
+    let mut config = base_config();
+    config.conversion.sequence_numbers = true;
+    config.conversion.source_tag = "foh".to_string();
+    let (_bridge, mut handle) = run_mock_bridge(config);
+
+    for (level, sequence) in [(-1000, 0), (-500, 1)] {
+        handle.send_rcp(&format!(
+            "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 {}",
+            level
+        ));
+        let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+        assert_eq!(
+            message(packet).args,
+            vec![
+                OscType::Int(0),
+                OscType::Int(0),
+                OscType::Int(level),
+                OscType::String("foh".to_string()),
+                OscType::Long(sequence),
+            ]
+        );
+    }
+
+    // Sent back unchanged, neither tag reaches the console
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![
+            OscType::Int(0),
+            OscType::Int(0),
+            OscType::Int(-500),
+            OscType::String("foh".to_string()),
+            OscType::Long(1),
+        ],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -500"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_dedup_skips_repeated_notify() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        dedup: true,
+        ..base_config()
+    });
+
+    for line in [
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+        "NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1",
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -500",
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+    ] {
+        handle.send_rcp(line);
+    }
+
+    // Only exact repeats on the same address are dropped
+    let mut received = Vec::new();
+    for _ in 0..4 {
+        let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+        received.push((msg.addr, msg.args[2].clone()));
+    }
+    let fader = "/set/MIXER:Current/InCh/Fader/Level".to_string();
+    assert_eq!(
+        received,
+        vec![
+            (fader.clone(), OscType::Int(-1000)),
+            (
+                "/set/MIXER:Current/InCh/Fader/On".to_string(),
+                OscType::Int(1)
+            ),
+            (fader.clone(), OscType::Int(-500)),
+            (fader, OscType::Int(-1000)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_mock_ramp_steps_toward_target() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        ramp_ms: 200,
+        ramp_addresses: vec!["/set/MIXER:Current/*/Fader/Level".to_string()],
+        ..base_config()
+    });
+    let fader = |value| {
+        OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+            args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(value)],
+        })
+    };
+    let level = |line: String| {
+        line.strip_prefix("set MIXER:Current/InCh/Fader/Level 0 0 ")
+            .unwrap()
+            .parse::<i32>()
+            .unwrap()
+    };
+
+    // The console's current value is where the ramp starts
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    handle.send_osc(fader(0));
+
+    let mut steps = Vec::new();
+    loop {
+        let value = level(timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap());
+        steps.push(value);
+        if value == 0 {
+            break;
+        }
+    }
+    assert!(steps.len() > 2, "expected intermediate steps: {:?}", steps);
+    assert!(steps[0] > -1000);
+    assert!(steps.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // An unramped parameter is written directly
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(1)],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "set MIXER:Current/InCh/Fader/On 0 0 1"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_ramp_retargets_mid_ramp() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        ramp_ms: 400,
+        ramp_addresses: vec!["/set/MIXER:Current/*/Fader/Level".to_string()],
+        ..base_config()
+    });
+    let fader = |value| {
+        OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+            args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(value)],
+        })
+    };
+
+    // Nothing to ramp from yet, so the first value is written as is
+    handle.send_osc(fader(0));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 0"
+    );
+
+    handle.send_osc(fader(1000));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    handle.send_osc(fader(-1000));
+
+    let mut last = String::new();
+    while !last.ends_with(" -1000") {
+        last = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap();
+        assert!(!last.ends_with(" 1000"), "overshot to the old target");
+    }
+}
+
+#[tokio::test]
+async fn test_mock_osc_out_filter() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        osc_out_filter: AddressFilter {
+            allow: vec!["/set/MIXER:Current/*/Fader/*".to_string()],
+            deny: vec!["/set/MIXER:Current/StInCh/*/*".to_string()],
+        },
+        ..base_config()
+    });
+
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"Vox\"");
+    handle.send_rcp("NOTIFY set MIXER:Current/StInCh/Fader/Level 0 0 -1000");
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+
+    // Only the allowed, undenied message gets through
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/Level");
+}
+
+#[tokio::test]
+async fn test_mock_deadband() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        deadbands: vec![Deadband {
+            address: "/set/MIXER:Current/*/Fader/Level".to_string(),
+            threshold: 10.0,
+        }],
+        ..base_config()
+    });
+    let fader = |channel: i32, value: f32| {
+        OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+            args: vec![
+                OscType::Int(channel),
+                OscType::Int(0),
+                OscType::Float(value),
+            ],
+        })
+    };
+
+    handle.send_osc(fader(0, -1000.0));
+    // Within 10 of the last value written, so both are dropped
+    handle.send_osc(fader(0, -995.5));
+    handle.send_osc(fader(0, -1010.0));
+    // Past the threshold, and another channel with a deadband of its own
+    handle.send_osc(fader(0, -980.0));
+    handle.send_osc(fader(1, -985.0));
+    for expected in [
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000.0",
+        "set MIXER:Current/InCh/Fader/Level 0 0 -980.0",
+        "set MIXER:Current/InCh/Fader/Level 1 0 -985.0",
+    ] {
+        assert_eq!(
+            timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+            expected
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_mock_raw_rcp() {
+    let raw = |command: &str| {
+        OscPacket::Message(OscMessage {
+            addr: "/bridge/raw".to_string(),
+            args: vec![OscType::String(command.to_string())],
+        })
+    };
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        allow_raw: true,
+        ..base_config()
+    });
+
+    // Written exactly as sent, quotes and all
+    handle.send_osc(raw("set MIXER:Current/InCh/Label/Name 0 0 \"Vox 1\""));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "set MIXER:Current/InCh/Label/Name 0 0 \"Vox 1\""
+    );
+
+    // More than one line is refused
+    handle.send_osc(raw("ssrecall_ex scene_a 5\nssrecall_ex scene_a 6"));
+    handle.send_osc(raw("ssrecall_ex scene_a 7"));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "ssrecall_ex scene_a 7"
+    );
+
+    // Without --allow-raw it is ignored rather than converted
+    let (_bridge, mut handle) = run_mock_bridge(base_config());
+    handle.send_osc(raw("ssrecall_ex scene_a 5"));
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "get MIXER:Current/InCh/Fader/Level 0 0"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_forward_types() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        forward_types: vec![RcpMessageKind::Notify, RcpMessageKind::Error],
+        ..base_config()
+    });
+
+    handle.send_rcp("OK set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 1 -1000");
+
+    // The OK confirmation is dropped, so the NOTIFY is the first to arrive
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/Level");
+    assert_eq!(
+        msg.args,
+        vec![OscType::Int(0), OscType::Int(1), OscType::Int(-1000)]
+    );
+}
+
+#[tokio::test]
+async fn test_mock_stats() {
+    let (bridge, mut handle) = run_mock_bridge(base_config());
+    let stats = bridge.stats();
+
+    let notify = "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000";
+    handle.send_rcp(notify);
+    handle.send_rcp("BOGUS line");
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    }));
+    let command = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap();
+    // The counters are updated once the write returns
+    timeout(TIMEOUT, async {
+        while stats.osc_to_rcp() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(stats.rcp_to_osc(), 1);
+    assert_eq!(stats.osc_to_rcp(), 1);
+    assert_eq!(stats.conversion_errors(), 1);
+    assert_eq!(stats.reconnects(), 0);
+    assert_eq!(
+        stats.bytes_read(),
+        (notify.len() + "BOGUS line".len()) as u64
+    );
+    assert_eq!(stats.bytes_written(), command.len() as u64);
+    assert!(stats.connected());
+}
+
+#[tokio::test]
+async fn test_mock_subscribers_receive_converted_osc() {
+    let (bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        osc_out_filter: AddressFilter {
+            allow: Vec::new(),
+            deny: vec!["/set/MIXER:Current/InCh/Label/*".to_string()],
+        },
+        ..base_config()
+    });
+    let mut first = bridge.subscribe();
+    let mut second = bridge.subscribe();
+
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"Vox\"");
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+
+    // Every subscriber gets the filtered messages, as does the transport
+    for subscriber in [&mut first, &mut second] {
+        let msg = message(timeout(TIMEOUT, subscriber.recv()).await.unwrap().unwrap());
+        assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/Level");
+    }
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/Level");
+}
+
+#[tokio::test]
+async fn test_mock_banner_on_connect_is_not_an_error() {
+    let (transport, mut handle) = MockTransport::pair();
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    let bridge = Bridge::new(
+        base_config(),
+        transport,
+        Box::new(move |level, msg| {
+            let _ = log_tx.send((level, msg));
+        }),
+    );
+    tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    handle.send_rcp("Welcome to the Yamaha RCP server");
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1");
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/On");
+
+    bridge.shutdown();
+    while let Ok(Some((level, msg))) = timeout(TIMEOUT, log_rx.recv()).await {
+        assert!(level < LogLevel::Error, "unexpected error: {}", msg);
+    }
+}
+
+#[tokio::test]
+async fn test_mock_reload() {
+    let (transport, mut handle) = MockTransport::pair();
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    let config = BridgeConfig {
+        osc_out_filter: AddressFilter {
+            allow: Vec::new(),
+            deny: vec!["/set/MIXER:Current/InCh/Label/*".to_string()],
+        },
+        ..base_config()
+    };
+    let bridge = Bridge::new(
+        config.clone(),
+        transport,
+        Box::new(move |_, msg| {
+            let _ = log_tx.send(msg);
+        }),
+    );
+    tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    // The filter is lifted and the options change while connected
+    bridge.reload(BridgeConfig {
+        osc_out_filter: AddressFilter::default(),
+        conversion: ConversionOptions {
+            unquote_strings: true,
+            ..Default::default()
+        },
+        ..config
+    });
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"Vox\"");
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Label/Name");
+    assert_eq!(msg.args[2], OscType::String("Vox".to_string()));
+
+    let summary = async {
+        loop {
+            let msg = log_rx.recv().await.unwrap();
+            if msg.starts_with("Reloaded") {
+                return msg;
+            }
+        }
+    };
+    assert_eq!(
+        timeout(TIMEOUT, summary).await.unwrap(),
+        "Reloaded the config, changed: conversion.unquote_strings, osc_out_filter.deny"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_sscurrent_ex_workaround() {
+    let (_bridge, mut handle) = run_mock_bridge(base_config());
+
+    handle.send_rcp("NOTIFY sscurrent_ex scene_a 5");
+
+    // The notification is forwarded, and the scene detail is queried
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/sscurrent_ex/scene_a");
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(line.as_deref(), Some("ssinfo_ex scene_a 5"));
+}
+
+#[tokio::test]
+async fn test_mock_osc_bundle_to_rcp() {
+    let (_bridge, mut handle) = run_mock_bridge(base_config());
+
+    handle.send_osc(OscPacket::Bundle(rosc::OscBundle {
+        timetag: (0, 1).into(),
+        content: vec![
+            OscPacket::Message(OscMessage {
+                addr: "/get/MIXER:Current/Channel/Fader/Level".to_string(),
+                args: vec![OscType::Int(0), OscType::Int(0)],
+            }),
+            OscPacket::Message(OscMessage {
+                addr: "/get/MIXER:Current/Channel/Fader/On".to_string(),
+                args: vec![OscType::Int(0), OscType::Int(0)],
+            }),
+        ],
+    }));
+    for expected in [
+        "get MIXER:Current/Channel/Fader/Level 0 0",
+        "get MIXER:Current/Channel/Fader/On 0 0",
+    ] {
+        let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+        assert_eq!(line.as_deref(), Some(expected));
+    }
+}
+
+#[tokio::test]
+async fn test_mock_reconnect_restores_session() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        init_commands: vec![
+            "scpmode sstype \"text\"".to_string(),
+            "get MIXER:Current/InCh/Fader/Level 0 0".to_string(),
+        ],
+        status_address: "/bridge/status".to_string(),
+        reconnect: true,
+        reconnect_delay_ms: 10,
+        ..base_config()
+    });
+    let status = |connected| OscMessage {
+        addr: "/bridge/status".to_string(),
+        args: vec![OscType::Int(connected)],
+    };
+
+    for _ in 0..2 {
+        assert_eq!(
+            timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+            "scpmode sstype \"text\""
+        );
+        assert_eq!(
+            timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+            "get MIXER:Current/InCh/Fader/Level 0 0"
+        );
+        let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+        assert_eq!(message(packet), status(1));
+
+        // Simulate the console dropping the connection and coming back
+        handle.close_rcp();
+        let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+        assert_eq!(message(packet), status(0));
+        handle.reopen_rcp();
+    }
+
+    // Lines on the new connection are still bridged
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(message(packet), status(1));
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(message(packet).addr, "/set/MIXER:Current/InCh/Fader/Level");
+}
+
+#[tokio::test]
+async fn test_mock_reconnect_restores_subscriptions() {
+    let mut meters = BridgeConfig {
+        status_address: "/bridge/status".to_string(),
+        reconnect: true,
+        reconnect_delay_ms: 10,
+        ..base_config()
+    };
+    meters.conversion.verb_mappings = vec![
+        VerbMapping {
+            osc: "subscribe".to_string(),
+            rcp: "mtrstart".to_string(),
+        },
+        VerbMapping {
+            osc: "unsubscribe".to_string(),
+            rcp: "mtrstop".to_string(),
+        },
+    ];
+    let (_bridge, mut handle) = run_mock_bridge(meters);
+    let osc = |addr: &str, args| {
+        OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args,
+        })
+    };
+    let reconnect = async |handle: &mut MockHandle| {
+        handle.close_rcp();
+        timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+        handle.reopen_rcp();
+    };
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+
+    for (addr, interval) in [
+        ("/subscribe/MIXER:Current/Meter/InCh", 100),
+        ("/subscribe/MIXER:Current/Meter/Mix", 100),
+        // Restarting a meter replaces its interval
+        ("/subscribe/MIXER:Current/Meter/InCh", 50),
+    ] {
+        handle.send_osc(osc(addr, vec![OscType::Int(interval)]));
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap();
+    }
+    reconnect(&mut handle).await;
+    for expected in [
+        "mtrstart MIXER:Current/Meter/Mix 100",
+        "mtrstart MIXER:Current/Meter/InCh 50",
+    ] {
+        let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+        assert_eq!(line.as_deref(), Some(expected));
+    }
+
+    // A stopped meter isn't started again
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    handle.send_osc(osc("/unsubscribe/MIXER:Current/Meter/Mix", vec![]));
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(line.as_deref(), Some("mtrstop MIXER:Current/Meter/Mix"));
+    reconnect(&mut handle).await;
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(
+        line.as_deref(),
+        Some("mtrstart MIXER:Current/Meter/InCh 50")
+    );
+    handle.send_osc(osc("/get/MIXER:Current/InCh/Fader/Level", vec![]));
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(line.as_deref(), Some("get MIXER:Current/InCh/Fader/Level"));
+}
+
+#[tokio::test]
+async fn test_mock_reconnect_restores_rcp_subscribe() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        status_address: "/bridge/status".to_string(),
+        reconnect: true,
+        reconnect_delay_ms: 10,
+        ..base_config()
+    });
+    let reconnect = async |handle: &mut MockHandle| {
+        handle.close_rcp();
+        timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+        handle.reopen_rcp();
+    };
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+
+    // Quoted names with spaces in them are told apart
+    for (name, interval) in [("Lead Vox", 100), ("Lead Guitar", 100), ("Lead Vox", 50)] {
+        handle.send_osc(OscPacket::Message(OscMessage {
+            addr: "/subscribe".to_string(),
+            args: vec![OscType::String(name.to_string()), OscType::Int(interval)],
+        }));
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap();
+    }
+    reconnect(&mut handle).await;
+    for expected in ["subscribe \"Lead Guitar\" 100", "subscribe \"Lead Vox\" 50"] {
+        let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+        assert_eq!(line.as_deref(), Some(expected));
+    }
+
+    // Unsubscribing keeps its arguments, and isn't restored
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/unsubscribe".to_string(),
+        args: vec![OscType::String("Lead Vox".to_string()), OscType::Int(1)],
+    }));
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(line.as_deref(), Some("unsubscribe \"Lead Vox\" 1"));
+    reconnect(&mut handle).await;
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(line.as_deref(), Some("subscribe \"Lead Guitar\" 100"));
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![],
+    }));
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(line.as_deref(), Some("get MIXER:Current/InCh/Fader/Level"));
+}
+
+#[tokio::test]
+async fn test_mock_reconnect_queue() {
+    let (transport, mut handle) = MockTransport::pair();
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    let bridge = Bridge::new(
+        BridgeConfig {
+            status_address: "/bridge/status".to_string(),
+            reconnect: true,
+            reconnect_delay_ms: 10,
+            reconnect_queue_size: 2,
+            ..base_config()
+        },
+        transport,
+        Box::new(move |_, msg| {
+            let _ = log_tx.send(msg);
+        }),
+    );
+    tokio::spawn(async move { bridge.run().await });
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    handle.close_rcp();
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+
+    // Sent while disconnected; the queue only has room for the last two
+    for channel in 0..3 {
+        handle.send_osc(OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/On".to_string(),
+            args: vec![OscType::Int(channel), OscType::Int(0), OscType::Int(0)],
+        }));
+    }
+    let mut queued = 0;
+    while queued < 3 {
+        let msg = timeout(TIMEOUT, log_rx.recv()).await.unwrap().unwrap();
+        if msg.starts_with("Queueing RCP") {
+            queued += 1;
+        }
+    }
+
+    handle.reopen_rcp();
+    for expected in [
+        "set MIXER:Current/InCh/Fader/On 1 0 0",
+        "set MIXER:Current/InCh/Fader/On 2 0 0",
+    ] {
+        let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+        assert_eq!(line.as_deref(), Some(expected));
+    }
+
+    // Once reconnected, commands are written straight away again
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0)],
+    }));
+    let line = timeout(TIMEOUT, handle.recv_rcp()).await.unwrap();
+    assert_eq!(line.as_deref(), Some("get MIXER:Current/InCh/Fader/On 0 0"));
+}
+
+#[tokio::test]
+async fn test_mock_reconnect_gives_up_after_max_attempts() {
+    let (transport, mut handle) = MockTransport::pair();
+    let bridge = Bridge::new(
+        BridgeConfig {
+            reconnect: true,
+            reconnect_delay_ms: 10,
+            reconnect_jitter: 0.5,
+            reconnect_max_attempts: 3,
+            ..base_config()
+        },
+        transport,
+        Box::new(|_, _| {}),
+    );
+    let task = tokio::spawn(async move { bridge.run().await });
+
+    // With the handle gone, every reconnect attempt is refused
+    handle.close_rcp();
+    drop(handle);
+    let error = timeout(TIMEOUT, task).await.unwrap().unwrap().unwrap_err();
+    assert!(error.to_string().contains("after 3 attempts"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_mock_idle_reconnect() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        status_address: "/bridge/status".to_string(),
+        idle_reconnect_secs: 1,
+        reconnect_delay_ms: 10,
+        ..base_config()
+    });
+    let status = |connected| OscMessage {
+        addr: "/bridge/status".to_string(),
+        args: vec![OscType::Int(connected)],
+    };
+
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(message(packet), status(1));
+
+    // Traffic keeps the connection alive past the idle timeout
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+        let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+        assert_eq!(message(packet).addr, "/set/MIXER:Current/InCh/Fader/Level");
+    }
+
+    // Silence forces a reconnect, even though reconnect is off
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(message(packet), status(0));
+    handle.reopen_rcp();
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(message(packet), status(1));
+}
+
+#[tokio::test]
+async fn test_mock_shutdown_stops_run() {
+    let (transport, mut handle) = MockTransport::pair();
+    let bridge = Bridge::new(
+        BridgeConfig {
+            status_address: "/bridge/status".to_string(),
+            reconnect: true,
+            ..base_config()
+        },
+        transport,
+        Box::new(|_, _| {}),
+    );
+    let run = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(message(packet).args, vec![OscType::Int(1)]);
+
+    bridge.shutdown();
+    timeout(TIMEOUT, run).await.unwrap().unwrap().unwrap();
+
+    // The final status is announced, and OSC is no longer bridged
+    let packet = timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    assert_eq!(message(packet).args, vec![OscType::Int(0)]);
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    }));
+    drop(bridge);
+    assert_eq!(timeout(TIMEOUT, handle.recv_rcp()).await.unwrap(), None);
+}
+
+#[test]
+fn test_config_from_partial_json() {
+    let config: BridgeConfig = serde_json::from_str(
+        r#"{
+            "console_ip": "192.168.0.128",
+            "conversion": {
+                "type_coercions": [{ "address": "/set/*", "type": "int" }]
+            }
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.console_ip, "192.168.0.128");
+    assert_eq!(config.rcp_port, BridgeConfig::default().rcp_port);
+    assert_eq!(config.dump_address, "/bridge/dump");
+    assert_eq!(config.conversion.type_coercions.len(), 1);
+}
+
+#[tokio::test]
+async fn test_mock_record_rcp_lines() {
+    let path = std::env::temp_dir().join(format!("rcp-record-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        record_path: path.to_str().unwrap().to_string(),
+        ..base_config()
+    });
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1");
+    for _ in 0..2 {
+        timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    }
+    bridge.shutdown();
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<(u64, &str)> = recorded
+        .lines()
+        .map(|line| {
+            let (millis, line) = line.split_once(' ').unwrap();
+            (millis.parse().unwrap(), line)
+        })
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0].1,
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    assert_eq!(lines[1].1, "NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1");
+    assert!(lines[0].0 <= lines[1].0);
+}
+
+#[tokio::test]
+async fn test_mock_log_csv() {
+    let path = std::env::temp_dir().join(format!("bridge-log-{}.csv", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (transport, mut handle) = MockTransport::pair();
+    let bridge = Bridge::new(
+        BridgeConfig {
+            csv_log_path: path.to_str().unwrap().to_string(),
+            ..base_config()
+        },
+        transport,
+        Box::new(|_, _| {}),
+    );
+    let run = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"Vox, lead\"");
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    }));
+    timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap();
+
+    // The rows are all written out by the time the bridge stops
+    bridge.shutdown();
+    timeout(TIMEOUT, run).await.unwrap().unwrap().unwrap();
+    let logged = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let lines: Vec<&str> = logged.lines().collect();
+    assert_eq!(lines[0], "timestamp,direction,rcp,osc_address,osc_args");
+    let (timestamp, row) = lines[1].split_once(',').unwrap();
+    assert!(timestamp.ends_with('Z') && timestamp.contains('T'));
+    assert_eq!(
+        row,
+        "rcp->osc,\"NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"\"Vox, lead\"\"\",\
+         /set/MIXER:Current/InCh/Label/Name,\"0 0 \"\"Vox, lead\"\"\""
+    );
+    let (_, row) = lines[2].split_once(',').unwrap();
+    assert_eq!(
+        row,
+        "osc->rcp,set MIXER:Current/InCh/Fader/Level 0 0 -1000,\
+         /set/MIXER:Current/InCh/Fader/Level,0 0 -1000"
+    );
+    assert_eq!(lines.len(), 3);
+}
+
+#[tokio::test]
+async fn test_replay_recording() {
+    let path = std::env::temp_dir().join(format!("rcp-replay-{}.log", std::process::id()));
+    std::fs::write(
+        &path,
+        "1718000000000 NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000\n\
+         1718000000200 NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -500\n",
+    )
+    .unwrap();
+
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = config(0, controller.local_addr().unwrap().port(), free_port());
+    let bridge = Bridge::replay(config, &path, Box::new(|_, _| {}))
+        .await
+        .unwrap();
+    let started = std::time::Instant::now();
+    let run = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    let mut buf = [0u8; 1024];
+    for level in [-1000, -500] {
+        let size = timeout(TIMEOUT, controller.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        let msg = message(rosc::decoder::decode_udp(&buf[..size]).unwrap().1);
+        assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/Level");
+        assert_eq!(msg.args[2], OscType::Int(level));
+    }
+    // The recorded gap between the lines is kept
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    // The replay ends with the file
+    timeout(TIMEOUT, run).await.unwrap().unwrap().unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_replay_speed() {
+    let path = std::env::temp_dir().join(format!("rcp-replay-speed-{}.log", std::process::id()));
+    std::fs::write(
+        &path,
+        "1718000000000 NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000\n\
+         1718000001000 NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -500\n",
+    )
+    .unwrap();
+
+    // Four times as fast, then ignoring the timing altogether
+    for (speed, no_timing, min_ms) in [(4.0, false, 250), (1.0, true, 0)] {
+        let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = BridgeConfig {
+            replay_speed: speed,
+            replay_no_timing: no_timing,
+            ..config(0, controller.local_addr().unwrap().port(), free_port())
+        };
+        let bridge = Bridge::replay(config, &path, Box::new(|_, _| {}))
+            .await
+            .unwrap();
+        let started = std::time::Instant::now();
+        let run = tokio::spawn({
+            let bridge = bridge.clone();
+            async move { bridge.run().await }
+        });
+
+        let mut buf = [0u8; 1024];
+        for _ in 0..2 {
+            timeout(TIMEOUT, controller.recv(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(min_ms));
+        assert!(elapsed < Duration::from_millis(1000));
+        timeout(TIMEOUT, run).await.unwrap().unwrap().unwrap();
+    }
+
+    let config = BridgeConfig {
+        replay_speed: 0.0,
+        ..config(0, free_port(), free_port())
+    };
+    assert!(
+        Bridge::replay(config, &path, Box::new(|_, _| {}))
+            .await
+            .is_err()
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_loopback() {
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let bridge = Bridge::loopback(
+        config(0, controller.local_addr().unwrap().port(), osc_in_port),
+        Box::new(|_, _| {}),
+    )
+    .await
+    .unwrap();
+    let run = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    });
+    controller
+        .send_to(
+            &rosc::encoder::encode(&packet).unwrap(),
+            ("127.0.0.1", osc_in_port),
+        )
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let len = timeout(TIMEOUT, controller.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..len]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/Level");
+    assert_eq!(
+        msg.args,
+        vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)]
+    );
+
+    bridge.shutdown();
+    timeout(TIMEOUT, run).await.unwrap().unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_osc_feedback_loop() {
+    let port = free_port();
+    let looped = BridgeConfig {
+        udp_osc_in_addr: "0.0.0.0".to_string(),
+        ..config(0, port, port)
+    };
+    assert!(
+        osc_feedback_loop(&looped)
+            .unwrap()
+            .starts_with(&format!("The OSC out address 127.0.0.1:{} ", port))
+    );
+    for (out_addr, in_addr) in [("localhost", "0.0.0.0"), ("192.168.1.5", "192.168.1.5")] {
+        let looped = BridgeConfig {
+            udp_osc_out_addr: out_addr.to_string(),
+            udp_osc_in_addr: in_addr.to_string(),
+            ..config(0, port, port)
+        };
+        assert!(
+            osc_feedback_loop(&looped).is_some(),
+            "{} -> {}",
+            out_addr,
+            in_addr
+        );
+    }
+    let local_port = BridgeConfig {
+        udp_osc_out_local_port: port,
+        ..config(0, free_port(), port)
+    };
+    assert!(osc_feedback_loop(&local_port).is_some());
+
+    // Another host, or another port, is fine
+    let remote = BridgeConfig {
+        udp_osc_out_addr: "192.168.1.20".to_string(),
+        udp_osc_in_addr: "0.0.0.0".to_string(),
+        ..config(0, port, port)
+    };
+    assert_eq!(osc_feedback_loop(&remote), None);
+    assert_eq!(osc_feedback_loop(&config(0, free_port(), port)), None);
+
+    // Only a warning unless strict
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    let bridge = Bridge::loopback(
+        looped.clone(),
+        Box::new(move |level, msg| {
+            let _ = log_tx.send((level, msg));
+        }),
+    )
+    .await
+    .unwrap();
+    let (level, msg) = log_rx.recv().await.unwrap();
+    assert_eq!(level, LogLevel::Warn);
+    assert!(msg.starts_with("The OSC out address"));
+    drop(bridge);
+
+    let strict = BridgeConfig {
+        strict: true,
+        ..looped
+    };
+    let error = Bridge::loopback(strict, Box::new(|_, _| {}))
+        .await
+        .err()
+        .unwrap();
+    assert!(error.to_string().starts_with("The OSC out address"));
+}
+
+#[tokio::test]
+async fn test_osc_send_queue() {
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let queued = BridgeConfig {
+        osc_send_queue_size: 64,
+        ..config(0, controller.local_addr().unwrap().port(), osc_in_port)
+    };
+    let bridge = Bridge::loopback(queued, Box::new(|_, _| {})).await.unwrap();
+    let run = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    // Packets sent through the queue arrive in order
+    for channel in 0..8 {
+        let packet = OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+            args: vec![OscType::Int(channel), OscType::Int(0), OscType::Int(-1000)],
+        });
+        controller
+            .send_to(
+                &rosc::encoder::encode(&packet).unwrap(),
+                ("127.0.0.1", osc_in_port),
+            )
+            .await
+            .unwrap();
+    }
+    let mut buf = [0u8; 1024];
+    for channel in 0..8 {
+        let len = timeout(TIMEOUT, controller.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        let msg = message(rosc::decoder::decode_udp(&buf[..len]).unwrap().1);
+        assert_eq!(msg.args[0], OscType::Int(channel));
+    }
+
+    bridge.shutdown();
+    timeout(TIMEOUT, run).await.unwrap().unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_osc_multicast() {
+    let group = std::net::Ipv4Addr::new(239, 255, 42, 1);
+    let loopback = std::net::Ipv4Addr::LOCALHOST;
+    let controller = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+    controller.join_multicast_v4(group, loopback).unwrap();
+    let osc_in_port = free_port();
+    let multicast = BridgeConfig {
+        udp_osc_out_addr: group.to_string(),
+        osc_multicast_interface: loopback.to_string(),
+        ..config(0, controller.local_addr().unwrap().port(), osc_in_port)
+    };
+    let bridge = Bridge::loopback(multicast, Box::new(|_, _| {}))
+        .await
+        .unwrap();
+    let run = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    // The confirmation of the set is sent to the group the controller joined
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    });
+    controller
+        .send_to(
+            &rosc::encoder::encode(&packet).unwrap(),
+            ("127.0.0.1", osc_in_port),
+        )
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let len = timeout(TIMEOUT, controller.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..len]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/Level");
+
+    bridge.shutdown();
+    timeout(TIMEOUT, run).await.unwrap().unwrap().unwrap();
+
+    // Multicast needs UDP
+    let multicast = BridgeConfig {
+        udp_osc_out_addr: group.to_string(),
+        osc_transport: OscTransport::Tcp,
+        ..config(0, free_port(), free_port())
+    };
+    assert!(
+        Bridge::loopback(multicast, Box::new(|_, _| {}))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_osc_packet_is_skipped() {
+    let controller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    let bridge = Bridge::loopback(
+        config(0, controller.local_addr().unwrap().port(), osc_in_port),
+        Box::new(move |level, msg| {
+            let _ = log_tx.send((level, msg));
+        }),
+    )
+    .await
+    .unwrap();
+    let run = tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    // The type tags declare three ints but only two follow
+    let mut malformed = rosc::encoder::encode(&OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    }))
+    .unwrap();
+    malformed.truncate(malformed.len() - 4);
+    let valid = OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(1)],
+    });
+    for packet in [malformed, rosc::encoder::encode(&valid).unwrap()] {
+        controller
+            .send_to(&packet, ("127.0.0.1", osc_in_port))
+            .await
+            .unwrap();
+    }
+
+    // Only the valid message makes it through, and the bridge keeps running
+    let mut buf = [0u8; 1024];
+    let len = timeout(TIMEOUT, controller.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..len]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/On");
+    let mut warned = false;
+    while let Ok((level, msg)) = log_rx.try_recv() {
+        warned |= level == LogLevel::Warn && msg.starts_with("Ignoring malformed OSC packet");
+    }
+    assert!(warned);
+
+    bridge.shutdown();
+    timeout(TIMEOUT, run).await.unwrap().unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_mock_detect_model() {
+    let (bridge, mut handle) = run_mock_bridge(BridgeConfig::default());
+
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "devinfo productname"
+    );
+    handle.send_rcp(r#"OK devinfo productname "QL5""#);
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "devinfo version"
+    );
+    handle.send_rcp(r#"OK devinfo version "V5.81""#);
+
+    // The replies are forwarded as usual, then the model is announced
+    let model = loop {
+        let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+        if msg.addr == "/bridge/model" {
+            break msg;
+        }
+    };
+    assert_eq!(
+        model.args,
+        vec![
+            OscType::String("QL5".to_string()),
+            OscType::String("V5.81".to_string())
+        ]
+    );
+    assert_eq!(
+        bridge.model(),
+        Some(ConsoleModel {
+            name: "QL5".to_string(),
+            firmware: Some("V5.81".to_string()),
+        })
+    );
+}
+
+fn mapping_tables() -> Vec<MappingTable> {
+    let table = |name: &str, models: &str, rcp: &str| MappingTable {
+        name: name.to_string(),
+        models: vec![models.to_string()],
+        mappings: vec![ParameterMapping {
+            osc: "fader".to_string(),
+            rcp: rcp.to_string(),
+            arg_types: Vec::new(),
+        }],
+    };
+    vec![
+        table("dm", "DM*", "MIXER:Current/InCh/Fader/Level"),
+        table("ql", "{QL,CL}*", "MIXER:Current/Channel/Fader/Level"),
+    ]
+}
+
+#[tokio::test]
+async fn test_mock_mapping_table_from_detected_model() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        mapping_tables: mapping_tables(),
+        ..BridgeConfig::default()
+    });
+
+    timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap();
+    handle.send_rcp(r#"OK devinfo productname "QL5""#);
+    timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap();
+    handle.send_rcp(r#"OK devinfo version "V5.81""#);
+    loop {
+        let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+        if msg.addr == "/bridge/model" {
+            break;
+        }
+    }
+
+    handle.send_rcp("NOTIFY set MIXER:Current/Channel/Fader/Level 0 0 -1000");
+    let msg = message(timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap());
+    assert_eq!(msg.addr, "/set/fader");
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/set/fader".to_string(),
+        args: vec![OscType::Int(1), OscType::Int(0), OscType::Int(0)],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "set MIXER:Current/Channel/Fader/Level 1 0 0"
+    );
+}
+
+#[tokio::test]
+async fn test_mock_mapping_table_from_model_override() {
+    let (_bridge, mut handle) = run_mock_bridge(BridgeConfig {
+        model: "DM7".to_string(),
+        mapping_tables: mapping_tables(),
+        ..base_config()
+    });
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/set/fader".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(0)],
+    }));
+    assert_eq!(
+        timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 0"
+    );
+}
+
+#[tokio::test]
+async fn test_multiple_consoles_route_by_prefix() {
+    let foh = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let monitors = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_out = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let osc_in_port = free_port();
+
+    let console = |listener: &TcpListener, prefix: &str| ConsoleConfig {
+        console_ip: "127.0.0.1".to_string(),
+        rcp_port: listener.local_addr().unwrap().port(),
+        osc_address_prefix: prefix.to_string(),
+        ..Default::default()
+    };
+    let config = BridgeConfig {
+        consoles: vec![console(&foh, "/foh"), console(&monitors, "/mon")],
+        ..config(0, osc_out.local_addr().unwrap().port(), osc_in_port)
+    };
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+
+    let (foh_read, mut foh_write) = timeout(TIMEOUT, foh.accept())
+        .await
+        .unwrap()
+        .unwrap()
+        .0
+        .into_split();
+    let (monitors_read, mut monitors_write) = timeout(TIMEOUT, monitors.accept())
+        .await
+        .unwrap()
+        .unwrap()
+        .0
+        .into_split();
+
+    // RCP -> OSC: each console's messages carry its prefix
+    let mut buf = [0u8; 1024];
+    for (write, prefix) in [(&mut foh_write, "/foh"), (&mut monitors_write, "/mon")] {
+        write
+            .write_all(b"NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000\n")
+            .await
+            .unwrap();
+        let size = timeout(TIMEOUT, osc_out.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        let msg = message(rosc::decoder::decode_udp(&buf[..size]).unwrap().1);
+        assert_eq!(
+            msg.addr,
+            format!("{}/set/MIXER:Current/InCh/Fader/Level", prefix)
+        );
+    }
+
+    // OSC -> RCP: each message reaches only the console it is addressed to
+    let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for addr in [
+        "/mon/set/MIXER:Current/InCh/Fader/On",
+        "/foh/set/MIXER:Current/InCh/Fader/On",
+    ] {
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(1)],
+        });
+        sender
+            .send_to(
+                &rosc::encoder::encode(&packet).unwrap(),
+                ("127.0.0.1", osc_in_port),
+            )
+            .await
+            .unwrap();
+    }
+    for read in [foh_read, monitors_read] {
+        let mut line = String::new();
+        timeout(TIMEOUT, BufReader::new(read).read_line(&mut line))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(line, "set MIXER:Current/InCh/Fader/On 0 0 1\n");
+    }
+}
+
+#[tokio::test]
+async fn test_multiple_consoles_connect_independently() {
+    let foh = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let monitors_port = free_port();
+    let osc_out = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    let bridge_config = BridgeConfig {
+        consoles: vec![
+            ConsoleConfig {
+                console_ip: "127.0.0.1".to_string(),
+                rcp_port: foh.local_addr().unwrap().port(),
+                osc_address_prefix: "/foh".to_string(),
+                ..Default::default()
+            },
+            ConsoleConfig {
+                console_ip: "127.0.0.1".to_string(),
+                rcp_port: monitors_port,
+                osc_address_prefix: "/mon".to_string(),
+                ..Default::default()
+            },
+        ],
+        reconnect: true,
+        reconnect_delay_ms: 10,
+        reconnect_max_delay_ms: 10,
+        ..config(0, osc_out.local_addr().unwrap().port(), free_port())
+    };
+    tokio::spawn(run_bridge_with_logger(bridge_config, Box::new(|_, _| {})));
+
+    // The unreachable monitors console doesn't keep FOH from bridging
+    let (mut foh_stream, _) = timeout(TIMEOUT, foh.accept()).await.unwrap().unwrap();
+    foh_stream
+        .write_all(b"NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000\n")
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let size = timeout(TIMEOUT, osc_out.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..size]).unwrap().1);
+    assert_eq!(msg.addr, "/foh/set/MIXER:Current/InCh/Fader/Level");
+
+    // and it connects once it comes up
+    let monitors = TcpListener::bind(("127.0.0.1", monitors_port))
+        .await
+        .unwrap();
+    timeout(TIMEOUT, monitors.accept()).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_multiple_consoles_stop_together_on_a_fatal_error() {
+    let foh = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let monitors = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_out = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    let console = |listener: &TcpListener, prefix: &str| ConsoleConfig {
+        console_ip: "127.0.0.1".to_string(),
+        rcp_port: listener.local_addr().unwrap().port(),
+        osc_address_prefix: prefix.to_string(),
+        ..Default::default()
+    };
+    let bridge_config = BridgeConfig {
+        consoles: vec![console(&foh, "/foh"), console(&monitors, "/mon")],
+        rcp_password: "secret".to_string(),
+        ..config(0, osc_out.local_addr().unwrap().port(), free_port())
+    };
+    let result = tokio::spawn(run_bridge_with_logger(bridge_config, Box::new(|_, _| {})));
+
+    // FOH logs in, then the monitors console rejects the login, which
+    // stops the bridge
+    let (foh_read, mut foh_write) = timeout(TIMEOUT, foh.accept())
+        .await
+        .unwrap()
+        .unwrap()
+        .0
+        .into_split();
+    let mut foh_read = BufReader::new(foh_read);
+    let mut line = String::new();
+    timeout(TIMEOUT, foh_read.read_line(&mut line))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(line, "login \"secret\"\n");
+    foh_write.write_all(b"OK login\n").await.unwrap();
+
+    let (mut monitors_stream, _) = timeout(TIMEOUT, monitors.accept()).await.unwrap().unwrap();
+    let mut buf = [0u8; 64];
+    timeout(TIMEOUT, monitors_stream.read(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    monitors_stream
+        .write_all(b"ERROR login InvalidCredentials\n")
+        .await
+        .unwrap();
+
+    let error = timeout(TIMEOUT, result)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("rejected the login"),
+        "{}",
+        error
+    );
+
+    // FOH's bridge was shut down too, closing its connection
+    let mut rest = Vec::new();
+    timeout(TIMEOUT, foh_read.read_to_end(&mut rest))
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_quoted_newline_does_not_split_line() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_out = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = config(
+        console.local_addr().unwrap().port(),
+        osc_out.local_addr().unwrap().port(),
+        free_port(),
+    );
+    tokio::spawn(run_bridge_with_logger(config, Box::new(|_, _| {})));
+
+    let (mut console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    // Split mid-quote across two writes, followed by another line
+    console_stream
+        .write_all(b"NOTIFY set MIXER:Current/Scene/Comment 0 0 \"Act 1")
+        .await
+        .unwrap();
+    console_stream
+        .write_all(b"\nOpening\"\nNOTIFY set MIXER:Current/InCh/Fader/On 0 0 1\n")
+        .await
+        .unwrap();
+
+    let mut buf = [0u8; 1024];
+    let size = timeout(TIMEOUT, osc_out.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..size]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/Scene/Comment");
+    assert_eq!(
+        msg.args[2],
+        OscType::String("\"Act 1\nOpening\"".to_string())
+    );
+
+    let size = timeout(TIMEOUT, osc_out.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..size]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/On");
+}
+
+#[tokio::test]
+async fn test_log_raw_rcp_hexdump() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_out = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = BridgeConfig {
+        log_raw_rcp: true,
+        ..config(
+            console.local_addr().unwrap().port(),
+            osc_out.local_addr().unwrap().port(),
+            free_port(),
+        )
+    };
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(run_bridge_with_logger(
+        config,
+        Box::new(move |_, msg| {
+            let _ = log_tx.send(msg);
+        }),
+    ));
+
+    let (mut console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    console_stream
+        .write_all(b"NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1\n")
+        .await
+        .unwrap();
+
+    let hexdump = loop {
+        let msg = timeout(TIMEOUT, log_rx.recv()).await.unwrap().unwrap();
+        if msg.starts_with("Read ") {
+            break msg;
+        }
+    };
+    assert_eq!(
+        hexdump,
+        "Read 45 bytes from RCP:\n\
+         00000000  4e 4f 54 49 46 59 20 73 65 74 20 4d 49 58 45 52  |NOTIFY set MIXER|\n\
+         00000010  3a 43 75 72 72 65 6e 74 2f 49 6e 43 68 2f 46 61  |:Current/InCh/Fa|\n\
+         00000020  64 65 72 2f 4f 6e 20 30 20 30 20 31 0a           |der/On 0 0 1.|"
+    );
+}
+
+#[tokio::test]
+async fn test_strict_utf8_skips_invalid_line() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let osc_out = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = BridgeConfig {
+        strict_utf8: true,
+        ..config(
+            console.local_addr().unwrap().port(),
+            osc_out.local_addr().unwrap().port(),
+            free_port(),
+        )
+    };
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(run_bridge_with_logger(
+        config,
+        Box::new(move |_, msg| {
+            let _ = log_tx.send(msg);
+        }),
+    ));
+
+    let (mut console_stream, _) = timeout(TIMEOUT, console.accept()).await.unwrap().unwrap();
+    console_stream
+        .write_all(b"NOTIFY set \xff\xfe 0 0 1\nNOTIFY set MIXER:Current/InCh/Fader/On 0 0 1\n")
+        .await
+        .unwrap();
+
+    // Only the valid line is converted
+    let mut buf = [0u8; 1024];
+    let (size, _) = timeout(TIMEOUT, osc_out.recv_from(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let msg = message(rosc::decoder::decode_udp(&buf[..size]).unwrap().1);
+    assert_eq!(msg.addr, "/set/MIXER:Current/InCh/Fader/On");
+
+    let skipped = loop {
+        let msg = timeout(TIMEOUT, log_rx.recv()).await.unwrap().unwrap();
+        if msg.starts_with("Skipped ") {
+            break msg;
+        }
+    };
+    assert_eq!(
+        skipped,
+        "Skipped RCP line that isn't valid UTF-8:\n\
+         00000000  4e 4f 54 49 46 59 20 73 65 74 20 ff fe 20 30 20  |NOTIFY set .. 0 |\n\
+         00000010  30 20 31                                         |0 1|"
+    );
+}
+
+#[tokio::test]
+async fn test_probe_console() {
+    let console = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = console.local_addr().unwrap().port();
+    probe_console("127.0.0.1", port, TIMEOUT).await.unwrap();
+
+    drop(console);
+    assert!(probe_console("127.0.0.1", port, TIMEOUT).await.is_err());
+}
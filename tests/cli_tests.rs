@@ -0,0 +1,99 @@
+use std::process::{Command, Output};
+
+/// Returns a local port that was free at the time of the call.
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Runs the bridge binary with `args` until it exits.
+fn bridge(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_yamaha-rcp-to-osc"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs the bridge against a console that refuses the connection, so it
+/// logs its startup and the failure and exits, and returns what it logged.
+fn log_of_failed_start(flags: &[&str]) -> String {
+    let rcp_port = free_port().to_string();
+    let mut args = vec![
+        "--console-ip",
+        "127.0.0.1",
+        "--rcp-port",
+        &rcp_port,
+        "--udp-osc-in-port",
+        "0",
+        "--no-color",
+    ];
+    args.extend(flags);
+    let output = bridge(&args);
+    assert!(!output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_default_log_level_is_info() {
+    let log = log_of_failed_start(&[]);
+    assert!(log.contains("[INFO] Attempting to connect"), "{}", log);
+    assert!(log.contains("[ERROR] Failed to connect"), "{}", log);
+}
+
+#[test]
+fn test_quiet_only_logs_warnings_and_errors() {
+    for flag in ["-q", "--quiet"] {
+        let log = log_of_failed_start(&[flag]);
+        assert!(!log.contains("[INFO]"), "{}", log);
+        assert!(!log.contains("[DEBUG]"), "{}", log);
+        assert!(log.contains("[ERROR] Failed to connect"), "{}", log);
+    }
+}
+
+#[test]
+fn test_quiet_conflicts_with_verbose() {
+    for args in [["-q", "-v"], ["--quiet", "-vv"]] {
+        let output = bridge(&[args[0], args[1], "print-config"]);
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("cannot be used with"), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_print_config_redacts_password_and_merges_cli_over_file() {
+    let path = std::env::temp_dir().join(format!("bridge-config-{}.json", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"{
+            "console_ip": "10.0.0.5",
+            "rcp_port": 49281,
+            "udp_osc_out_port": 9000,
+            "rcp_password": "hunter2"
+        }"#,
+    )
+    .unwrap();
+
+    let output = bridge(&[
+        "--config",
+        path.to_str().unwrap(),
+        "--udp-osc-out-port",
+        "9100",
+        "print-config",
+    ]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("hunter2"), "{}", stdout);
+
+    let printed: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(printed["rcp_password"], "<redacted>");
+    // Options only in the file are kept, and ones given on the command line
+    // override it
+    assert_eq!(printed["console_ip"], "10.0.0.5");
+    assert_eq!(printed["rcp_port"], 49281);
+    assert_eq!(printed["udp_osc_out_port"], 9100);
+}
@@ -1,6 +1,14 @@
-use rosc::{OscMessage, OscType};
+use rosc::{OscArray, OscBundle, OscMessage, OscPacket, OscTime, OscType};
 use yamaha_rcp_to_osc::{
-    osc_to_rcp, osc_to_rcp_arg, rcp_to_osc, rcp_to_osc_type, split_respecting_quotes,
+    AddressAlias, ArgGroup, ArgType, BlobEncoding, BulkGet, ColorMode, ConversionError,
+    ConversionOptions, ErrorAddress, FloatFormat, LogFormat, LogLevel, NilArg, OscNamespace,
+    ParameterMapping, RcpCommandBuilder, RcpLineFramer, RcpMessage, RcpMessageKind, RoundMode,
+    TypeCoercion, ValueRange, VerbMapping, canonicalize_rcp, clamp_osc_value, escape_osc_address,
+    expand_bulk_get, osc_packet_to_rcp, osc_packet_to_rcp_with, osc_pattern_matches,
+    osc_time_to_unix, osc_to_rcp, osc_to_rcp_arg, osc_to_rcp_arg_with, osc_to_rcp_with, quote,
+    rcp_parts_to_osc, rcp_to_osc, rcp_to_osc_blob, rcp_to_osc_type, rcp_to_osc_type_with,
+    rcp_to_osc_with, split_respecting_quotes, split_respecting_quotes_with, unescape_osc_address,
+    unix_to_osc_time, unquote,
 };
 
 #[test]
@@ -10,14 +18,89 @@ fn test_rcp_to_osc_type() {
     assert!(matches!(rcp_to_osc_type(&int_arg), OscType::Int(42)));
 
     // Test float conversion
-    let float_arg = "3.14".to_string();
+    let float_arg = "3.25".to_string();
     assert!(
-        matches!(rcp_to_osc_type(&float_arg), OscType::Float(f) if (f - 3.14).abs() < f32::EPSILON)
+        matches!(rcp_to_osc_type(&float_arg), OscType::Float(f) if (f - 3.25).abs() < f32::EPSILON)
     );
 
     // Test string conversion
     let string_arg = "test".to_string();
     assert!(matches!(rcp_to_osc_type(&string_arg), OscType::String(s) if s == "test"));
+
+    // Test signs and surrounding whitespace
+    assert!(matches!(rcp_to_osc_type("+42"), OscType::Int(42)));
+    assert!(matches!(rcp_to_osc_type(" 42 "), OscType::Int(42)));
+    assert!(matches!(rcp_to_osc_type("-42"), OscType::Int(-42)));
+    assert!(matches!(rcp_to_osc_type("+3000"), OscType::Int(3000)));
+    assert!(
+        matches!(rcp_to_osc_type(" +1.5"), OscType::Float(f) if (f - 1.5).abs() < f32::EPSILON)
+    );
+    assert!(matches!(rcp_to_osc_type(" test "), OscType::String(s) if s == "test"));
+}
+
+#[test]
+fn test_rcp_to_osc_type_keep_leading_zeros() {
+    let options = ConversionOptions {
+        keep_leading_zeros: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        rcp_to_osc_type_with("01", &options),
+        OscType::String("01".to_string())
+    );
+    assert_eq!(
+        rcp_to_osc_type_with("-007", &options),
+        OscType::String("-007".to_string())
+    );
+    assert_eq!(rcp_to_osc_type_with("0", &options), OscType::Int(0));
+    assert_eq!(rcp_to_osc_type_with("10", &options), OscType::Int(10));
+    assert_eq!(rcp_to_osc_type_with("0.5", &options), OscType::Float(0.5));
+    assert_eq!(
+        rcp_to_osc_type_with("0x", &options),
+        OscType::String("0x".to_string())
+    );
+    // Off by default
+    assert_eq!(
+        rcp_to_osc_type_with("01", &ConversionOptions::default()),
+        OscType::Int(1)
+    );
+
+    let osc = rcp_to_osc_with(
+        "NOTIFY sscurrent_ex MIXER:Current/Scene 01".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(osc.args, vec![OscType::String("01".to_string())]);
+}
+
+#[test]
+fn test_non_finite_floats() {
+    // Scientific notation is a float, but the words for non-finite values
+    // and values that overflow f32 stay strings
+    assert_eq!(rcp_to_osc_type("1.5e3"), OscType::Float(1500.0));
+    assert_eq!(rcp_to_osc_type("-2.5E-2"), OscType::Float(-0.025));
+    for word in ["inf", "-inf", "infinity", "nan", "NaN", "1e40"] {
+        assert_eq!(rcp_to_osc_type(word), OscType::String(word.to_string()));
+    }
+
+    // NaN and infinity can't be written to the console
+    assert_eq!(
+        osc_to_rcp_arg(&OscType::Float(f32::INFINITY)),
+        Err(ConversionError::NonFiniteFloat(f32::INFINITY))
+    );
+    assert_eq!(
+        osc_to_rcp_arg(&OscType::Float(f32::NEG_INFINITY)),
+        Err(ConversionError::NonFiniteFloat(f32::NEG_INFINITY))
+    );
+    assert!(matches!(
+        osc_to_rcp_arg(&OscType::Float(f32::NAN)),
+        Err(ConversionError::NonFiniteFloat(f)) if f.is_nan()
+    ));
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Float(f32::NAN)],
+    };
+    assert!(osc_to_rcp(&msg).is_err());
 }
 
 #[test]
@@ -48,13 +131,235 @@ fn test_split_respecting_quotes() {
     );
 }
 
+#[test]
+fn test_quote_and_unquote() {
+    assert_eq!(quote("Vocal 1"), "\"Vocal 1\"");
+    assert_eq!(quote(""), "\"\"");
+    // Already quoted strings aren't quoted twice
+    assert_eq!(quote("\"Vocal 1\""), "\"Vocal 1\"");
+    // A lone quote isn't a quoted string
+    assert_eq!(quote("\""), "\"\"\"");
+    // RCP can't escape embedded quotes, so they're kept
+    assert_eq!(quote("say \"hi\" now"), "\"say \"hi\" now\"");
+
+    assert_eq!(unquote("\"Vocal 1\""), "Vocal 1");
+    assert_eq!(unquote("\"\""), "");
+    assert_eq!(unquote(""), "");
+    assert_eq!(unquote("\""), "\"");
+    assert_eq!(unquote("bare"), "bare");
+    assert_eq!(unquote("\"say \"hi\"\""), "say \"hi\"");
+    assert_eq!(unquote(&quote("Vocal 1")), "Vocal 1");
+
+    let options = ConversionOptions {
+        unquote_strings: true,
+        ..Default::default()
+    };
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"Vocal 1\"".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(osc.args[2], OscType::String("Vocal 1".to_string()));
+    assert_eq!(
+        osc_to_rcp_with(&osc, &options).unwrap(),
+        "set MIXER:Current/InCh/Label/Name 0 0 \"Vocal 1\""
+    );
+}
+
+#[test]
+fn test_split_respecting_quotes_with() {
+    // Tab-delimited, where spaces are part of an argument
+    let tabbed = "command\targ with spaces\t\targ2";
+    assert_eq!(
+        split_respecting_quotes_with(tabbed, '\t', '"'),
+        vec!["command", "arg with spaces", "arg2"]
+    );
+
+    // Single-quoted, where double quotes are ordinary characters
+    let single = "command 'arg with spaces' \"arg2\"";
+    assert_eq!(
+        split_respecting_quotes_with(single, ' ', '\''),
+        vec!["command", "'arg with spaces'", "\"arg2\""]
+    );
+
+    // The defaults match split_respecting_quotes
+    let quoted = r#"command "arg with spaces" arg2"#;
+    assert_eq!(
+        split_respecting_quotes_with(quoted, ' ', '"'),
+        split_respecting_quotes(quoted)
+    );
+}
+
+#[test]
+fn test_ok_and_notify_prefixes() {
+    let options = ConversionOptions {
+        osc_address_prefix: "/foh".to_string(),
+        ok_prefix: "/ok".to_string(),
+        notify_prefix: "notify/".to_string(),
+        ..Default::default()
+    };
+    let convert = |line: &str| rcp_to_osc_with(line.to_string(), &options).unwrap();
+
+    assert_eq!(
+        convert("OK set MIXER:Current/InCh/Fader/On 0 0 1").addr,
+        "/foh/ok/set/MIXER:Current/InCh/Fader/On"
+    );
+    assert_eq!(
+        convert("NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1").addr,
+        "/foh/notify/set/MIXER:Current/InCh/Fader/On"
+    );
+    // Errors aren't confirmations or updates
+    assert_eq!(convert("ERROR set InvalidArgument").addr, "/foh/error");
+
+    // Either is stripped from incoming OSC, so feedback can be sent back
+    for addr in [
+        "/foh/ok/set/MIXER:Current/InCh/Fader/On",
+        "/foh/notify/set/MIXER:Current/InCh/Fader/On",
+        "/foh/set/MIXER:Current/InCh/Fader/On",
+    ] {
+        let msg = OscMessage {
+            addr: addr.to_string(),
+            args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(1)],
+        };
+        assert_eq!(
+            osc_to_rcp_with(&msg, &options).unwrap(),
+            "set MIXER:Current/InCh/Fader/On 0 0 1"
+        );
+    }
+
+    // Without them, replies and updates share addresses
+    let merged = rcp_to_osc("OK set MIXER:Current/InCh/Fader/On 0 0 1".to_string()).unwrap();
+    assert_eq!(merged.addr, "/set/MIXER:Current/InCh/Fader/On");
+}
+
+#[test]
+fn test_osc_namespace() {
+    let mut namespace = OscNamespace::new();
+    for line in [
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+        "NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"Vox\"",
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 1 0 -500",
+    ] {
+        namespace.observe(&OscPacket::Message(rcp_to_osc(line.to_string()).unwrap()));
+    }
+    namespace.observe(&OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content: vec![OscPacket::Message(OscMessage {
+            addr: "/set/MIXER:Current/InCh/Fader/On".to_string(),
+            args: vec![OscType::Int(0), OscType::Int(0), OscType::Float(0.5)],
+        })],
+    }));
+
+    // Sorted, with the first arguments each address was seen with
+    assert_eq!(namespace.len(), 3);
+    assert_eq!(
+        namespace.to_text(),
+        "/set/MIXER:Current/InCh/Fader/Level ,iii 0 0 -1000\n\
+         /set/MIXER:Current/InCh/Fader/On ,iif 0 0 0.5\n\
+         /set/MIXER:Current/InCh/Label/Name ,iis 0 0 \"Vox\"\n"
+    );
+    let json: serde_json::Value = serde_json::from_str(&namespace.to_json()).unwrap();
+    assert_eq!(json[0]["address"], "/set/MIXER:Current/InCh/Fader/Level");
+    assert_eq!(json[0]["types"], ",iii");
+    assert_eq!(json[0]["example"][2], "-1000");
+}
+
+#[test]
+fn test_default_verb() {
+    let fader = |addr: &str| OscMessage {
+        addr: addr.to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    };
+    let options = ConversionOptions {
+        default_verb: "set".to_string(),
+        ..Default::default()
+    };
+
+    // A bare parameter path gets the default verb
+    assert_eq!(
+        osc_to_rcp_with(&fader("/MIXER/Current/InCh/Fader/Level"), &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    assert_eq!(
+        osc_to_rcp_with(&fader("/MIXER:Current/InCh/Fader/Level"), &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    // A recognized verb is kept
+    assert_eq!(
+        osc_to_rcp_with(&fader("/get/MIXER:Current/InCh/Fader/Level"), &options).unwrap(),
+        "get MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    assert_eq!(
+        osc_to_rcp_with(&fader("/set/MIXER/Current/InCh/Fader/Level"), &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+
+    // The recognized verbs can be replaced
+    let options = ConversionOptions {
+        verbs: vec!["get".to_string(), "event".to_string()],
+        ..options
+    };
+    assert_eq!(
+        osc_to_rcp_with(&fader("/event/MIXER:Current/InCh/Fader/Level"), &options).unwrap(),
+        "event MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    assert_eq!(
+        osc_to_rcp_with(&fader("/devinfo/MIXER:Current/InCh/Fader/Level"), &options).unwrap(),
+        "set devinfo/MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+
+    // Without a default verb, the first segment is always the command
+    assert_eq!(
+        osc_to_rcp(&fader("/MIXER/Current/InCh/Fader/Level")).unwrap(),
+        "MIXER Current/InCh/Fader/Level 0 0 -1000"
+    );
+}
+
+#[test]
+fn test_canonicalize_rcp() {
+    assert_eq!(
+        canonicalize_rcp("  NOTIFY  set MIXER:Current/InCh/Fader/Level\t0 +0 -01000 ").unwrap(),
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    assert_eq!(
+        canonicalize_rcp(r#"OK get MIXER:Current/InCh/Label/Name 0 0 "Lead  Vox""#).unwrap(),
+        r#"OK get MIXER:Current/InCh/Label/Name 0 0 "Lead  Vox""#
+    );
+    assert_eq!(
+        canonicalize_rcp("set MIXER:Current/InCh/Fader/Level 0 0 1.5e3").unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 1500.0"
+    );
+    assert!(matches!(
+        canonicalize_rcp("   "),
+        Err(ConversionError::Empty)
+    ));
+
+    // Canonical lines are left as they are
+    for line in [
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+        r#"NOTIFY set MIXER:Current/InCh/Label/Name 0 0 "Vocal Mic""#,
+        "set MIXER:Current/InCh/Fader/Level 0 0 2.5",
+        r#"OK ssrecall_ex scene_a 5 "Intro""#,
+        "ERROR set InvalidArgument",
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 3000000001",
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 123456789012345678901234",
+        "set MIXER:Current/InCh/Fader/Level 0 0 0.123456789",
+    ] {
+        assert_eq!(canonicalize_rcp(line).unwrap(), line);
+        let canonical = canonicalize_rcp(line).unwrap();
+        assert_eq!(canonicalize_rcp(&canonical).unwrap(), canonical);
+    }
+}
+
 #[test]
 fn test_osc_to_rcp_arg() {
     // Test integer conversion
     assert_eq!(osc_to_rcp_arg(&OscType::Int(42)).unwrap(), "42");
 
     // Test float conversion
-    assert_eq!(osc_to_rcp_arg(&OscType::Float(3.14)).unwrap(), "3.14");
+    assert_eq!(osc_to_rcp_arg(&OscType::Float(3.25)).unwrap(), "3.25");
+    assert_eq!(osc_to_rcp_arg(&OscType::Float(3.0)).unwrap(), "3.0");
+    assert_eq!(osc_to_rcp_arg(&OscType::Float(3.5)).unwrap(), "3.5");
 
     // Test string conversion
     assert_eq!(
@@ -63,7 +368,260 @@ fn test_osc_to_rcp_arg() {
     );
 
     // Test unsupported type
-    assert!(osc_to_rcp_arg(&OscType::Nil).is_err());
+    assert!(osc_to_rcp_arg(&OscType::Bool(true)).is_err());
+}
+
+#[test]
+fn test_nil_and_infinitum() {
+    // A trigger with a single Nil is the bare command
+    let trigger = OscMessage {
+        addr: "/devstatus/runmode".to_string(),
+        args: vec![OscType::Nil],
+    };
+    assert_eq!(osc_to_rcp(&trigger).unwrap(), "devstatus runmode");
+    let msg = OscMessage {
+        addr: "/get/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Nil, OscType::Int(0)],
+    };
+    assert_eq!(
+        osc_to_rcp(&msg).unwrap(),
+        "get MIXER:Current/InCh/Fader/Level 0 0"
+    );
+
+    let options = ConversionOptions {
+        nil_arg: NilArg::Reject,
+        ..Default::default()
+    };
+    assert_eq!(
+        osc_to_rcp_with(&trigger, &options),
+        Err(ConversionError::UnsupportedArgType(OscType::Nil))
+    );
+
+    assert_eq!(
+        osc_to_rcp_arg(&OscType::Inf),
+        Err(ConversionError::Infinitum)
+    );
+}
+
+#[test]
+fn test_subscribe_and_unsubscribe() {
+    // Written as the RCP commands of the same name, arguments and all
+    let subscribe = OscMessage {
+        addr: "/subscribe/MIXER:Current/Meter/InCh".to_string(),
+        args: vec![OscType::Int(100)],
+    };
+    assert_eq!(
+        osc_to_rcp(&subscribe).unwrap(),
+        "subscribe MIXER:Current/Meter/InCh 100"
+    );
+    let unsubscribe = OscMessage {
+        addr: "/unsubscribe/MIXER/Current/Meter/InCh".to_string(),
+        args: vec![OscType::Int(100)],
+    };
+    assert_eq!(
+        osc_to_rcp(&unsubscribe).unwrap(),
+        "unsubscribe MIXER:Current/Meter/InCh 100"
+    );
+
+    // Recognized as commands with a default verb
+    let options = ConversionOptions {
+        default_verb: "set".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        osc_to_rcp_with(&subscribe, &options).unwrap(),
+        "subscribe MIXER:Current/Meter/InCh 100"
+    );
+
+    // Or mapped to other commands, still with their arguments
+    let options = ConversionOptions {
+        default_verb: "set".to_string(),
+        verbs: vec!["get".to_string()],
+        verb_mappings: vec![
+            VerbMapping {
+                osc: "subscribe".to_string(),
+                rcp: "mtrstart".to_string(),
+            },
+            VerbMapping {
+                osc: "unsubscribe".to_string(),
+                rcp: "mtrstop".to_string(),
+            },
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        osc_to_rcp_with(&subscribe, &options).unwrap(),
+        "mtrstart MIXER:Current/Meter/InCh 100"
+    );
+    assert_eq!(
+        osc_to_rcp_with(&unsubscribe, &options).unwrap(),
+        "mtrstop MIXER:Current/Meter/InCh 100"
+    );
+}
+
+#[test]
+fn test_control_characters_are_rejected() {
+    assert_eq!(
+        osc_to_rcp_arg(&OscType::String("Vocal\nset".to_string())),
+        Err(ConversionError::ControlCharacter("Vocal\nset".to_string()))
+    );
+
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+        args: vec![
+            OscType::Int(0),
+            OscType::Int(0),
+            OscType::String("Vocal\r".to_string()),
+        ],
+    };
+    assert!(matches!(
+        osc_to_rcp(&msg),
+        Err(ConversionError::ControlCharacter(_))
+    ));
+
+    // An escaped line break in the address would split the command too
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level%0Aset".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    };
+    assert!(matches!(
+        osc_to_rcp(&msg),
+        Err(ConversionError::ControlCharacter(_))
+    ));
+}
+
+#[test]
+fn test_osc_to_rcp_arg_array() {
+    let color = OscType::Array(OscArray {
+        content: vec![OscType::Int(255), OscType::Int(0), OscType::Int(0)],
+    });
+    assert_eq!(osc_to_rcp_arg(&color).unwrap(), "255 0 0");
+
+    // Nested arrays flatten depth-first
+    let nested = OscType::Array(OscArray {
+        content: vec![
+            OscType::Int(1),
+            OscType::Array(OscArray {
+                content: vec![OscType::Float(2.0), OscType::String("x".to_string())],
+            }),
+            OscType::Array(OscArray { content: vec![] }),
+            OscType::Int(3),
+        ],
+    });
+    assert_eq!(osc_to_rcp_arg(&nested).unwrap(), "1 2.0 \"x\" 3");
+
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Color".to_string(),
+        args: vec![
+            OscType::Int(0),
+            color,
+            OscType::Array(OscArray { content: vec![] }),
+        ],
+    };
+    assert_eq!(
+        osc_to_rcp(&msg).unwrap(),
+        "set MIXER:Current/InCh/Color 0 255 0 0"
+    );
+
+    // Unsupported elements still fail the whole array
+    let invalid = OscType::Array(OscArray {
+        content: vec![OscType::Int(1), OscType::Bool(true)],
+    });
+    assert_eq!(
+        osc_to_rcp_arg(&invalid),
+        Err(ConversionError::UnsupportedArgType(OscType::Bool(true)))
+    );
+}
+
+#[test]
+fn test_osc_to_rcp_arg_float_format() {
+    let decimal = ConversionOptions {
+        float_format: FloatFormat::Decimal,
+        ..Default::default()
+    };
+    assert_eq!(
+        osc_to_rcp_arg_with(&OscType::Float(3.0), &decimal).unwrap(),
+        "3.0"
+    );
+    assert_eq!(
+        osc_to_rcp_arg_with(&OscType::Float(3.5), &decimal).unwrap(),
+        "3.5"
+    );
+    assert_eq!(
+        osc_to_rcp_arg_with(&OscType::Float(-3.0), &decimal).unwrap(),
+        "-3.0"
+    );
+
+    let integer_when_whole = ConversionOptions {
+        float_format: FloatFormat::IntegerWhenWhole,
+        ..Default::default()
+    };
+    assert_eq!(
+        osc_to_rcp_arg_with(&OscType::Float(3.0), &integer_when_whole).unwrap(),
+        "3"
+    );
+    assert_eq!(
+        osc_to_rcp_arg_with(&OscType::Float(3.5), &integer_when_whole).unwrap(),
+        "3.5"
+    );
+
+    // Default options always include a decimal point
+    assert_eq!(
+        ConversionOptions::default().float_format,
+        FloatFormat::Decimal
+    );
+}
+
+#[test]
+fn test_osc_to_rcp_arg_float_decimals() {
+    // By default floats are written in the shortest form that reads back as
+    // the same f32, so values don't pick up extra digits
+    let shortest = ConversionOptions::default();
+    for (value, expected) in [
+        (0.1, "0.1"),
+        (1.23, "1.23"),
+        (-0.5, "-0.5"),
+        (0.000001, "0.000001"),
+        (-96.0, "-96.0"),
+        (1000.0, "1000.0"),
+    ] {
+        assert_eq!(
+            osc_to_rcp_arg_with(&OscType::Float(value), &shortest).unwrap(),
+            expected
+        );
+        assert_eq!(expected.parse::<f32>().unwrap(), value);
+    }
+
+    // Fixed decimals round to that many places
+    let fixed = ConversionOptions {
+        float_decimals: Some(2),
+        ..Default::default()
+    };
+    for (value, expected) in [
+        (0.1, "0.10"),
+        (1.23456, "1.23"),
+        (-0.126, "-0.13"),
+        (-96.0, "-96.00"),
+    ] {
+        assert_eq!(
+            osc_to_rcp_arg_with(&OscType::Float(value), &fixed).unwrap(),
+            expected
+        );
+    }
+
+    // Whole values still drop the decimals when asked to
+    let fixed_integer_when_whole = ConversionOptions {
+        float_format: FloatFormat::IntegerWhenWhole,
+        ..fixed
+    };
+    assert_eq!(
+        osc_to_rcp_arg_with(&OscType::Float(-96.0), &fixed_integer_when_whole).unwrap(),
+        "-96"
+    );
+    assert_eq!(
+        osc_to_rcp_arg_with(&OscType::Float(0.126), &fixed_integer_when_whole).unwrap(),
+        "0.13"
+    );
 }
 
 #[test]
@@ -88,9 +646,78 @@ fn test_rcp_to_osc() {
     assert_eq!(osc_msg.addr, "/error");
     assert_eq!(osc_msg.args.len(), 3);
 
+    // Test structured ERROR message with a numeric code
+    let error_msg = r#"ERROR 404 "No such parameter""#.to_string();
+    let osc_msg = rcp_to_osc(error_msg).unwrap();
+    assert_eq!(osc_msg.addr, "/error/404");
+    assert_eq!(osc_msg.args.len(), 2);
+    assert!(matches!(&osc_msg.args[0], OscType::Int(404)));
+    assert!(matches!(&osc_msg.args[1], OscType::String(s) if s == "No such parameter"));
+
+    // Unquoted message tokens are joined into a single string
+    let osc_msg = rcp_to_osc("ERROR 500 unknown command".to_string()).unwrap();
+    assert_eq!(osc_msg.addr, "/error/500");
+    assert!(matches!(&osc_msg.args[1], OscType::String(s) if s == "unknown command"));
+
+    // A code on its own produces no message argument
+    let osc_msg = rcp_to_osc("ERROR 404".to_string()).unwrap();
+    assert_eq!(osc_msg.addr, "/error/404");
+    assert_eq!(osc_msg.args.len(), 1);
+
     // Test invalid message
     let invalid_msg = "INVALID message".to_string();
     assert!(rcp_to_osc(invalid_msg).is_err());
+
+    // Test NOTIFY without a type and name
+    assert_eq!(
+        rcp_to_osc("NOTIFY set".to_string()),
+        Err(ConversionError::TooFewParts)
+    );
+}
+
+#[test]
+fn test_message_type_is_case_insensitive() {
+    let expected =
+        rcp_to_osc("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000".to_string()).unwrap();
+    for line in [
+        "notify set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+        "Notify set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+        "ok set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+        "oK set MIXER:Current/InCh/Fader/Level 0 0 -1000",
+    ] {
+        assert_eq!(rcp_to_osc(line.to_string()).unwrap(), expected, "{}", line);
+    }
+    assert_eq!(
+        rcp_to_osc("error 404".to_string()).unwrap().addr,
+        "/error/404"
+    );
+
+    // The kind picks the OK or NOTIFY prefix whatever its case
+    let options = ConversionOptions {
+        ok_prefix: "/ok".to_string(),
+        ..Default::default()
+    };
+    let msg = rcp_to_osc_with("Ok scene current 2".to_string(), &options).unwrap();
+    assert_eq!(msg.addr, "/ok/scene/current");
+
+    let message = RcpMessage::parse("notify set MIXER:Current/InCh/Fader/Level 0 0 -1000").unwrap();
+    assert_eq!(message.kind, RcpMessageKind::Notify);
+    assert_eq!(
+        message.to_string(),
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+}
+
+#[test]
+fn test_rcp_parts_to_osc() {
+    let line = r#"NOTIFY set MIXER:Current/InCh/Label/Name 0 0 "Kick In""#;
+    let parts = split_respecting_quotes(line);
+    assert_eq!(
+        rcp_parts_to_osc(&parts).unwrap(),
+        rcp_to_osc(line.to_string()).unwrap()
+    );
+
+    assert_eq!(rcp_parts_to_osc(&[]), Err(ConversionError::Empty));
 }
 
 #[test]
@@ -112,6 +739,26 @@ fn test_osc_to_rcp() {
         r#"scene name 1 "Test Scene""#
     );
 
+    // Test message without arguments has no trailing space
+    let osc_msg = OscMessage {
+        addr: "/scene/current".to_string(),
+        args: vec![],
+    };
+    assert_eq!(osc_to_rcp(&osc_msg).unwrap(), "scene current");
+
+    // Test single-segment address is a bare command
+    let osc_msg = OscMessage {
+        addr: "/devstatus".to_string(),
+        args: vec![],
+    };
+    assert_eq!(osc_to_rcp(&osc_msg).unwrap(), "devstatus");
+
+    let osc_msg = OscMessage {
+        addr: "/ssrecall_ex".to_string(),
+        args: vec![OscType::String("scene_a".to_string()), OscType::Int(5)],
+    };
+    assert_eq!(osc_to_rcp(&osc_msg).unwrap(), r#"ssrecall_ex "scene_a" 5"#);
+
     // Test invalid address
     let invalid_msg = OscMessage {
         addr: "".to_string(),
@@ -120,6 +767,55 @@ fn test_osc_to_rcp() {
     assert!(osc_to_rcp(&invalid_msg).is_err());
 }
 
+#[test]
+fn test_osc_to_rcp_multi_segment() {
+    // Everything after the command is the path
+    let osc_msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    };
+    assert_eq!(
+        osc_to_rcp(&osc_msg).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+
+    // A module given as its own segment gets its colon back
+    let osc_msg = OscMessage {
+        addr: "/set/MIXER/Current/Channel/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    };
+    let rcp = osc_to_rcp(&osc_msg).unwrap();
+    assert_eq!(rcp, "set MIXER:Current/Channel/Fader/Level 0 0 -1000");
+
+    // The console echoes it in the colon form, which converts back to the
+    // same command
+    let echoed = rcp_to_osc(format!("NOTIFY {}", rcp)).unwrap();
+    assert_eq!(echoed.addr, "/set/MIXER:Current/Channel/Fader/Level");
+    assert_eq!(echoed.args, osc_msg.args);
+    assert_eq!(osc_to_rcp(&echoed).unwrap(), rcp);
+
+    let osc_msg = OscMessage {
+        addr: "/set/MIXER/Current/InCh/EQ/Band/Gain".to_string(),
+        args: vec![OscType::Int(3), OscType::Int(1), OscType::Int(-150)],
+    };
+    assert_eq!(
+        osc_to_rcp(&osc_msg).unwrap(),
+        "set MIXER:Current/InCh/EQ/Band/Gain 3 1 -150"
+    );
+
+    // Lower-case segments, and a module with nothing after it, are kept
+    let osc_msg = OscMessage {
+        addr: "/scene/current/name".to_string(),
+        args: vec![],
+    };
+    assert_eq!(osc_to_rcp(&osc_msg).unwrap(), "scene current/name");
+    let osc_msg = OscMessage {
+        addr: "/get/MIXER".to_string(),
+        args: vec![],
+    };
+    assert_eq!(osc_to_rcp(&osc_msg).unwrap(), "get MIXER");
+}
+
 #[test]
 fn test_bidirectional_conversion() {
     // Test RCP -> OSC -> RCP conversion
@@ -134,3 +830,1213 @@ fn test_bidirectional_conversion() {
     let rcp = osc_to_rcp(&osc).unwrap();
     assert_eq!(rcp, "scene name 1 \"Test Scene\"");
 }
+
+#[test]
+fn test_osc_address_prefix() {
+    let options = ConversionOptions {
+        osc_address_prefix: "/console1".to_string(),
+        ..Default::default()
+    };
+
+    // Prefix is prepended to outgoing OSC addresses
+    let osc = rcp_to_osc_with("NOTIFY scene current 1".to_string(), &options).unwrap();
+    assert_eq!(osc.addr, "/console1/scene/current");
+
+    // ...and stripped from incoming ones
+    assert_eq!(osc_to_rcp_with(&osc, &options).unwrap(), "scene current 1");
+
+    // Leading/trailing slashes on the configured prefix are optional
+    let options = ConversionOptions {
+        osc_address_prefix: "console1/".to_string(),
+        ..Default::default()
+    };
+    let osc = rcp_to_osc_with("OK scene name 1 \"Test Scene\"".to_string(), &options).unwrap();
+    assert_eq!(osc.addr, "/console1/scene/name");
+    assert_eq!(
+        osc_to_rcp_with(&osc, &options).unwrap(),
+        "scene name 1 \"Test Scene\""
+    );
+
+    // Addresses without the prefix are rejected
+    let unprefixed = OscMessage {
+        addr: "/scene/current".to_string(),
+        args: vec![OscType::Int(1)],
+    };
+    assert!(osc_to_rcp_with(&unprefixed, &options).is_err());
+
+    // A segment that merely starts with the prefix text is not a match
+    let lookalike = OscMessage {
+        addr: "/console10/scene/current".to_string(),
+        args: vec![OscType::Int(1)],
+    };
+    assert!(osc_to_rcp_with(&lookalike, &options).is_err());
+}
+
+fn bundle(content: Vec<OscPacket>) -> OscPacket {
+    OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content,
+    })
+}
+
+fn message(addr: &str, args: Vec<OscType>) -> OscPacket {
+    OscPacket::Message(OscMessage {
+        addr: addr.to_string(),
+        args,
+    })
+}
+
+#[test]
+fn test_osc_packet_to_rcp() {
+    // A single message converts to a single command
+    let packet = message("/scene/current", vec![OscType::Int(1)]);
+    assert_eq!(osc_packet_to_rcp(&packet).unwrap(), vec!["scene current 1"]);
+
+    // A bundle of two messages keeps their order
+    let packet = bundle(vec![
+        message(
+            "/set/MIXER:Current/Channel/Fader/Level",
+            vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+        ),
+        message(
+            "/set/MIXER:Current/Channel/Fader/On",
+            vec![OscType::Int(0), OscType::Int(0), OscType::Int(1)],
+        ),
+    ]);
+    assert_eq!(
+        osc_packet_to_rcp(&packet).unwrap(),
+        vec![
+            "set MIXER:Current/Channel/Fader/Level 0 0 -1000",
+            "set MIXER:Current/Channel/Fader/On 0 0 1",
+        ]
+    );
+
+    // Nested bundles are flattened depth-first
+    let packet = bundle(vec![
+        message("/get/A", vec![]),
+        bundle(vec![message("/get/B", vec![]), message("/get/C", vec![])]),
+        message("/get/D", vec![]),
+    ]);
+    assert_eq!(
+        osc_packet_to_rcp(&packet).unwrap(),
+        vec!["get A", "get B", "get C", "get D"]
+    );
+
+    // One bad message fails the whole packet
+    let packet = bundle(vec![
+        message("/get/A", vec![]),
+        message("/get/B", vec![OscType::Bool(true)]),
+    ]);
+    assert_eq!(
+        osc_packet_to_rcp(&packet),
+        Err(ConversionError::UnsupportedArgType(OscType::Bool(true)))
+    );
+}
+
+#[test]
+fn test_time_round_trip() {
+    // 2024-01-01T00:00:00Z
+    let time = OscTime {
+        seconds: 3_913_056_000,
+        fractional: 0,
+    };
+    assert_eq!(osc_time_to_unix(time), 1_704_067_200);
+    assert_eq!(unix_to_osc_time(1_704_067_200), Some(time));
+    assert_eq!(unix_to_osc_time(-2_208_988_801), None);
+
+    let options = ConversionOptions {
+        time_addresses: vec!["/set/MIXER:Current/Clock/Time".to_string()],
+        ..Default::default()
+    };
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/Clock/Time".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Time(time)],
+    };
+    let rcp = osc_to_rcp_with(&msg, &options).unwrap();
+    assert_eq!(rcp, "set MIXER:Current/Clock/Time 0 0 1704067200");
+
+    let osc = rcp_to_osc_with(format!("NOTIFY {}", rcp), &options).unwrap();
+    assert_eq!(osc, msg);
+
+    // The fraction of a second is dropped
+    let precise = OscType::Time(OscTime {
+        seconds: time.seconds,
+        fractional: 1 << 31,
+    });
+    assert_eq!(osc_to_rcp_arg(&precise).unwrap(), "1704067200");
+    // Other addresses keep the number
+    let osc = rcp_to_osc(format!("NOTIFY {}", rcp)).unwrap();
+    assert_eq!(osc.args[2], OscType::Int(1_704_067_200));
+}
+
+#[test]
+fn test_blob_round_trip() {
+    let bytes = vec![0x00, 0x01, 0x7f, 0x80, 0xff];
+
+    for (encoding, encoded) in [
+        (BlobEncoding::Base64, "\"AAF/gP8=\""),
+        (BlobEncoding::Hex, "\"00017f80ff\""),
+    ] {
+        let options = ConversionOptions {
+            blob_encoding: encoding,
+            blob_addresses: vec!["/set/MIXER:Current/Blob".to_string()],
+            ..Default::default()
+        };
+
+        let msg = OscMessage {
+            addr: "/set/MIXER:Current/Blob".to_string(),
+            args: vec![OscType::Int(0), OscType::Blob(bytes.clone())],
+        };
+        let rcp = osc_to_rcp_with(&msg, &options).unwrap();
+        assert_eq!(rcp, format!("set MIXER:Current/Blob 0 {}", encoded));
+
+        let back = rcp_to_osc_with(format!("OK {}", rcp), &options).unwrap();
+        assert_eq!(back, msg);
+
+        assert_eq!(
+            rcp_to_osc_blob(encoded, encoding).unwrap(),
+            OscType::Blob(bytes.clone())
+        );
+    }
+
+    // Only configured addresses are decoded
+    let osc_msg = rcp_to_osc(r#"OK set MIXER:Current/Other 0 "AAF/gP8=""#.to_string()).unwrap();
+    assert!(matches!(&osc_msg.args[1], OscType::String(_)));
+
+    assert_eq!(
+        rcp_to_osc_blob("\"0g\"", BlobEncoding::Hex),
+        Err(ConversionError::InvalidBlob("\"0g\"".to_string()))
+    );
+}
+
+#[test]
+fn test_osc_address_escaping() {
+    assert_eq!(
+        escape_osc_address("MIXER:Current/InCh"),
+        "MIXER:Current/InCh"
+    );
+    assert_eq!(
+        escape_osc_address("Ch 1#*?[]{},%"),
+        "Ch%201%23%2A%3F%5B%5D%7B%7D%2C%25"
+    );
+    assert_eq!(escape_osc_address("Bühne"), "B%C3%BChne");
+    for name in ["Ch 1#*?[]{},%", "Bühne", "100%", "%2"] {
+        assert_eq!(unescape_osc_address(&escape_osc_address(name)), name);
+    }
+    // Unescaped addresses pass through
+    assert_eq!(unescape_osc_address("/set/100%"), "/set/100%");
+
+    // A quoted name with a space
+    let osc_msg = rcp_to_osc(r#"NOTIFY set "Vocal Mic" 0"#.to_string()).unwrap();
+    assert_eq!(osc_msg.addr, "/set/\"Vocal%20Mic\"");
+    assert_eq!(osc_to_rcp(&osc_msg).unwrap(), r#"set "Vocal Mic" 0"#);
+
+    // OSC wildcard characters
+    let osc_msg = rcp_to_osc("NOTIFY set MIXER:Current/Ch*/Fader? 0 [1]".to_string()).unwrap();
+    assert_eq!(osc_msg.addr, "/set/MIXER:Current/Ch%2A/Fader%3F");
+    assert_eq!(
+        osc_to_rcp(&osc_msg).unwrap(),
+        "set MIXER:Current/Ch*/Fader? 0 \"[1]\""
+    );
+}
+
+#[test]
+fn test_osc_pattern_matches() {
+    let address = "/set/MIXER:Current/InCh/Fader/Level";
+    assert!(osc_pattern_matches(address, address));
+    assert!(osc_pattern_matches(
+        "/set/MIXER:Current/*/Fader/Level",
+        address
+    ));
+    assert!(osc_pattern_matches(
+        "/set/MIXER:Current/InCh/Fader/*",
+        address
+    ));
+    assert!(osc_pattern_matches(
+        "/set/MIXER:Current/?nCh/Fader/Level",
+        address
+    ));
+    assert!(osc_pattern_matches(
+        "/set/MIXER:Current/[A-Z]nCh/Fader/Level",
+        address
+    ));
+    assert!(osc_pattern_matches(
+        "/set/MIXER:Current/[!a-z]nCh/Fader/Level",
+        address
+    ));
+    assert!(osc_pattern_matches(
+        "/set/MIXER:Current/{InCh,StInCh}/Fader/Level",
+        address
+    ));
+
+    // Wildcards don't cross segments
+    assert!(!osc_pattern_matches("/set/*/Level", address));
+    assert!(!osc_pattern_matches(
+        "/set/MIXER:Current/[a-z]nCh/Fader/Level",
+        address
+    ));
+    assert!(!osc_pattern_matches(
+        "/set/MIXER:Current/{Mix,Mtrx}/Fader/Level",
+        address
+    ));
+    assert!(!osc_pattern_matches(
+        "/set/MIXER:Current/InCh/Fader",
+        address
+    ));
+}
+
+#[test]
+fn test_round_mode() {
+    let cases = [
+        (RoundMode::HalfAwayFromZero, 3, -3),
+        (RoundMode::HalfEven, 2, -2),
+        (RoundMode::HalfUp, 3, -2),
+        (RoundMode::Truncate, 2, -2),
+    ];
+    for (mode, up, down) in cases {
+        assert_eq!(mode.to_int(2.5), Ok(up), "{}", mode);
+        assert_eq!(mode.to_int(-2.5), Ok(down), "{}", mode);
+        assert_eq!(mode.to_string().parse::<RoundMode>(), Ok(mode));
+    }
+    assert_eq!(RoundMode::HalfEven.to_int(3.5), Ok(4));
+    assert_eq!(RoundMode::Truncate.to_int(2.9), Ok(2));
+
+    // Out of range values are rejected rather than saturated
+    assert_eq!(
+        RoundMode::default().to_int(3e9),
+        Err(ConversionError::IntOverflow(3e9))
+    );
+    assert!(RoundMode::default().to_int(f64::NAN).is_err());
+    assert_eq!(
+        RoundMode::HalfAwayFromZero.to_int(2_147_483_647.4),
+        Ok(i32::MAX)
+    );
+
+    let options = |round_mode| ConversionOptions {
+        type_suffixes: true,
+        round_mode,
+        ..Default::default()
+    };
+    let msg = OscMessage {
+        addr: "/ssrecall_ex/scene_a/i".to_string(),
+        args: vec![OscType::Float(2.5)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options(RoundMode::HalfEven)).unwrap(),
+        "ssrecall_ex scene_a 2"
+    );
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options(RoundMode::HalfAwayFromZero)).unwrap(),
+        "ssrecall_ex scene_a 3"
+    );
+    let huge = OscMessage {
+        args: vec![OscType::Float(1e10)],
+        ..msg
+    };
+    assert_eq!(
+        osc_to_rcp_with(&huge, &options(RoundMode::default())),
+        Err(ConversionError::IntOverflow(1e10))
+    );
+}
+
+#[test]
+fn test_type_coercion() {
+    let options = ConversionOptions {
+        type_coercions: vec![
+            TypeCoercion {
+                address: "/set/MIXER:Current/*/Fader/Level".to_string(),
+                arg_type: ArgType::Int,
+                positions: Vec::new(),
+            },
+            TypeCoercion {
+                address: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+                arg_type: ArgType::String,
+                positions: Vec::new(),
+            },
+        ],
+        ..Default::default()
+    };
+
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![
+            OscType::Float(0.0),
+            OscType::Float(1.0),
+            OscType::Float(-1000.6),
+        ],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 1 -1001"
+    );
+
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+        args: vec![OscType::Int(7)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "set MIXER:Current/InCh/Label/Name \"7\""
+    );
+
+    // Unmapped addresses are unchanged
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Float(1.0)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/On 1.0"
+    );
+}
+
+#[test]
+fn test_type_coercion_positions() {
+    // A numeric scene label must reach the console as a string, while the
+    // scene number stays a number
+    let options = ConversionOptions {
+        type_coercions: vec![
+            TypeCoercion {
+                address: "/ssrecall_ex/*".to_string(),
+                arg_type: ArgType::String,
+                positions: vec![1],
+            },
+            TypeCoercion {
+                address: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+                arg_type: ArgType::String,
+                positions: vec![2],
+            },
+        ],
+        ..Default::default()
+    };
+
+    let msg = OscMessage {
+        addr: "/ssrecall_ex/scene_a".to_string(),
+        args: vec![OscType::Int(5), OscType::Int(5)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "ssrecall_ex scene_a 5 \"5\""
+    );
+
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(7)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "set MIXER:Current/InCh/Label/Name 0 0 \"7\""
+    );
+
+    // Positions past the end are ignored
+    let msg = OscMessage {
+        addr: "/ssrecall_ex/scene_a".to_string(),
+        args: vec![OscType::Int(5)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "ssrecall_ex scene_a 5"
+    );
+}
+
+#[test]
+fn test_error_addresses() {
+    let options = ConversionOptions {
+        error_addresses: vec![
+            ErrorAddress {
+                address: "/error/warn".to_string(),
+                codes: vec![404, 409],
+            },
+            ErrorAddress {
+                address: "/error/fatal/".to_string(),
+                codes: vec![500],
+            },
+        ],
+        osc_address_prefix: "/foh".to_string(),
+        ..Default::default()
+    };
+    let convert = |line: &str| rcp_to_osc_with(line.to_string(), &options).unwrap();
+
+    let osc_msg = convert(r#"ERROR 404 "No such parameter""#);
+    assert_eq!(osc_msg.addr, "/foh/error/warn/404");
+    assert_eq!(osc_msg.args[0], OscType::Int(404));
+    assert_eq!(convert("ERROR 409").addr, "/foh/error/warn/409");
+    assert_eq!(
+        convert("ERROR 500 unknown command").addr,
+        "/foh/error/fatal/500"
+    );
+
+    // Unmapped codes and unstructured errors keep the default address
+    assert_eq!(convert("ERROR 401").addr, "/foh/error/401");
+    assert_eq!(convert("ERROR some error message").addr, "/foh/error");
+}
+
+#[test]
+fn test_type_suffixes() {
+    let options = ConversionOptions {
+        type_suffixes: true,
+        ..Default::default()
+    };
+    let msg = |addr: &str, arg| OscMessage {
+        addr: addr.to_string(),
+        args: vec![arg],
+    };
+
+    // /i rounds to an integer
+    assert_eq!(
+        osc_to_rcp_with(&msg("/scene/current/i", OscType::Float(4.6)), &options).unwrap(),
+        "scene current 5"
+    );
+
+    // /f writes a decimal
+    assert_eq!(
+        osc_to_rcp_with(
+            &msg("/set/MIXER:Current/InCh/Fader/Level/f", OscType::Int(-1000)),
+            &options
+        )
+        .unwrap(),
+        "set MIXER:Current/InCh/Fader/Level -1000.0"
+    );
+
+    // /s writes a quoted string
+    assert_eq!(
+        osc_to_rcp_with(
+            &msg("/set/MIXER:Current/InCh/Label/Name/s", OscType::Int(7)),
+            &options
+        )
+        .unwrap(),
+        "set MIXER:Current/InCh/Label/Name \"7\""
+    );
+
+    // A suffix takes precedence over a type coercion
+    let coerced = ConversionOptions {
+        type_coercions: vec![TypeCoercion {
+            address: "/scene/*/*".to_string(),
+            arg_type: ArgType::String,
+            positions: Vec::new(),
+        }],
+        ..options.clone()
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg("/scene/current/i", OscType::Float(4.6)), &coerced).unwrap(),
+        "scene current 5"
+    );
+
+    // A lone suffix is a command, and suffixes are only read when enabled
+    assert_eq!(
+        osc_to_rcp_with(&msg("/i", OscType::Int(1)), &options).unwrap(),
+        "i 1"
+    );
+    assert_eq!(
+        osc_to_rcp(&msg("/scene/current/i", OscType::Float(4.5))).unwrap(),
+        "scene current/i 4.5"
+    );
+}
+
+#[test]
+fn test_value_range_min_above_max_is_rejected() {
+    let error = serde_json::from_str::<ConversionOptions>(
+        r#"{ "value_ranges": [{ "address": "/set/fader", "min": 10, "max": -10 }] }"#,
+    )
+    .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("Invalid value range for /set/fader: min 10 is larger than max -10")
+    );
+
+    // Built in code it clamps to max rather than panicking
+    let options = ConversionOptions {
+        value_ranges: vec![ValueRange {
+            address: "/set/fader".to_string(),
+            min: 10.0,
+            max: -10.0,
+        }],
+        ..Default::default()
+    };
+    for (value, clamped) in [
+        (OscType::Int(0), OscType::Int(-10)),
+        (OscType::Long(0), OscType::Long(-10)),
+        (OscType::Float(0.0), OscType::Float(-10.0)),
+        (OscType::Double(0.0), OscType::Double(-10.0)),
+    ] {
+        let mut msg = OscMessage {
+            addr: "/set/fader".to_string(),
+            args: vec![value],
+        };
+        clamp_osc_value(&mut msg, &options);
+        assert_eq!(msg.args, vec![clamped]);
+    }
+}
+
+#[test]
+fn test_value_range_without_an_integer_is_rejected() {
+    let error = serde_json::from_str::<ConversionOptions>(
+        r#"{ "value_ranges": [{ "address": "/set/pan", "min": 0.2, "max": 0.8 }] }"#,
+    )
+    .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("Invalid value range for /set/pan: there is no integer between 0.2 and 0.8")
+    );
+
+    // Built in code, integers are clamped without panicking
+    let options = ConversionOptions {
+        value_ranges: vec![ValueRange {
+            address: "/set/pan".to_string(),
+            min: 0.2,
+            max: 0.8,
+        }],
+        ..Default::default()
+    };
+    for value in [OscType::Int(5), OscType::Long(5)] {
+        let mut msg = OscMessage {
+            addr: "/set/pan".to_string(),
+            args: vec![value],
+        };
+        assert!(clamp_osc_value(&mut msg, &options).is_some());
+    }
+    let msg = OscMessage {
+        addr: "/set/pan".to_string(),
+        args: vec![OscType::Float(1.0)],
+    };
+    assert_eq!(osc_to_rcp_with(&msg, &options).unwrap(), "set pan 0.8");
+
+    // A valid range still loads
+    let options: ConversionOptions = serde_json::from_str(
+        r#"{ "value_ranges": [{ "address": "/set/pan", "min": -63, "max": 63 }] }"#,
+    )
+    .unwrap();
+    assert!(options.value_ranges[0].check().is_ok());
+}
+
+#[test]
+fn test_value_ranges() {
+    let options = ConversionOptions {
+        value_ranges: vec![ValueRange {
+            address: "/set/MIXER:Current/*/Fader/Level".to_string(),
+            min: -32768.0,
+            max: 1000.0,
+        }],
+        ..Default::default()
+    };
+    let fader = |value| OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), value],
+    };
+
+    assert_eq!(
+        osc_to_rcp_with(&fader(OscType::Int(1200)), &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 1000"
+    );
+    assert_eq!(
+        osc_to_rcp_with(&fader(OscType::Float(-40000.0)), &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -32768.0"
+    );
+    assert_eq!(
+        osc_to_rcp_with(&fader(OscType::Int(-500)), &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -500"
+    );
+
+    // The original and clamped values are reported only when it changes
+    let mut msg = fader(OscType::Int(1200));
+    assert_eq!(
+        clamp_osc_value(&mut msg, &options),
+        Some((OscType::Int(1200), OscType::Int(1000)))
+    );
+    assert_eq!(msg.args[2], OscType::Int(1000));
+    assert_eq!(clamp_osc_value(&mut msg, &options), None);
+
+    // A trailing sequence number isn't the value
+    let numbered = ConversionOptions {
+        sequence_numbers: true,
+        ..options.clone()
+    };
+    let mut msg = fader(OscType::Int(1200));
+    msg.args.push(OscType::Long(7));
+    assert_eq!(
+        clamp_osc_value(&mut msg, &numbered),
+        Some((OscType::Int(1200), OscType::Int(1000)))
+    );
+    assert_eq!(msg.args[3], OscType::Long(7));
+
+    // Unconfigured parameters pass through
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/On".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(5)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/On 0 0 5"
+    );
+}
+
+#[test]
+fn test_parameter_mapping_arg_types() {
+    let options = ConversionOptions {
+        parameter_mappings: vec![ParameterMapping {
+            osc: "label".to_string(),
+            rcp: "MIXER:Current/InCh/Label/Name".to_string(),
+            arg_types: vec![ArgType::Int, ArgType::Int, ArgType::String],
+        }],
+        ..Default::default()
+    };
+
+    // The zero-padded label stays a string instead of becoming 7
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Label/Name 0 0 007".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(osc.addr, "/set/label");
+    assert_eq!(
+        osc.args,
+        vec![
+            OscType::Int(0),
+            OscType::Int(0),
+            OscType::String("007".to_string())
+        ]
+    );
+    assert_eq!(
+        osc_to_rcp_with(&osc, &options).unwrap(),
+        "set MIXER:Current/InCh/Label/Name 0 0 \"007\""
+    );
+    // Without the hint it reads as a number
+    assert_eq!(
+        rcp_to_osc("NOTIFY set MIXER:Current/InCh/Label/Name 0 0 007".to_string())
+            .unwrap()
+            .args[2],
+        OscType::Int(7)
+    );
+
+    let mapping: ParameterMapping = serde_json::from_str(
+        r#"{"osc": "label", "rcp": "MIXER:Current/InCh/Label/Name", "arg_types": ["int", "int", "string"]}"#,
+    )
+    .unwrap();
+    assert_eq!(mapping, options.parameter_mappings[0]);
+}
+
+#[test]
+fn test_parameter_mappings() {
+    let options = ConversionOptions {
+        parameter_mappings: vec![ParameterMapping {
+            osc: "fader".to_string(),
+            rcp: "MIXER:Current/InCh/Fader/Level".to_string(),
+            arg_types: Vec::new(),
+        }],
+        ..Default::default()
+    };
+
+    let msg = OscMessage {
+        addr: "/set/fader".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(osc.addr, "/set/fader");
+    assert_eq!(osc, msg);
+
+    // Unmapped parameters use their RCP path as usual
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(osc.addr, "/set/MIXER:Current/InCh/Fader/On");
+    assert_eq!(
+        osc_to_rcp_with(&osc, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/On 0 0 1"
+    );
+}
+
+#[test]
+fn test_bulk_get_expands_range() {
+    let options = ConversionOptions {
+        bulk_gets: vec![BulkGet {
+            address: "/get/ch/*/fader".to_string(),
+            rcp: "MIXER:Current/InCh/Fader/Level {} 0".to_string(),
+            from: 0,
+            to: 3,
+        }],
+        ..Default::default()
+    };
+
+    let expected = vec![
+        "get MIXER:Current/InCh/Fader/Level 0 0",
+        "get MIXER:Current/InCh/Fader/Level 1 0",
+        "get MIXER:Current/InCh/Fader/Level 2 0",
+        "get MIXER:Current/InCh/Fader/Level 3 0",
+    ];
+    assert_eq!(
+        expand_bulk_get("/get/ch/*/fader", &options).unwrap(),
+        expected
+    );
+    assert_eq!(expand_bulk_get("/get/ch/1/fader", &options), None);
+
+    // In a bundle the expanded commands keep their place among the others
+    let bundle = OscPacket::Bundle(OscBundle {
+        timetag: OscTime::from((0, 1)),
+        content: vec![
+            OscPacket::Message(OscMessage {
+                addr: "/get/ch/*/fader".to_string(),
+                args: vec![],
+            }),
+            OscPacket::Message(OscMessage {
+                addr: "/get/MIXER:Current/InCh/Fader/On".to_string(),
+                args: vec![OscType::Int(0), OscType::Int(0)],
+            }),
+        ],
+    });
+    let mut with_on = expected;
+    with_on.push("get MIXER:Current/InCh/Fader/On 0 0");
+    assert_eq!(osc_packet_to_rcp_with(&bundle, &options).unwrap(), with_on);
+}
+
+#[test]
+fn test_address_alias_round_trip() {
+    let options = ConversionOptions {
+        address_aliases: vec![
+            AddressAlias {
+                osc: "/ch/{}/fader".to_string(),
+                rcp: "MIXER:Current/InCh/Fader/Level {} 0".to_string(),
+                index_base: 1,
+            },
+            AddressAlias {
+                osc: "/ch/{}/send/{}".to_string(),
+                rcp: "MIXER:Current/InCh/ToMix/Level {} {}".to_string(),
+                index_base: 1,
+            },
+        ],
+        ..Default::default()
+    };
+
+    let fader = OscMessage {
+        addr: "/ch/1/fader".to_string(),
+        args: vec![OscType::Int(-1000)],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&fader, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    let feedback = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(feedback, fader);
+
+    // Without a value the alias is read with a get, and the reply comes
+    // back on it too
+    let query = OscMessage {
+        addr: "/ch/12/fader".to_string(),
+        args: vec![],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&query, &options).unwrap(),
+        "get MIXER:Current/InCh/Fader/Level 11 0"
+    );
+    let reply = rcp_to_osc_with(
+        "OK get MIXER:Current/InCh/Fader/Level 11 0 -500".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(reply.addr, "/ch/12/fader");
+    assert_eq!(reply.args, vec![OscType::Int(-500)]);
+
+    // Several indices are captured in order
+    let send = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/ToMix/Level 2 5 -300".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(send.addr, "/ch/3/send/6");
+    assert_eq!(
+        osc_to_rcp_with(&send, &options).unwrap(),
+        "set MIXER:Current/InCh/ToMix/Level 2 5 -300"
+    );
+
+    // Anything else is converted as usual
+    let other = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 1 -1000".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(other.addr, "/set/MIXER:Current/InCh/Fader/Level");
+    let other = OscMessage {
+        addr: "/ch/one/fader".to_string(),
+        args: vec![OscType::Int(0)],
+    };
+    assert_eq!(osc_to_rcp_with(&other, &options).unwrap(), "ch one/fader 0");
+}
+
+#[test]
+fn test_source_tag() {
+    let options = ConversionOptions {
+        source_tag: "foh".to_string(),
+        value_ranges: vec![ValueRange {
+            address: "/set/MIXER:Current/*/Fader/Level".to_string(),
+            min: -32768.0,
+            max: 1000.0,
+        }],
+        ..Default::default()
+    };
+
+    // Appended to messages from the console
+    let mut osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        osc.args,
+        vec![
+            OscType::Int(0),
+            OscType::Int(0),
+            OscType::Int(-1000),
+            OscType::String("foh".to_string()),
+        ]
+    );
+
+    // Dropped on the way back, and not mistaken for the value to clamp
+    assert_eq!(
+        osc_to_rcp_with(&osc, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    let mut loud = osc.clone();
+    loud.args[2] = OscType::Int(2000);
+    assert_eq!(
+        clamp_osc_value(&mut loud, &options),
+        Some((OscType::Int(2000), OscType::Int(1000)))
+    );
+    assert_eq!(
+        osc_to_rcp_with(&loud, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 1000"
+    );
+
+    // Untagged messages are converted as usual, as is any other string
+    osc.args.pop();
+    assert_eq!(
+        osc_to_rcp_with(&osc, &options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -1000"
+    );
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Label/Name".to_string(),
+        args: vec![
+            OscType::Int(0),
+            OscType::Int(0),
+            OscType::String("mon".to_string()),
+        ],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "set MIXER:Current/InCh/Label/Name 0 0 \"mon\""
+    );
+}
+
+#[test]
+fn test_arg_group_round_trips_pan_pair() {
+    let options = ConversionOptions {
+        arg_groups: vec![ArgGroup {
+            address: "/set/MIXER:Current/InCh/ToSt/Pan".to_string(),
+            start: 2,
+            len: 2,
+        }],
+        ..Default::default()
+    };
+
+    // The value and flag are sent as one array
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/ToSt/Pan 0 0 -20 1".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        osc.args,
+        vec![
+            OscType::Int(0),
+            OscType::Int(0),
+            OscType::Array(OscArray {
+                content: vec![OscType::Int(-20), OscType::Int(1)],
+            }),
+        ]
+    );
+
+    // And split back into separate arguments
+    assert_eq!(
+        osc_to_rcp_with(&osc, &options).unwrap(),
+        "set MIXER:Current/InCh/ToSt/Pan 0 0 -20 1"
+    );
+
+    // Too few arguments to group are left alone
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/ToSt/Pan 0 0 -20".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(osc.args[2], OscType::Int(-20));
+}
+
+#[test]
+fn test_list_addresses() {
+    let options = ConversionOptions {
+        list_addresses: vec!["/set/MIXER:Current/*/Assign".to_string()],
+        ..Default::default()
+    };
+
+    // Expanded into an array of typed values
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Assign 0 0 1,2,3".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        osc.args,
+        vec![
+            OscType::Int(0),
+            OscType::Int(0),
+            OscType::Array(OscArray {
+                content: vec![OscType::Int(1), OscType::Int(2), OscType::Int(3)],
+            }),
+        ]
+    );
+
+    // And joined with commas on the way back
+    assert_eq!(
+        osc_to_rcp_with(&osc, &options).unwrap(),
+        "set MIXER:Current/InCh/Assign 0 0 1,2,3"
+    );
+
+    // Quoted strings are left alone
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Assign 0 0 \"a,b\"".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(osc.args[2], OscType::String("\"a,b\"".to_string()));
+
+    // Other addresses keep the token as a string, and flatten arrays
+    let osc = rcp_to_osc_with(
+        "NOTIFY set MIXER:Current/InCh/Other 0 0 1,2,3".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(osc.args[2], OscType::String("1,2,3".to_string()));
+    let msg = OscMessage {
+        addr: "/set/MIXER:Current/InCh/Other".to_string(),
+        args: vec![OscType::Array(OscArray {
+            content: vec![OscType::Int(1), OscType::Int(2)],
+        })],
+    };
+    assert_eq!(
+        osc_to_rcp_with(&msg, &options).unwrap(),
+        "set MIXER:Current/InCh/Other 1 2"
+    );
+}
+
+#[test]
+fn test_rcp_command_builder() {
+    // Integers
+    assert_eq!(
+        RcpCommandBuilder::set("MIXER:Current/Channel/Fader/Level")
+            .args([0, 0, -3000])
+            .build()
+            .unwrap(),
+        "set MIXER:Current/Channel/Fader/Level 0 0 -3000"
+    );
+
+    // Floats keep their decimal point, or follow the options
+    let builder = RcpCommandBuilder::set("MIXER:Current/InCh/Fader/Level")
+        .args([0, 0])
+        .arg(-10.0f32);
+    assert_eq!(
+        builder.build().unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -10.0"
+    );
+    let options = ConversionOptions {
+        float_format: FloatFormat::IntegerWhenWhole,
+        ..Default::default()
+    };
+    assert_eq!(
+        builder.build_with(&options).unwrap(),
+        "set MIXER:Current/InCh/Fader/Level 0 0 -10"
+    );
+
+    // Strings are quoted once
+    assert_eq!(
+        RcpCommandBuilder::set("MIXER:Current/InCh/Label/Name")
+            .args([0, 0])
+            .arg("Lead Vox".to_string())
+            .build()
+            .unwrap(),
+        r#"set MIXER:Current/InCh/Label/Name 0 0 "Lead Vox""#
+    );
+    assert_eq!(
+        RcpCommandBuilder::new("ssrecall_ex")
+            .arg("\"scene_a\"".to_string())
+            .arg(5)
+            .build()
+            .unwrap(),
+        r#"ssrecall_ex "scene_a" 5"#
+    );
+
+    // Bare commands and gets
+    assert_eq!(
+        RcpCommandBuilder::new("devstatus").build().unwrap(),
+        "devstatus"
+    );
+    assert_eq!(
+        RcpCommandBuilder::get("MIXER:Current/InCh/Fader/On")
+            .args([0, 0])
+            .build()
+            .unwrap(),
+        "get MIXER:Current/InCh/Fader/On 0 0"
+    );
+
+    // Arguments RCP can't take are an error
+    assert_eq!(
+        RcpCommandBuilder::set("MIXER:Current/InCh/Fader/Level")
+            .arg(f32::NAN)
+            .build()
+            .map_err(|e| matches!(e, ConversionError::NonFiniteFloat(_))),
+        Err(true)
+    );
+}
+
+#[test]
+fn test_rcp_line_framer() {
+    let mut framer = RcpLineFramer::new();
+
+    // Lines split across chunks, including between \r and \n and in the
+    // middle of a multi-byte character
+    let mut lines: Vec<String> = framer.push(b"NOTIFY set A 0 0 1\nNOTIFY set B").collect();
+    lines.extend(framer.push(b" 0 0 \"Caf\xc3"));
+    assert_eq!(lines, vec!["NOTIFY set A 0 0 1"]);
+    lines.extend(framer.push(b"\xa9\"\r"));
+    lines.extend(framer.push(b"\n\nOK devstatus"));
+    assert_eq!(
+        lines,
+        vec!["NOTIFY set A 0 0 1", "NOTIFY set B 0 0 \"Caf\u{e9}\"", ""]
+    );
+
+    // The partial line is only returned once complete
+    assert_eq!(framer.push(b" runmode").count(), 0);
+    assert_eq!(
+        framer.push(b" \"normal\"\n").collect::<Vec<_>>(),
+        vec!["OK devstatus runmode \"normal\""]
+    );
+
+    // Clearing drops the partial line
+    assert_eq!(framer.push(b"NOTIFY set").count(), 0);
+    framer.clear();
+    assert_eq!(framer.push(b"OK set\n").collect::<Vec<_>>(), vec!["OK set"]);
+
+    // Without quote awareness a quoted newline ends the line
+    assert_eq!(
+        framer.push(b"\"a\nb\"\n").collect::<Vec<_>>(),
+        vec!["\"a", "b\""]
+    );
+}
+
+#[test]
+fn test_rcp_line_framer_raw() {
+    let mut framer = RcpLineFramer::new();
+
+    // Invalid UTF-8 is returned as is, rather than replaced
+    let lines: Vec<Vec<u8>> = framer.push_raw(b"NOTIFY \xff\r\nOK set\n").collect();
+    assert_eq!(lines, vec![b"NOTIFY \xff".to_vec(), b"OK set".to_vec()]);
+    assert_eq!(
+        framer.push(b"NOTIFY \xff\n").collect::<Vec<_>>(),
+        vec!["NOTIFY \u{fffd}"]
+    );
+}
+
+#[test]
+fn test_rcp_line_framer_quote_aware() {
+    let mut framer = RcpLineFramer::quote_aware();
+    let mut lines: Vec<String> = framer
+        .push(b"NOTIFY set MIXER:Current/Scene/Comment 0 0 \"Act 1\n")
+        .collect();
+    assert!(lines.is_empty());
+    lines.extend(framer.push(b"Opening\"\nNOTIFY set A 0 0 1\n"));
+    assert_eq!(
+        lines,
+        vec![
+            "NOTIFY set MIXER:Current/Scene/Comment 0 0 \"Act 1\nOpening\"",
+            "NOTIFY set A 0 0 1",
+        ]
+    );
+}
+
+#[test]
+fn test_log_format() {
+    assert_eq!(
+        LogFormat::Text.format(LogLevel::Info, "Connected"),
+        "[INFO] Connected"
+    );
+
+    let parse = |line: String| serde_json::from_str::<serde_json::Value>(&line).unwrap();
+    let record = parse(LogFormat::Json.format(LogLevel::Info, "Connected"));
+    assert_eq!(record["level"], "INFO");
+    assert_eq!(record["message"], "Connected");
+    assert!(record["timestamp"].as_str().unwrap().ends_with('Z'));
+    assert!(record.get("direction").is_none());
+
+    let record = parse(LogFormat::Json.format(
+        LogLevel::Debug,
+        "Received RCP: NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1",
+    ));
+    assert_eq!(record["direction"], "rcp->osc");
+    assert_eq!(
+        record["raw"],
+        "NOTIFY set MIXER:Current/InCh/Fader/On 0 0 1"
+    );
+
+    let record = parse(LogFormat::Json.format(
+        LogLevel::Debug,
+        "Received OSC: /get/MIXER:Current/InCh/Fader/On, Int(0), Int(0)",
+    ));
+    assert_eq!(record["direction"], "osc->rcp");
+    assert_eq!(record["osc_address"], "/get/MIXER:Current/InCh/Fader/On");
+
+    assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+    assert!("xml".parse::<LogFormat>().is_err());
+}
+
+#[test]
+fn test_log_format_colored() {
+    assert_eq!(
+        LogFormat::Text.format_colored(LogLevel::Warn, "Reconnecting"),
+        "\x1b[33m[WARN]\x1b[0m Reconnecting"
+    );
+    assert_eq!(
+        LogFormat::Text.format_colored(LogLevel::Debug, "Received RCP: OK set X 0 0 1"),
+        "\x1b[2m[DEBUG]\x1b[0m \x1b[36mReceived RCP: OK set X 0 0 1\x1b[0m"
+    );
+    assert_eq!(
+        LogFormat::Text.format_colored(LogLevel::Debug, "Sending RCP: set X 0 0 1"),
+        "\x1b[2m[DEBUG]\x1b[0m \x1b[35mSending RCP: set X 0 0 1\x1b[0m"
+    );
+
+    // JSON is left alone
+    assert!(
+        !LogFormat::Json
+            .format_colored(LogLevel::Error, "Disconnected")
+            .contains('\x1b')
+    );
+
+    assert!(ColorMode::Auto.enabled(true));
+    assert!(!ColorMode::Auto.enabled(false));
+    assert!(ColorMode::Always.enabled(false));
+    assert!(!ColorMode::Never.enabled(true));
+    assert_eq!("always".parse::<ColorMode>(), Ok(ColorMode::Always));
+    assert!("sometimes".parse::<ColorMode>().is_err());
+}
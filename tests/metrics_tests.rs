@@ -0,0 +1,118 @@
+#![cfg(feature = "metrics")]
+
+use rosc::{OscMessage, OscPacket, OscType};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use yamaha_rcp_to_osc::{Bridge, BridgeConfig, MockTransport};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn get(addr: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            format!(
+                "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+                path, addr
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = format!("127.0.0.1:{}", port);
+
+    let (transport, mut handle) = MockTransport::pair();
+    let bridge = Bridge::new(
+        BridgeConfig {
+            metrics_addr: addr.clone(),
+            detect_model: false,
+            ..Default::default()
+        },
+        transport,
+        Box::new(|_, _| {}),
+    );
+    tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    handle.send_rcp("BOGUS line");
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -500");
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+
+    let response = timeout(TIMEOUT, get(&addr, "/metrics")).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("\nbridge_rcp_to_osc_total 2\n"));
+    assert!(response.contains("\nbridge_osc_to_rcp_total 0\n"));
+    assert!(response.contains("\nbridge_conversion_errors_total 1\n"));
+    assert!(response.contains("\nbridge_connected 1\n"));
+
+    let response = timeout(TIMEOUT, get(&addr, "/other")).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+#[tokio::test]
+async fn test_latency_histograms() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = format!("127.0.0.1:{}", port);
+
+    let (transport, mut handle) = MockTransport::pair();
+    let bridge = Bridge::new(
+        BridgeConfig {
+            metrics_addr: addr.clone(),
+            measure_latency: true,
+            detect_model: false,
+            ..Default::default()
+        },
+        transport,
+        Box::new(|_, _| {}),
+    );
+    tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    handle.send_osc(OscPacket::Message(OscMessage {
+        addr: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+        args: vec![OscType::Int(0), OscType::Int(0), OscType::Int(-1000)],
+    }));
+    timeout(TIMEOUT, handle.recv_rcp()).await.unwrap().unwrap();
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+
+    // Each is recorded just after the write, so poll until both are in
+    let recorded = async {
+        loop {
+            let response = get(&addr, "/metrics").await;
+            if response.contains("\nbridge_osc_to_rcp_latency_seconds_count 1\n")
+                && response.contains("\nbridge_rcp_to_osc_latency_seconds_count 1\n")
+            {
+                return response;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    let response = timeout(TIMEOUT, recorded).await.unwrap();
+    assert!(response.contains("# TYPE bridge_osc_to_rcp_latency_seconds histogram\n"));
+    assert!(response.contains("\nbridge_osc_to_rcp_latency_seconds_bucket{le=\"+Inf\"} 1\n"));
+}
@@ -0,0 +1,137 @@
+use proptest::prelude::*;
+use rosc::{OscMessage, OscType};
+use yamaha_rcp_to_osc::{canonicalize_rcp, osc_to_rcp, rcp_to_osc};
+
+/// A single RCP argument as the console would send it: an integer, a
+/// decimal, a bare word or a quoted string.
+fn rcp_arg() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<i32>().prop_map(|i| i.to_string()),
+        (-100000i32..100000, 1u32..1000).prop_map(|(i, f)| format!("{}.{}", i, f)),
+        "[A-Za-z_][A-Za-z0-9_]{0,8}",
+        "[A-Za-z0-9_ ]{0,12}".prop_map(|s| format!("\"{}\"", s)),
+    ]
+}
+
+/// An RCP parameter address such as `MIXER:Current/Channel/Fader/Level`.
+fn rcp_address() -> impl Strategy<Value = String> {
+    prop::collection::vec("[A-Za-z][A-Za-z0-9_]{0,8}", 1..5).prop_map(|segments| {
+        let (module, path) = segments.split_first().unwrap();
+        if path.is_empty() {
+            module.clone()
+        } else {
+            format!("{}:{}", module, path.join("/"))
+        }
+    })
+}
+
+/// An OSC argument of a type the bridge can convert to RCP.
+fn osc_arg() -> impl Strategy<Value = OscType> {
+    prop_oneof![
+        any::<i32>().prop_map(OscType::Int),
+        any::<f32>()
+            .prop_filter("finite", |f| f.is_finite())
+            .prop_map(OscType::Float),
+        "[A-Za-z0-9_ ]{0,12}".prop_map(OscType::String),
+    ]
+}
+
+/// Converts an OSC message to RCP and back, as if the console echoed the
+/// command in a NOTIFY.
+fn osc_round_trip(msg: &OscMessage) -> OscMessage {
+    let rcp = osc_to_rcp(msg).unwrap();
+    rcp_to_osc(format!("NOTIFY {}", rcp)).unwrap()
+}
+
+proptest! {
+    #[test]
+    fn rcp_round_trip_is_stable(
+        verb in "[a-z]{1,8}",
+        address in rcp_address(),
+        args in prop::collection::vec(rcp_arg(), 0..6),
+    ) {
+        let mut line = format!("NOTIFY {} {}", verb, address);
+        for arg in &args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+
+        // The first pass canonicalizes the line; after that, further round
+        // trips must not change it
+        let canonical = osc_to_rcp(&rcp_to_osc(line).unwrap()).unwrap();
+        let again = osc_to_rcp(&rcp_to_osc(format!("NOTIFY {}", canonical)).unwrap()).unwrap();
+        prop_assert_eq!(&again, &canonical);
+        let prefix = format!("{} {}", verb, address);
+        prop_assert!(canonical.starts_with(&prefix));
+        prop_assert!(!canonical.ends_with(' '));
+    }
+
+    #[test]
+    fn osc_round_trip_is_stable(
+        segments in prop::collection::vec("[A-Za-z][A-Za-z0-9_:]{0,8}", 2..6),
+        args in prop::collection::vec(osc_arg(), 0..6),
+    ) {
+        // A bare module segment gains a colon; see the test below
+        prop_assume!(
+            segments.len() < 3 || !segments[1].chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        );
+        let msg = OscMessage {
+            addr: format!("/{}", segments.join("/")),
+            args,
+        };
+
+        let once = osc_round_trip(&msg);
+        prop_assert_eq!(&once.addr, &msg.addr);
+        prop_assert_eq!(once.args.len(), msg.args.len());
+
+        // Numbers survive exactly
+        for (original, converted) in msg.args.iter().zip(&once.args) {
+            match original {
+                OscType::Int(_) | OscType::Float(_) => prop_assert_eq!(original, converted),
+                _ => {}
+            }
+        }
+
+        // Strings may be canonicalized on the first pass, but not after
+        prop_assert_eq!(osc_round_trip(&once), once);
+    }
+
+    #[test]
+    fn module_segment_round_trip(
+        verb in "[a-z]{1,8}",
+        module in "[A-Z][A-Z0-9_]{0,6}",
+        path in prop::collection::vec("[A-Za-z][A-Za-z0-9_]{0,8}", 1..6),
+        args in prop::collection::vec(osc_arg(), 0..4),
+    ) {
+        let msg = OscMessage {
+            addr: format!("/{}/{}/{}", verb, module, path.join("/")),
+            args,
+        };
+        let canonical = format!("/{}/{}:{}", verb, module, path.join("/"));
+
+        // Converted to the colon form, which then round trips unchanged
+        let rcp = osc_to_rcp(&msg).unwrap();
+        prop_assert!(rcp.starts_with(&canonical[1..].replacen('/', " ", 1)));
+        let once = osc_round_trip(&msg);
+        prop_assert_eq!(&once.addr, &canonical);
+        prop_assert_eq!(osc_round_trip(&once), once);
+    }
+
+    #[test]
+    fn canonicalize_rcp_is_idempotent(
+        verb in "[a-z]{1,8}",
+        address in rcp_address(),
+        args in prop::collection::vec(rcp_arg(), 0..6),
+        spacing in prop::collection::vec(" {1,3}", 8),
+    ) {
+        let mut line = format!("NOTIFY{}{}{}{}", spacing[0], verb, spacing[1], address);
+        for (arg, space) in args.iter().zip(&spacing[2..]) {
+            line.push_str(space);
+            line.push_str(arg);
+        }
+
+        let canonical = canonicalize_rcp(&line).unwrap();
+        prop_assert!(!canonical.contains("  ") || canonical.contains('"'));
+        prop_assert_eq!(canonicalize_rcp(&canonical).unwrap(), canonical);
+    }
+}
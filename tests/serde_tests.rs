@@ -0,0 +1,32 @@
+#![cfg(feature = "serde")]
+
+use rosc::{OscArray, OscType};
+use yamaha_rcp_to_osc::{ConversionError, RcpMessage};
+
+#[test]
+fn test_rcp_message_json_round_trip() {
+    let message =
+        RcpMessage::parse("NOTIFY set MIXER:Current/InCh/Label/Name 0 0 \"Vocal 1\"").unwrap();
+    let json = serde_json::to_string(&message).unwrap();
+    assert_eq!(serde_json::from_str::<RcpMessage>(&json).unwrap(), message);
+}
+
+#[test]
+fn test_conversion_error_json_round_trip() {
+    for error in [
+        ConversionError::TooFewParts,
+        ConversionError::MissingPrefix {
+            address: "/set/MIXER:Current/InCh/Fader/Level".to_string(),
+            prefix: "/foh".to_string(),
+        },
+        ConversionError::UnsupportedArgType(OscType::Array(OscArray {
+            content: vec![OscType::Bool(true), OscType::Char('x')],
+        })),
+    ] {
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ConversionError>(&json).unwrap(),
+            error
+        );
+    }
+}
@@ -0,0 +1,65 @@
+#![cfg(all(feature = "systemd", unix))]
+
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+use tokio::time::timeout;
+use yamaha_rcp_to_osc::{Bridge, BridgeConfig, MockTransport};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn test_systemd_notify() {
+    let path = std::env::temp_dir().join(format!("bridge-notify-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let socket = tokio::net::UnixDatagram::from_std({
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        socket
+    })
+    .unwrap();
+    // SAFETY: this is the only test in this binary, and the bridge that
+    // reads the variables hasn't been started yet
+    unsafe {
+        std::env::set_var("NOTIFY_SOCKET", &path);
+        std::env::set_var("WATCHDOG_USEC", "100000");
+        std::env::remove_var("WATCHDOG_PID");
+    }
+
+    let (transport, mut handle) = MockTransport::pair();
+    let bridge = Bridge::new(
+        BridgeConfig {
+            detect_model: false,
+            ..Default::default()
+        },
+        transport,
+        Box::new(|_, _| {}),
+    );
+    tokio::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.run().await }
+    });
+
+    let mut buf = [0u8; 64];
+    let len = timeout(TIMEOUT, socket.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&buf[..len], b"READY=1");
+
+    // The watchdog is only notified once something has been read
+    assert!(
+        timeout(Duration::from_millis(200), socket.recv(&mut buf))
+            .await
+            .is_err()
+    );
+    handle.send_rcp("NOTIFY set MIXER:Current/InCh/Fader/Level 0 0 -1000");
+    timeout(TIMEOUT, handle.recv_osc()).await.unwrap().unwrap();
+    let len = timeout(TIMEOUT, socket.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&buf[..len], b"WATCHDOG=1");
+
+    bridge.shutdown();
+    let _ = std::fs::remove_file(&path);
+}